@@ -0,0 +1,146 @@
+//! Conversion throughput on deeply nested and wide values, comparing the iterative converter
+//! against the recursive one it replaced, and of whole containers with and without `--jobs`.
+//!
+//! Run with `cargo bench`. To compare a change against the current code, save a baseline
+//! first with `cargo bench -- --save-baseline before`, then run `cargo bench -- --baseline
+//! before` with the change applied.
+
+use apache_avro::types::Value as AvroValue;
+use apache_avro::Schema;
+use avro_to_json::{
+    avro_value_to_json, avro_value_to_json_recursive, avro_value_to_json_with_schema,
+    convert_bytes_to_json_string_with_options, ConvertOptions, JsonOptions,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+/// Arrays nested `depth` levels deep, each also holding a few scalars.
+fn nested_arrays(depth: usize) -> AvroValue {
+    let mut value = AvroValue::Array(vec![AvroValue::Long(1), AvroValue::String("leaf".to_string())]);
+    for level in 1..depth {
+        value = AvroValue::Array(vec![AvroValue::Int(level as i32), value, AvroValue::Boolean(true)]);
+    }
+    value
+}
+
+/// A recursive `Node` record schema and a chain of `depth` nodes following it.
+fn nested_records(depth: usize) -> (Schema, AvroValue) {
+    let schema = Schema::parse_str(
+        r#"{"type": "record", "name": "Node", "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "label", "type": "string"},
+            {"name": "child", "type": ["null", "Node"]}
+        ]}"#,
+    )
+    .unwrap();
+    let mut value = AvroValue::Union(0, Box::new(AvroValue::Null));
+    for id in 0..depth {
+        value = AvroValue::Union(
+            1,
+            Box::new(AvroValue::Record(vec![
+                ("id".to_string(), AvroValue::Long(id as i64)),
+                ("label".to_string(), AvroValue::String(format!("node {}", id))),
+                ("child".to_string(), value),
+            ])),
+        );
+    }
+    let AvroValue::Union(_, node) = value else { unreachable!() };
+    (schema, *node)
+}
+
+/// A shallow record with many fields, each a small map of arrays.
+fn wide_record(fields: usize) -> AvroValue {
+    AvroValue::Record(
+        (0..fields)
+            .map(|field| {
+                let entries = (0..4)
+                    .map(|key| (format!("key{}", key), AvroValue::Array(vec![AvroValue::Int(field as i32); 4])))
+                    .collect();
+                (format!("field{}", field), AvroValue::Map(entries))
+            })
+            .collect(),
+    )
+}
+
+/// Times the iterative converter that [`avro_value_to_json`] uses against the recursive one it
+/// replaced, on the same value.
+fn compare(c: &mut Criterion, name: &str, value: &AvroValue, schema: Option<&Schema>) {
+    let options = JsonOptions::default();
+    let mut group = c.benchmark_group(name);
+    group.bench_function("iterative", |b| {
+        b.iter(|| match schema {
+            Some(schema) => avro_value_to_json_with_schema(black_box(value), schema).unwrap(),
+            None => avro_value_to_json(black_box(value)).unwrap(),
+        })
+    });
+    group.bench_function("recursive", |b| {
+        b.iter(|| avro_value_to_json_recursive(black_box(value), schema, &options).unwrap())
+    });
+    group.finish();
+}
+
+/// A container of `records` order records mixing strings, logical types and collections.
+fn orders_container(records: i64) -> Vec<u8> {
+    let schema = Schema::parse_str(
+        r#"{"type": "record", "name": "Order", "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "customer", "type": "string"},
+            {"name": "total", "type": {"type": "bytes", "logicalType": "decimal", "precision": 12, "scale": 2}},
+            {"name": "placed_at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+            {"name": "note", "type": ["null", "string"]},
+            {"name": "tags", "type": {"type": "array", "items": "string"}},
+            {"name": "attributes", "type": {"type": "map", "values": "long"}}
+        ]}"#,
+    )
+    .unwrap();
+    let mut writer = apache_avro::Writer::new(&schema, Vec::new());
+    for id in 0..records {
+        let note = match id % 3 {
+            0 => AvroValue::Union(0, Box::new(AvroValue::Null)),
+            _ => AvroValue::Union(1, Box::new(AvroValue::String(format!("deliver after {}:00", id % 24)))),
+        };
+        let record = AvroValue::Record(vec![
+            ("id".to_string(), AvroValue::Long(id)),
+            ("customer".to_string(), AvroValue::String(format!("customer-{}", id % 1000))),
+            ("total".to_string(), AvroValue::Decimal(apache_avro::Decimal::from((id * 137).to_be_bytes()))),
+            ("placed_at".to_string(), AvroValue::TimestampMillis(1_700_000_000_000 + id * 1000)),
+            ("note".to_string(), note),
+            ("tags".to_string(), AvroValue::Array(vec![AvroValue::String("retail".to_string()); 3])),
+            (
+                "attributes".to_string(),
+                AvroValue::Map((0..4).map(|key| (format!("attr{}", key), AvroValue::Long(id + key))).collect()),
+            ),
+        ]);
+        writer.append(record).unwrap();
+    }
+    writer.into_inner().unwrap()
+}
+
+/// Converting a whole container on one thread and with `--jobs`.
+fn bench_jobs(c: &mut Criterion) {
+    let container = orders_container(20_000);
+    let mut group = c.benchmark_group("20000 orders");
+    group.sample_size(10);
+    for jobs in [1, 2, 4] {
+        let options = ConvertOptions {
+            jobs: Some(jobs),
+            ..Default::default()
+        };
+        group.bench_function(format!("jobs {}", jobs), |b| {
+            b.iter(|| convert_bytes_to_json_string_with_options(black_box(&container), &options).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_convert(c: &mut Criterion) {
+    compare(c, "nested arrays, depth 200", &nested_arrays(200), None);
+
+    let (schema, records) = nested_records(200);
+    compare(c, "nested records with schema, depth 200", &records, Some(&schema));
+
+    compare(c, "wide record, 1000 fields", &wide_record(1000), None);
+}
+
+criterion_group!(benches, bench_convert, bench_jobs);
+criterion_main!(benches);
@@ -0,0 +1,167 @@
+//! Error type returned by the library.
+
+use std::fmt::Display;
+use std::io;
+use thiserror::Error;
+
+/// Everything that can go wrong while converting Avro to JSON.
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    /// Reading the input or writing the output failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// An input URL could not be fetched, or the server answered with an error status.
+    #[cfg(feature = "http")]
+    #[error("Failed to fetch {url}: {reason}")]
+    Http { url: String, reason: String },
+    /// The input is not valid Avro, or a record failed to decode.
+    #[error("Failed to read Avro data: {0}")]
+    AvroRead(#[from] apache_avro::Error),
+    /// Records could not be written as Avro.
+    #[error("Failed to write Avro data: {0}")]
+    AvroWrite(#[source] apache_avro::Error),
+    /// JSON input to [`convert_json_to_avro`](crate::convert_json_to_avro) could not be parsed.
+    #[error("Failed to read JSON: {0}")]
+    InvalidJson(#[source] serde_json::Error),
+    /// A JSON value can't be written as Avro with the schema it was given.
+    #[error("JSON value does not match the schema: {0}")]
+    JsonMismatch(String),
+    /// A value could not be serialized as JSON.
+    #[error("Failed to serialize JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The container is compressed with a codec this build can't decompress.
+    #[error(
+        "file uses {codec} codec{}",
+        match feature {
+            Some(feature) => format!("; rebuild with the {} feature", feature),
+            None => ", which is not supported".to_string(),
+        }
+    )]
+    UnsupportedCodec { codec: String, feature: Option<&'static str> },
+    /// Records could not be written as CSV.
+    #[error("Failed to write CSV: {0}")]
+    Csv(#[from] csv::Error),
+    /// Records could not be written as YAML.
+    #[cfg(feature = "yaml")]
+    #[error("Failed to write YAML: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    /// Records could not be written as TOML.
+    #[cfg(feature = "toml")]
+    #[error("Failed to write TOML: {0}")]
+    Toml(#[from] toml::ser::Error),
+    /// Records could not be written as MessagePack.
+    #[cfg(feature = "msgpack")]
+    #[error("Failed to write MessagePack: {0}")]
+    MessagePack(#[from] rmp_serde::encode::Error),
+    /// A decoded record does not conform to the writer schema.
+    #[error("Record {index} does not match the writer schema: {reason}")]
+    InvalidRecord { index: u64, reason: String },
+    /// A length-prefixed frame (see [`ConvertOptions::framed_schema`](crate::ConvertOptions::framed_schema))
+    /// is cut short, or its datum doesn't fill it.
+    #[error("Invalid frame at byte {offset}: {reason}")]
+    InvalidFrame { offset: u64, reason: String },
+    /// A container input ends partway through a block, as a file cut short by an interrupted
+    /// copy or upload does, rather than after its last complete block. `records` counts those
+    /// read from it before the cut, and `source` is the decode error that exposed it. Only
+    /// raised for container files; single-object and framed streams fail as usual. The output
+    /// is still completed with the records read before the cut, and later inputs are not read.
+    #[error(
+        "{input} is truncated: {kind}, after {records} complete record{}",
+        if *records == 1 { "" } else { "s" }
+    )]
+    Truncated { input: String, kind: Truncation, records: u64, source: Box<ConvertError> },
+    /// A record decoded fine but could not be converted to JSON.
+    #[error("Failed to convert record {index}: {source}")]
+    Record { index: u64, source: Box<ConvertError> },
+    /// A NaN or infinite float was found under [`FloatPolicy::Error`](crate::FloatPolicy::Error).
+    #[error("Non-finite float value {0}")]
+    NonFiniteFloat(f64),
+    /// A string passed to [`base64_decode`](crate::base64_decode) is not valid base64.
+    #[error("Invalid base64: {0}")]
+    InvalidBase64(String),
+    /// A date or timestamp format string is invalid, or asks for fields the value doesn't have.
+    #[error("Invalid date/time format string {0:?}")]
+    InvalidFormat(String),
+    /// A logical type that the converter cannot render.
+    #[error("Unsupported logical type: {0}")]
+    UnsupportedLogicalType(String),
+    /// A record has no value at the pointer that [`KeyBy`](crate::KeyBy) keys records by.
+    #[error("Record {index} has no value at {pointer} to key it by")]
+    MissingKey { index: u64, pointer: String },
+    /// Two records have the same key under [`KeyBy`](crate::KeyBy) without `last_wins`.
+    #[error("More than one record has the key {0}")]
+    DuplicateKey(String),
+    /// Output that has to be held in memory until complete, such as CSV, grew past
+    /// [`ConvertOptions::max_records_in_memory`](crate::ConvertOptions::max_records_in_memory).
+    #[error(
+        "More than {0} records would have to be held in memory; write newline-delimited JSON or a JSON array, \
+         which are streamed, or raise the limit"
+    )]
+    TooManyRecordsInMemory(usize),
+    /// Partitioned output reached more distinct values than
+    /// [`PartitionBy::max_partitions`](crate::PartitionBy::max_partitions) allows.
+    #[error("Records have more than {0} distinct partition values; raise the limit or partition by another field")]
+    TooManyPartitions(usize),
+    /// A string holds U+FFFD, the replacement character left by decoding invalid UTF-8, under
+    /// [`JsonOptions::strict_utf8`](crate::JsonOptions::strict_utf8). `field` is the path to
+    /// the string within its record, e.g. `/user/name`, and `position` the character's byte
+    /// offset in the string.
+    #[error(
+        "String {} holds a U+FFFD replacement character at byte {position}, a sign of invalid UTF-8",
+        if field.is_empty() { "value".to_string() } else { format!("at {}", field) }
+    )]
+    ReplacementCharacter { field: String, position: usize },
+    /// A value nests deeper than [`JsonOptions::max_depth`](crate::JsonOptions::max_depth) allows.
+    #[error("Value is nested more than {0} levels deep")]
+    MaxDepthExceeded(usize),
+    /// The options asked for something that can't be done, such as splitting output that has
+    /// no file to name the chunks after, or the inputs don't allow it.
+    #[error("{0}")]
+    InvalidOptions(String),
+}
+
+pub type Result<T, E = ConvertError> = std::result::Result<T, E>;
+
+/// Where a [`ConvertError::Truncated`] container was cut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum Truncation {
+    /// The container itself ends inside a block.
+    #[error("it ends partway through a block")]
+    Block,
+    /// The input is gzip'd, and the compressed stream ends before its trailer.
+    #[error("its gzip stream ends early")]
+    GzipStream,
+}
+
+/// Marks the decompressor's error for a gzip stream that ends early, so it can be told apart
+/// from a complete stream holding a cut container.
+#[derive(Debug, Error)]
+#[error("gzip stream ends early")]
+pub(crate) struct GzipStreamEnded;
+
+impl ConvertError {
+    /// How the input was cut short, when decoding failed because it ran out. apache-avro only
+    /// reports that as an I/O error with `UnexpectedEof` somewhere down the chain of causes,
+    /// or one carrying [`GzipStreamEnded`] for gzip'd input.
+    pub(crate) fn truncation(&self) -> Option<Truncation> {
+        let mut cause: Option<&(dyn std::error::Error + 'static)> = Some(self);
+        while let Some(err) = cause {
+            if let Some(err) = err.downcast_ref::<io::Error>() {
+                if err.get_ref().is_some_and(|inner| inner.is::<GzipStreamEnded>()) {
+                    return Some(Truncation::GzipStream);
+                }
+                if err.kind() == io::ErrorKind::UnexpectedEof {
+                    return Some(Truncation::Block);
+                }
+            }
+            cause = err.source();
+        }
+        None
+    }
+}
+
+/// Prefixes an I/O error with what was being attempted, keeping its `ErrorKind` so callers
+/// can still tell e.g. a missing file apart from a permission problem.
+pub(crate) fn io_error(context: impl Display, err: io::Error) -> ConvertError {
+    ConvertError::Io(io::Error::new(err.kind(), format!("{}: {}", context, err)))
+}
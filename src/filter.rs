@@ -0,0 +1,140 @@
+//! Deciding which converted records get written.
+
+use serde_json::Value;
+
+/// A single-condition record filter such as `/status==ACTIVE`: a JSON Pointer into the
+/// converted record, an operator, and a literal to compare against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    /// JSON Pointer (RFC 6901) to the compared value, e.g. `/address/city`.
+    pub pointer: String,
+    pub op: FilterOp,
+    /// Literal the value is compared with. Numbers, quoted strings, `true`, `false` and
+    /// `null` are parsed as JSON; anything else is taken as a bare string.
+    pub value: Value,
+}
+
+/// Comparison operator of a [`Filter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOp {
+    Eq,
+    Ne,
+}
+
+impl std::str::FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (position, op) = match (s.find("=="), s.find("!=")) {
+            (Some(eq), Some(ne)) if ne < eq => (ne, FilterOp::Ne),
+            (Some(eq), _) => (eq, FilterOp::Eq),
+            (None, Some(ne)) => (ne, FilterOp::Ne),
+            (None, None) => return Err(format!("expected `<pointer>==<value>` or `<pointer>!=<value>`, got `{}`", s)),
+        };
+        let pointer = s[..position].trim();
+        if !pointer.is_empty() && !pointer.starts_with('/') {
+            return Err(format!("expected a JSON Pointer starting with `/`, got `{}`", pointer));
+        }
+
+        let literal = s[position + 2..].trim();
+        let value = match serde_json::from_str::<Value>(literal) {
+            Ok(value) if !value.is_array() && !value.is_object() => value,
+            _ => Value::String(literal.to_string()),
+        };
+        Ok(Filter {
+            pointer: pointer.to_string(),
+            op,
+            value,
+        })
+    }
+}
+
+/// Whether a converted record satisfies `filter`. A pointer that doesn't resolve never
+/// matches, whichever the operator.
+///
+/// Numbers compare by value (`1` equals `1.0`), and a number literal also matches a string
+/// holding the same digits, so filters work on longs rendered as strings.
+pub fn record_matches(value: &Value, filter: &Filter) -> bool {
+    let Some(actual) = value.pointer(&filter.pointer) else {
+        return false;
+    };
+    let equal = match (actual, &filter.value) {
+        (Value::Number(actual), Value::Number(expected)) => actual.as_f64() == expected.as_f64(),
+        (Value::String(actual), Value::Number(expected)) => *actual == expected.to_string(),
+        (actual, expected) => actual == expected,
+    };
+    match filter.op {
+        FilterOp::Eq => equal,
+        FilterOp::Ne => !equal,
+    }
+}
+
+/// Drops records whose value at a JSON Pointer has already been seen, keeping the first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dedup {
+    /// JSON Pointer (RFC 6901) to the key, e.g. `/event_id`.
+    pub pointer: String,
+    /// Drop records where the pointer doesn't resolve instead of passing them through.
+    pub drop_missing: bool,
+}
+
+/// The key a record is deduplicated by: the serialized JSON at the pointer, so `1` and `"1"`
+/// are different keys. `None` when the pointer doesn't resolve.
+pub fn dedup_key(value: &Value, dedup: &Dedup) -> Option<String> {
+    value.pointer(&dedup.pointer).map(Value::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_filter() {
+        let filter: Filter = "/status==ACTIVE".parse().unwrap();
+        assert_eq!(filter.pointer, "/status");
+        assert_eq!(filter.op, FilterOp::Eq);
+        assert_eq!(filter.value, json!("ACTIVE"));
+
+        let filter: Filter = "/address/zip != 75001".parse().unwrap();
+        assert_eq!(filter.pointer, "/address/zip");
+        assert_eq!(filter.op, FilterOp::Ne);
+        assert_eq!(filter.value, json!(75001));
+
+        let filter: Filter = r#"/code=="42""#.parse().unwrap();
+        assert_eq!(filter.value, json!("42"));
+
+        assert!("status==ACTIVE".parse::<Filter>().is_err());
+        assert!("/status".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn test_record_matches() {
+        let record = json!({"status": "ACTIVE", "age": 28, "id": "9007199254740993", "address": {"city": "Paris"}});
+        let matches = |filter: &str| record_matches(&record, &filter.parse().unwrap());
+
+        assert!(matches("/status==ACTIVE"));
+        assert!(!matches("/status!=ACTIVE"));
+        assert!(matches("/age==28"));
+        assert!(matches("/age==28.0"));
+        assert!(!matches("/age==\"28\""));
+        assert!(matches("/id==9007199254740993"));
+        assert!(matches("/address/city==Paris"));
+
+        // Missing paths never match, even with `!=`
+        assert!(!matches("/email==x"));
+        assert!(!matches("/email!=x"));
+        assert!(!matches("/address/city/name==Paris"));
+    }
+
+    #[test]
+    fn test_dedup_key() {
+        let dedup = Dedup {
+            pointer: "/event/id".to_string(),
+            drop_missing: false,
+        };
+        assert_eq!(dedup_key(&json!({"event": {"id": 1}}), &dedup), Some("1".to_string()));
+        assert_eq!(dedup_key(&json!({"event": {"id": "1"}}), &dedup), Some("\"1\"".to_string()));
+        assert_eq!(dedup_key(&json!({"event": {}}), &dedup), None);
+    }
+}
@@ -0,0 +1,678 @@
+//! The reverse direction: JSON records written to an Avro container with a given schema.
+
+use crate::error::{io_error, ConvertError, Result};
+use crate::output::AtomicFile;
+use crate::profile::json_type;
+use crate::{
+    avro_type_name, base64_decode, read_schema_file, union_branch_name, Input, Names, SchemaContext,
+    DEFAULT_BUFFER_SIZE, FIXED_UUID_ATTRIBUTE, UNIX_EPOCH_DAYS_FROM_CE,
+};
+use apache_avro::schema::{DecimalSchema, Schema, SchemaKind, UnionSchema};
+use apache_avro::types::Value as AvroValue;
+use apache_avro::{Days, Decimal, Duration, Millis, Months, Writer};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime};
+use num_bigint::BigInt;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+/// Writes JSON records to an Avro container with the schema in `schema_file` (`.avsc`).
+/// The input is newline-delimited JSON, or any other run of JSON values, or a JSON array of
+/// records; `-` reads stdin. Without an output file the container goes to stdout.
+///
+/// Records are read back from the default rendering; see [`json_to_avro_value`].
+pub fn convert_json_to_avro(input_file: &str, schema_file: &str, output_file: Option<&String>) -> Result<()> {
+    let schema = read_schema_file(schema_file)?;
+    let reader = Input::from(input_file).open()?;
+    match output_file {
+        Some(output_path) => {
+            let mut file = AtomicFile::create(Path::new(output_path), DEFAULT_BUFFER_SIZE)?;
+            write_container(reader, &schema, &mut file)?;
+            file.commit()
+        }
+        None => {
+            let mut stdout = BufWriter::with_capacity(DEFAULT_BUFFER_SIZE, std::io::stdout());
+            write_container(reader, &schema, &mut stdout)?;
+            stdout.flush().map_err(|err| io_error("Failed to write Avro output", err))
+        }
+    }
+}
+
+/// Writes the JSON records read from `reader` to `output` as an Avro container, returning
+/// how many there were.
+fn write_container(reader: impl Read, schema: &Schema, output: &mut dyn Write) -> Result<u64> {
+    let context = SchemaContext::new(schema)?;
+    let mut writer = Writer::new(schema, output);
+    let mut index = 0;
+    for value in serde_json::Deserializer::from_reader(reader).into_iter::<Value>() {
+        let value = value.map_err(ConvertError::InvalidJson)?;
+        // A top-level array holds the records, unless the records are arrays themselves
+        let records = match value {
+            Value::Array(records) if !matches!(schema, Schema::Array(_)) => records,
+            record => vec![record],
+        };
+        for record in records {
+            let avro_value = to_avro(&record, schema, &context.names, false).map_err(|mismatch| ConvertError::Record {
+                index,
+                source: Box::new(ConvertError::JsonMismatch(mismatch.to_string())),
+            })?;
+            writer.append(avro_value).map_err(ConvertError::AvroWrite)?;
+            index += 1;
+        }
+    }
+    let output = writer.into_inner().map_err(ConvertError::AvroWrite)?;
+    // `Writer` only writes the header along with the first record
+    if index == 0 {
+        output.write_all(&empty_container(schema)?)?;
+    }
+    Ok(index)
+}
+
+/// A container with a header and no blocks.
+fn empty_container(schema: &Schema) -> Result<Vec<u8>> {
+    let metadata = HashMap::from([
+        ("avro.schema".to_string(), AvroValue::Bytes(serde_json::to_vec(schema)?)),
+        ("avro.codec".to_string(), AvroValue::Bytes(b"null".to_vec())),
+    ]);
+    let mut container = b"Obj\x01".to_vec();
+    container.extend(
+        apache_avro::to_avro_datum(&Schema::Map(Box::new(Schema::Bytes)), AvroValue::Map(metadata))
+            .map_err(ConvertError::AvroWrite)?,
+    );
+    // No block follows, so the sync marker is never looked for
+    container.extend(*uuid::Uuid::nil().as_bytes());
+    Ok(container)
+}
+
+/// Converts a JSON value into the Avro value `schema` describes, undoing the default
+/// rendering:
+///
+/// - `bytes` and `fixed` are read from base64, or from the objects `--bytes-verbose` writes
+/// - dates, times and timestamps are read from ISO-8601 strings or the tagged strings of
+///   `--legacy-logical-types`, and decimals from strings such as `"123.45"`; the numbers the
+///   logical types annotate are accepted too
+/// - durations are read from `{"months", "days", "millis"}` objects or ISO-8601 strings
+/// - a union value takes the first branch it fits, or the branch named by a single-key
+///   object such as `--union-mode tagged` writes
+/// - `long`s may be strings, as `--large-longs-as-string` writes them, and floats may be
+///   `"NaN"`, `"Infinity"` or `"-Infinity"`
+///
+/// A record field missing from the JSON gets its default from the schema. Keys that are
+/// not fields of the record are ignored.
+pub fn json_to_avro_value(json: &Value, schema: &Schema) -> Result<AvroValue> {
+    let context = SchemaContext::new(schema)?;
+    to_avro(json, schema, &context.names, false).map_err(|mismatch| ConvertError::JsonMismatch(mismatch.to_string()))
+}
+
+/// Why a JSON value can't be written with its schema, and where in the value it is.
+#[derive(Debug)]
+struct Mismatch {
+    /// JSON Pointer to the offending value; empty for the value itself.
+    path: String,
+    reason: String,
+}
+
+impl Mismatch {
+    fn new(reason: impl Into<String>) -> Self {
+        Mismatch {
+            path: String::new(),
+            reason: reason.into(),
+        }
+    }
+
+    fn expected(expected: &str, json: &Value) -> Self {
+        Mismatch::new(format!("expected {}, got {}", expected, json_type(json)))
+    }
+
+    /// Moves the mismatch one level down, under `segment`.
+    fn within(mut self, segment: &str) -> Self {
+        self.path = format!("/{}{}", segment.replace('~', "~0").replace('/', "~1"), self.path);
+        self
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.reason)
+        } else {
+            write!(f, "{} at {}", self.reason, self.path)
+        }
+    }
+}
+
+/// Converts `json` to the Avro value `schema` describes. A schema `default` is in the Avro
+/// JSON encoding instead of the default rendering: `bytes` and `fixed` values are strings
+/// with one code point per byte, logical types are the values they annotate, and a union
+/// value is always in its first branch.
+fn to_avro(json: &Value, schema: &Schema, names: &Names, default: bool) -> Result<AvroValue, Mismatch> {
+    let schema = match schema {
+        Schema::Ref { name } => names
+            .get(name)
+            .ok_or_else(|| Mismatch::new(format!("unknown type {}", name.fullname(None))))?,
+        schema => schema,
+    };
+    let value = match (schema, json) {
+        (Schema::Null, Value::Null) => AvroValue::Null,
+        (Schema::Boolean, Value::Bool(b)) => AvroValue::Boolean(*b),
+        (Schema::Int, _) => AvroValue::Int(int_from_json(json)?),
+        (Schema::Long, _) => AvroValue::Long(long_from_json(json)?),
+        (Schema::Float, _) => AvroValue::Float(float_from_json(json)? as f32),
+        (Schema::Double, _) => AvroValue::Double(float_from_json(json)?),
+        (Schema::Bytes, _) => AvroValue::Bytes(bytes_from_json(json, default)?),
+        (Schema::String, Value::String(s)) => AvroValue::String(s.clone()),
+        (Schema::Fixed(fixed), Value::String(s)) if fixed.attributes.contains_key(FIXED_UUID_ATTRIBUTE) => {
+            let uuid = s.parse::<uuid::Uuid>().map_err(|_| Mismatch::new(format!("{:?} is not a UUID", s)))?;
+            AvroValue::Fixed(fixed.size, uuid.as_bytes().to_vec())
+        }
+        (Schema::Fixed(fixed), _) => {
+            let bytes = bytes_from_json(json, default)?;
+            if bytes.len() != fixed.size {
+                return Err(Mismatch::new(format!("expected {} bytes, got {}", fixed.size, bytes.len())));
+            }
+            AvroValue::Fixed(fixed.size, bytes)
+        }
+        (Schema::Enum(enum_schema), _) => {
+            // `--enum-mode named` wraps the symbol as {"enum": ..., "symbol": ...}, and
+            // `--enum-as-index` writes its position
+            let symbol = match json {
+                Value::String(symbol) => symbol,
+                Value::Number(number) => {
+                    let symbol = number.as_u64().and_then(|index| enum_schema.symbols.get(index as usize));
+                    let out_of_range = || Mismatch::new(format!("{} is not a position in {}", number, enum_schema.name));
+                    symbol.ok_or_else(out_of_range)?
+                }
+                Value::Object(object) => match object.get("symbol") {
+                    Some(Value::String(symbol)) => symbol,
+                    _ => return Err(Mismatch::expected("an enum symbol", json)),
+                },
+                _ => return Err(Mismatch::expected("an enum symbol", json)),
+            };
+            let position = enum_schema
+                .symbols
+                .iter()
+                .position(|candidate| candidate == symbol)
+                .ok_or_else(|| Mismatch::new(format!("{:?} is not a symbol of {}", symbol, enum_schema.name)))?;
+            AvroValue::Enum(position as u32, symbol.clone())
+        }
+        (Schema::Array(items), Value::Array(values)) => {
+            let mut avro_items = Vec::with_capacity(values.len());
+            for (index, value) in values.iter().enumerate() {
+                avro_items.push(to_avro(value, items, names, default).map_err(|err| err.within(&index.to_string()))?);
+            }
+            AvroValue::Array(avro_items)
+        }
+        (Schema::Map(values), Value::Object(entries)) => {
+            let mut avro_entries = HashMap::with_capacity(entries.len());
+            for (key, value) in entries {
+                let value = to_avro(value, values, names, default).map_err(|err| err.within(key))?;
+                avro_entries.insert(key.clone(), value);
+            }
+            AvroValue::Map(avro_entries)
+        }
+        (Schema::Record(record), Value::Object(entries)) => {
+            let mut fields = Vec::with_capacity(record.fields.len());
+            for field in &record.fields {
+                let aliases = field.aliases.iter().flatten();
+                let value = match std::iter::once(&field.name).chain(aliases).find_map(|name| entries.get(name)) {
+                    Some(value) => to_avro(value, &field.schema, names, default),
+                    None => match &field.default {
+                        Some(field_default) => to_avro(field_default, &field.schema, names, true),
+                        None => Err(Mismatch::new("missing field with no default")),
+                    },
+                };
+                fields.push((field.name.clone(), value.map_err(|err| err.within(&field.name))?));
+            }
+            AvroValue::Record(fields)
+        }
+        (Schema::Union(union), _) => union_from_json(json, union, names, default)?,
+        (Schema::Date, _) => AvroValue::Date(logical_from_json(json, "days-since-epoch", default, parse_date)?),
+        (Schema::TimeMillis, _) => AvroValue::TimeMillis(logical_from_json(json, "time-millis", default, |_| None)?),
+        (Schema::TimeMicros, _) => AvroValue::TimeMicros(logical_from_json(json, "time-micros", default, |_| None)?),
+        (Schema::TimestampMillis, _) => AvroValue::TimestampMillis(logical_from_json(
+            json,
+            "timestamp-millis",
+            default,
+            |s| Some(DateTime::parse_from_rfc3339(s).ok()?.timestamp_millis()),
+        )?),
+        (Schema::TimestampMicros, _) => AvroValue::TimestampMicros(logical_from_json(
+            json,
+            "timestamp-micros",
+            default,
+            |s| Some(DateTime::parse_from_rfc3339(s).ok()?.timestamp_micros()),
+        )?),
+        (Schema::LocalTimestampMillis, _) => AvroValue::LocalTimestampMillis(logical_from_json(
+            json,
+            "local-timestamp-millis",
+            default,
+            |s| Some(parse_local_timestamp(s)?.and_utc().timestamp_millis()),
+        )?),
+        (Schema::LocalTimestampMicros, _) => AvroValue::LocalTimestampMicros(logical_from_json(
+            json,
+            "local-timestamp-micros",
+            default,
+            |s| Some(parse_local_timestamp(s)?.and_utc().timestamp_micros()),
+        )?),
+        (Schema::Decimal(_), _) if default => AvroValue::Decimal(Decimal::from(bytes_from_json(json, true)?)),
+        (Schema::Decimal(decimal), _) => AvroValue::Decimal(decimal_from_json(json, decimal)?),
+        (Schema::Uuid, Value::String(s)) => {
+            AvroValue::Uuid(s.parse().map_err(|_| Mismatch::new(format!("{:?} is not a UUID", s)))?)
+        }
+        (Schema::Duration, _) if default => {
+            let bytes = <[u8; 12]>::try_from(bytes_from_json(json, true)?)
+                .map_err(|bytes| Mismatch::new(format!("expected 12 bytes, got {}", bytes.len())))?;
+            AvroValue::Duration(bytes.into())
+        }
+        (Schema::Duration, _) => duration_from_json(json)?,
+        _ => return Err(Mismatch::expected(avro_type_name(SchemaKind::from(schema)), json)),
+    };
+    Ok(value)
+}
+
+/// Picks the union branch for a JSON value: the first one the bare value fits, or the one
+/// named by a single-key object wrapping the value.
+fn union_from_json(json: &Value, union: &UnionSchema, names: &Names, default: bool) -> Result<AvroValue, Mismatch> {
+    let variants = union.variants();
+    let wrap = |index: usize, value| AvroValue::Union(index as u32, Box::new(value));
+    if default {
+        let first = variants.first().ok_or_else(|| Mismatch::new("empty union"))?;
+        return Ok(wrap(0, to_avro(json, first, names, true)?));
+    }
+
+    // Numbers may be written as strings, but a string that fits a non-numeric branch is
+    // taken to be a string
+    let is_numeric = |variant: &Schema| matches!(variant, Schema::Int | Schema::Long | Schema::Float | Schema::Double);
+    let mut order: Vec<usize> = (0..variants.len()).collect();
+    if json.is_string() {
+        order.sort_by_key(|&index| is_numeric(&variants[index]));
+    }
+    let mut mismatches: Vec<Option<Mismatch>> = variants.iter().map(|_| None).collect();
+    for index in order {
+        match to_avro(json, &variants[index], names, false) {
+            Ok(value) => return Ok(wrap(index, value)),
+            Err(mismatch) => mismatches[index] = Some(mismatch),
+        }
+    }
+    if let Value::Object(object) = json {
+        if let Some((name, value)) = object.iter().next().filter(|_| object.len() == 1) {
+            let tagged =
+                variants.iter().position(|variant| union_branch_name(Some(variant), &AvroValue::Null) == *name);
+            if let Some(index) = tagged {
+                let value = to_avro(value, &variants[index], names, false).map_err(|err| err.within(name))?;
+                return Ok(wrap(index, value));
+            }
+        }
+    }
+
+    // With a single branch besides null, say what's wrong with the value in that branch
+    let non_null: Vec<usize> = (0..variants.len()).filter(|&index| variants[index] != Schema::Null).collect();
+    match non_null[..] {
+        [index] if !json.is_null() => Err(mismatches[index].take().expect("every branch was tried")),
+        _ => {
+            let branches: Vec<String> =
+                variants.iter().map(|variant| union_branch_name(Some(variant), &AvroValue::Null)).collect();
+            Err(Mismatch::expected(&format!("one of {}", branches.join(", ")), json))
+        }
+    }
+}
+
+fn out_of_range(json: &Value, avro_type: &str) -> Mismatch {
+    Mismatch::new(format!("{} is out of range for {}", json, avro_type))
+}
+
+fn long_from_json(json: &Value) -> Result<i64, Mismatch> {
+    match json {
+        Value::Number(number) => number.as_i64().ok_or_else(|| out_of_range(json, "long")),
+        // `--large-longs-as-string`
+        Value::String(s) => s.parse().map_err(|_| Mismatch::new(format!("{:?} is not a whole number", s))),
+        _ => Err(Mismatch::expected("long", json)),
+    }
+}
+
+fn int_from_json(json: &Value) -> Result<i32, Mismatch> {
+    let long = long_from_json(json).map_err(|err| match json {
+        Value::Number(_) | Value::String(_) => err,
+        _ => Mismatch::expected("int", json),
+    })?;
+    i32::try_from(long).map_err(|_| out_of_range(json, "int"))
+}
+
+fn float_from_json(json: &Value) -> Result<f64, Mismatch> {
+    match json {
+        Value::Number(number) => number.as_f64().ok_or_else(|| Mismatch::expected("a number", json)),
+        // `--float-policy string`
+        Value::String(s) if s == "NaN" => Ok(f64::NAN),
+        Value::String(s) if s == "Infinity" => Ok(f64::INFINITY),
+        Value::String(s) if s == "-Infinity" => Ok(f64::NEG_INFINITY),
+        _ => Err(Mismatch::expected("a number", json)),
+    }
+}
+
+/// Decodes a `bytes` or `fixed` value: base64, or with `latin1`, one code point per byte.
+fn bytes_from_json(json: &Value, latin1: bool) -> Result<Vec<u8>, Mismatch> {
+    let invalid = |err: ConvertError| Mismatch::new(err.to_string());
+    match json {
+        Value::String(s) if latin1 => s
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| Mismatch::new(format!("{:?} is not a byte", c))))
+            .collect(),
+        Value::String(s) => base64_decode(s).map_err(invalid),
+        // `--bytes-verbose` writes {"<encoding>": ..., "len": N}
+        Value::Object(object) if object.contains_key("len") => {
+            match (object.get("base64"), object.get("hex"), object.get("text")) {
+                (Some(Value::String(s)), _, _) => base64_decode(s).map_err(invalid),
+                (_, Some(Value::String(s)), _) => hex_decode(s.strip_prefix("0x").unwrap_or(s))
+                    .ok_or_else(|| Mismatch::new(format!("{:?} is not hexadecimal", s))),
+                (_, _, Some(Value::String(s))) => Ok(s.as_bytes().to_vec()),
+                _ => Err(Mismatch::expected("base64, hex or text bytes", json)),
+            }
+        }
+        _ => Err(Mismatch::expected("a base64 string", json)),
+    }
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+/// Reads a logical value that annotates an `int` or `long`: the number itself, the string
+/// `--legacy-logical-types` tags with `legacy_tag`, or a string `parse` understands.
+fn logical_from_json<T: TryFrom<i64>>(
+    json: &Value,
+    legacy_tag: &str,
+    default: bool,
+    parse: impl Fn(&str) -> Option<i64>,
+) -> Result<T, Mismatch> {
+    let number = match json {
+        Value::Number(number) => number.as_i64(),
+        Value::String(s) if !default => match s.strip_prefix(legacy_tag).and_then(|rest| rest.strip_prefix(':')) {
+            Some(raw) => raw.parse().ok(),
+            None => parse(s),
+        },
+        _ => return Err(Mismatch::expected(legacy_tag, json)),
+    };
+    let number = number.ok_or_else(|| Mismatch::new(format!("{} is not a valid {}", json, legacy_tag)))?;
+    T::try_from(number).map_err(|_| out_of_range(json, legacy_tag))
+}
+
+/// Days since the Unix epoch of a `YYYY-MM-DD` date.
+fn parse_date(s: &str) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+    Some(i64::from(date.num_days_from_ce()) - i64::from(UNIX_EPOCH_DAYS_FROM_CE))
+}
+
+/// The wall-clock time of a local timestamp, written without an offset or, under
+/// `--local-tz`, with one.
+fn parse_local_timestamp(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+        .ok()
+        .or_else(|| Some(DateTime::parse_from_rfc3339(s).ok()?.naive_local()))
+}
+
+/// Reads a decimal literal such as `"-123.45"` as the unscaled value for `schema`'s scale,
+/// refusing digits that the scale or precision has no room for.
+fn decimal_from_json(json: &Value, schema: &DecimalSchema) -> Result<Decimal, Mismatch> {
+    let literal = match json {
+        Value::String(s) => s.clone(),
+        Value::Number(number) => number.to_string(),
+        _ => return Err(Mismatch::expected("a decimal string", json)),
+    };
+    let invalid = || Mismatch::new(format!("{:?} is not a decimal", literal));
+    let (sign, unsigned) = match literal.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", literal.strip_prefix('+').unwrap_or(&literal)),
+    };
+    let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if integer.is_empty() || !integer.bytes().chain(fraction.bytes()).all(|byte| byte.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    if fraction.len() > schema.scale {
+        return Err(Mismatch::new(format!("{} has more than {} decimal places", literal, schema.scale)));
+    }
+    let digits = format!("{}{}{:0<width$}", sign, integer, fraction, width = schema.scale);
+    let unscaled: BigInt = digits.parse().map_err(|_| invalid())?;
+    if unscaled.magnitude().to_string().len() > schema.precision {
+        return Err(Mismatch::new(format!("{} has more than {} digits", literal, schema.precision)));
+    }
+    let bytes = unscaled.to_signed_bytes_be();
+    if let Schema::Fixed(fixed) = schema.inner.as_ref() {
+        if bytes.len() > fixed.size {
+            return Err(Mismatch::new(format!("{} doesn't fit in {} bytes", literal, fixed.size)));
+        }
+    }
+    Ok(Decimal::from(bytes))
+}
+
+/// Reads a duration from a `{"months", "days", "millis"}` object or, as
+/// `--duration-format iso8601` writes it, a string such as `P1M2DT3.5S`.
+fn duration_from_json(json: &Value) -> Result<AvroValue, Mismatch> {
+    let (months, days, millis) = match json {
+        Value::Object(object) => {
+            let part = |name: &str| {
+                let value = object.get(name).unwrap_or(&Value::Null);
+                let number = value.as_u64().ok_or_else(|| Mismatch::expected("a whole number", value).within(name))?;
+                u32::try_from(number).map_err(|_| out_of_range(value, "a duration").within(name))
+            };
+            (part("months")?, part("days")?, part("millis")?)
+        }
+        Value::String(s) => {
+            parse_iso8601_duration(s).ok_or_else(|| Mismatch::new(format!("{:?} is not a duration", s)))?
+        }
+        _ => return Err(Mismatch::expected("a duration", json)),
+    };
+    Ok(AvroValue::Duration(Duration::new(Months::new(months), Days::new(days), Millis::new(millis))))
+}
+
+/// Parses the ISO-8601 durations that [`duration_to_iso8601`](crate::duration_to_iso8601)
+/// writes: months, days and (fractional) seconds, returned as months, days and millis.
+fn parse_iso8601_duration(s: &str) -> Option<(u32, u32, u32)> {
+    let rest = s.strip_prefix('P')?;
+    let (mut date, time) = match rest.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (rest, None),
+    };
+    let mut part = |unit: char| -> Option<u32> {
+        match date.split_once(unit) {
+            Some((number, rest)) => {
+                date = rest;
+                number.parse().ok()
+            }
+            None => Some(0),
+        }
+    };
+    let (months, days) = (part('M')?, part('D')?);
+    if !date.is_empty() {
+        return None;
+    }
+    let millis = match time {
+        Some(time) => {
+            let seconds = time.strip_suffix('S')?;
+            let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+            if fraction.len() > 3 || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+            let fraction: u32 = format!("{:0<3}", fraction).parse().ok()?;
+            whole.parse::<u32>().ok()?.checked_mul(1000)?.checked_add(fraction)?
+        }
+        None => 0,
+    };
+    Some((months, days, millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema(json: Value) -> Schema {
+        Schema::parse(&json).unwrap()
+    }
+
+    #[test]
+    fn test_json_to_avro_value_round_trips_default_rendering() {
+        let schema = schema(json!({
+            "type": "record",
+            "name": "Payment",
+            "fields": [
+                {"name": "id", "type": {"type": "string", "logicalType": "uuid"}},
+                {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal", "precision": 9, "scale": 2}},
+                {"name": "day", "type": {"type": "int", "logicalType": "date"}},
+                {"name": "at", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+                {"name": "local", "type": {"type": "long", "logicalType": "local-timestamp-millis"}},
+                {"name": "hash", "type": {"type": "fixed", "name": "Hash", "size": 4}},
+                {"name": "payload", "type": "bytes"},
+                {"name": "status", "type": {"type": "enum", "name": "Status", "symbols": ["OK", "FAILED"]}},
+                {"name": "note", "type": ["null", "string"]},
+                {"name": "tags", "type": {"type": "array", "items": "string"}},
+                {"name": "counts", "type": {"type": "map", "values": "long"}},
+                {"name": "retries", "type": "int", "default": 3},
+                {"name": "period", "type": {"type": "fixed", "name": "Period", "size": 12, "logicalType": "duration"}},
+            ]
+        }));
+        let record = json!({
+            "id": "5b2c1f8e-3d5a-4f7b-9c1e-2a3b4c5d6e7f",
+            "amount": "-123.45",
+            "day": "2024-02-29",
+            "at": "2023-11-14T22:13:20.000123Z",
+            "local": "2023-11-14T22:13:20",
+            "hash": "3q2+7w==",
+            "payload": "aGVsbG8=",
+            "status": "FAILED",
+            "note": "hi",
+            "tags": ["a", "b"],
+            "counts": {"x": 1},
+            "period": {"months": 1, "days": 2, "millis": 3500},
+        });
+
+        let avro_value = json_to_avro_value(&record, &schema).unwrap();
+        assert!(avro_value.validate(&schema));
+        let mut expected = record.clone();
+        expected["retries"] = json!(3);
+        let json = crate::avro_value_to_json_with_schema(&avro_value, &schema).unwrap();
+        assert_eq!(json, expected);
+
+        let datum = apache_avro::to_avro_datum(&schema, avro_value).unwrap();
+        assert_eq!(crate::convert_datum(&schema, &datum).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_json_to_avro_value_alternative_renderings() {
+        let schema = schema(json!({
+            "type": "record",
+            "name": "Event",
+            "fields": [
+                {"name": "big", "type": "long"},
+                {"name": "ratio", "type": "double"},
+                {"name": "day", "type": {"type": "int", "logicalType": "date"}},
+                {"name": "at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+                {"name": "payload", "type": "bytes"},
+                {"name": "value", "type": ["null", "long", "string"]},
+                {"name": "code", "type": ["null", "long", "string"]},
+                {"name": "status", "type": {"type": "enum", "name": "Status", "symbols": ["OK"]}},
+                {"name": "level", "type": {"type": "enum", "name": "Level", "symbols": ["LOW", "HIGH"]}},
+                {"name": "period", "type": {"type": "fixed", "name": "Period", "size": 12, "logicalType": "duration"}},
+            ]
+        }));
+        let record = json!({
+            "big": "9007199254740993",
+            "ratio": "NaN",
+            "day": "days-since-epoch:19782",
+            "at": 1700000000000i64,
+            "payload": {"hex": "0xdead", "len": 2},
+            "value": {"long": 5},
+            "code": "007",
+            "status": {"enum": "Status", "symbol": "OK"},
+            "level": 1,
+            "period": "P1M2DT3.5S",
+        });
+        let AvroValue::Record(fields) = json_to_avro_value(&record, &schema).unwrap() else {
+            panic!("expected a record");
+        };
+        assert_eq!(fields[0].1, AvroValue::Long(9_007_199_254_740_993));
+        assert!(matches!(fields[1].1, AvroValue::Double(ratio) if ratio.is_nan()));
+        assert_eq!(fields[2].1, AvroValue::Date(19_782));
+        assert_eq!(fields[3].1, AvroValue::TimestampMillis(1_700_000_000_000));
+        assert_eq!(fields[4].1, AvroValue::Bytes(vec![0xDE, 0xAD]));
+        assert_eq!(fields[5].1, AvroValue::Union(1, Box::new(AvroValue::Long(5))));
+        assert_eq!(fields[6].1, AvroValue::Union(2, Box::new(AvroValue::String("007".to_string()))));
+        assert_eq!(fields[7].1, AvroValue::Enum(0, "OK".to_string()));
+        assert_eq!(fields[8].1, AvroValue::Enum(1, "HIGH".to_string()));
+        assert_eq!(
+            fields[9].1,
+            AvroValue::Duration(Duration::new(Months::new(1), Days::new(2), Millis::new(3500)))
+        );
+    }
+
+    #[test]
+    fn test_json_to_avro_value_mismatches() {
+        let schema = schema(json!({
+            "type": "record",
+            "name": "User",
+            "fields": [
+                {"name": "name", "type": "string"},
+                {"name": "age", "type": ["null", "int"]},
+                {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal", "precision": 4, "scale": 2}},
+            ]
+        }));
+        let mismatch = |record: Value| json_to_avro_value(&record, &schema).unwrap_err().to_string();
+
+        assert_eq!(
+            mismatch(json!({"age": 1, "amount": "1.00"})),
+            "JSON value does not match the schema: missing field with no default at /name"
+        );
+        assert_eq!(
+            mismatch(json!({"name": "a", "age": "old", "amount": "1.00"})),
+            "JSON value does not match the schema: \"old\" is not a whole number at /age"
+        );
+        assert_eq!(
+            mismatch(json!({"name": "a", "age": 3000000000i64, "amount": "1.00"})),
+            "JSON value does not match the schema: 3000000000 is out of range for int at /age"
+        );
+        assert_eq!(
+            mismatch(json!({"name": "a", "age": null, "amount": "1.005"})),
+            "JSON value does not match the schema: 1.005 has more than 2 decimal places at /amount"
+        );
+        assert_eq!(
+            mismatch(json!({"name": "a", "age": null, "amount": "100.00"})),
+            "JSON value does not match the schema: 100.00 has more than 4 digits at /amount"
+        );
+        assert_eq!(
+            mismatch(json!(["a"])),
+            "JSON value does not match the schema: expected record, got array"
+        );
+    }
+
+    #[test]
+    fn test_write_container() {
+        let schema = schema(json!({
+            "type": "record",
+            "name": "User",
+            "fields": [{"name": "name", "type": "string"}, {"name": "age", "type": "int"}]
+        }));
+        let ndjson = "{\"name\": \"Alice\", \"age\": 28}\n{\"name\": \"Bob\", \"age\": 35}\n";
+        let array = "[{\"name\": \"Alice\", \"age\": 28}, {\"name\": \"Bob\", \"age\": 35}]";
+        for input in [ndjson, array] {
+            let mut container = Vec::new();
+            assert_eq!(write_container(input.as_bytes(), &schema, &mut container).unwrap(), 2);
+            let json = crate::convert_bytes_to_json_string(&container, false, true).unwrap();
+            assert_eq!(json, "[{\"name\":\"Alice\",\"age\":28},{\"name\":\"Bob\",\"age\":35}]\n");
+        }
+
+        // A record that doesn't fit is reported by its index
+        let input = "{\"name\": \"Alice\", \"age\": 28}\n{\"name\": 1}";
+        let err = write_container(input.as_bytes(), &schema, &mut Vec::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to convert record 1: JSON value does not match the schema: expected string, got number at /name"
+        );
+
+        let mut container = Vec::new();
+        assert_eq!(write_container("".as_bytes(), &schema, &mut container).unwrap(), 0);
+        assert_eq!(crate::convert_bytes_to_json_string(&container, false, true).unwrap(), "[]\n");
+    }
+}
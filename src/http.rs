@@ -0,0 +1,91 @@
+//! Reading inputs over HTTP(S), such as signed URLs to objects in cloud storage.
+
+use crate::error::{ConvertError, Result};
+use std::io::{BufReader, Read};
+use std::time::Duration;
+
+/// Whether an input names a URL rather than a file.
+pub(crate) fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// The URL without its query string, which for a signed URL holds the credentials, so it can
+/// be shown in messages.
+pub(crate) fn redact_url(url: &str) -> &str {
+    url.split_once('?').map_or(url, |(without_query, _)| without_query)
+}
+
+/// Starts a GET of `url` and returns its body for streaming. Containers are read front to
+/// back, so no range requests are needed. Anything but a 2xx response is an error.
+pub(crate) fn open_url(url: &str, buffer_size: usize) -> Result<Box<dyn Read>> {
+    let fetch_error = |reason: String| ConvertError::Http {
+        url: redact_url(url).to_string(),
+        reason,
+    };
+    // A large file can take any amount of time to stream, so only connecting is timed out
+    let client = reqwest::blocking::Client::builder()
+        .timeout(None)
+        .connect_timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|err| fetch_error(err.to_string()))?;
+    let response = client.get(url).send().map_err(|err| fetch_error(err.without_url().to_string()))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(fetch_error(format!("server responded {}", status)));
+    }
+    Ok(Box::new(BufReader::with_capacity(buffer_size, response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    /// Serves one request with `status` and `body`, returning the URL to request.
+    fn serve_once(status: &'static str, body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // The request itself doesn't matter; read its head so the client isn't cut off
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, body.len());
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        });
+        format!("http://{}/data/users.avro?X-Signature=secret", address)
+    }
+
+    #[test]
+    fn test_url_inputs() {
+        let input = crate::Input::from("https://bucket.example.com/users.avro.gz?X-Signature=secret");
+        assert_eq!(input, crate::Input::Url("https://bucket.example.com/users.avro.gz?X-Signature=secret".to_string()));
+        assert_eq!(input.to_string(), "https://bucket.example.com/users.avro.gz");
+        assert!(input.is_gzip());
+        assert_eq!(crate::Input::from("http.avro"), crate::Input::Path("http.avro".to_string()));
+    }
+
+    #[test]
+    fn test_open_url_streams_the_body() {
+        let avro = std::fs::read("test_users.avro").unwrap();
+        let url = serve_once("200 OK", avro.clone());
+        let mut body = Vec::new();
+        open_url(&url, 1024).unwrap().read_to_end(&mut body).unwrap();
+        assert_eq!(body, avro);
+
+        let url = serve_once("200 OK", avro);
+        let records = crate::avro_json_records(crate::Input::from(url.as_str()).open().unwrap()).unwrap();
+        assert_eq!(records.count(), 3);
+    }
+
+    #[test]
+    fn test_open_url_rejects_error_responses() {
+        let url = serve_once("403 Forbidden", b"expired".to_vec());
+        let err = open_url(&url, 1024).err().unwrap();
+        let base = redact_url(&url).to_string();
+        assert_eq!(err.to_string(), format!("Failed to fetch {}: server responded 403 Forbidden", base));
+        assert!(!err.to_string().contains("secret"));
+    }
+}
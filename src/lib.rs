@@ -1,9 +1,136 @@
+use apache_avro::schema::{Name, ResolvedSchema, Schema, SchemaKind};
 use apache_avro::Reader;
-use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use num_bigint::BigInt;
+use rayon::prelude::*;
 use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
+use std::time::Instant;
+
+mod error;
+pub mod filter;
+mod from_json;
+#[cfg(feature = "http")]
+mod http;
+mod output;
+pub mod profile;
+mod range;
+mod sample;
+pub mod stats;
+#[cfg(feature = "tokio")]
+pub mod stream;
+pub mod transform;
+
+pub use error::{ConvertError, Result, Truncation};
+use error::{io_error, GzipStreamEnded};
+pub use filter::{dedup_key, record_matches, Dedup, Filter, FilterOp};
+pub use from_json::{convert_json_to_avro, json_to_avro_value};
+use output::{AtomicFile, PartitionFiles, SplitFiles, WithoutFinalNewline};
+pub use output::{chunk_path, partition_path, PartitionBy, DEFAULT_MAX_PARTITIONS};
+pub use profile::{profile_records, FieldProfile, ProfileOptions};
+pub use range::BlockRange;
+use sample::Reservoir;
+pub use sample::Sample;
+pub use stats::{LogicalTypeStats, Timing};
+#[cfg(feature = "tokio")]
+pub use stream::convert_avro_to_json_async;
+use range::open_block_range;
+pub use transform::{
+    extract_pointer, extracted_line, flatten_value, flatten_value_with_options, omit_nulls, omit_nulls_with_options,
+    parse_field_paths, project_value, records_to_envelope, records_to_keyed_object, rename_key, rename_keys,
+    replace_nulls, sort_keys, Envelope, Extract, FlattenOptions, KeyBy, KeyStyle,
+};
+
+/// Handling of records that fail to decode or convert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnError {
+    /// Stop the conversion with the record's error.
+    #[default]
+    Abort,
+    /// Log the record's index and error as a `tracing` warning and carry on, then log how many
+    /// records were skipped once done. A record that fails to decode ends its input, since the data
+    /// after it can't be located; conversion continues with the next input. A truncated
+    /// container still stops the conversion, with [`ConvertError::Truncated`].
+    Skip,
+}
+
+/// Where Avro container data is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Path(String),
+    Stdin,
+    /// An `http://` or `https://` URL, fetched with a GET and streamed.
+    #[cfg(feature = "http")]
+    Url(String),
+}
+
+impl Input {
+    /// Opens the input for sequential reading. Avro containers are read front to back, so
+    /// non-seekable sources such as pipes work fine.
+    pub fn open(&self) -> Result<Box<dyn Read>> {
+        self.open_with_buffer_size(DEFAULT_BUFFER_SIZE)
+    }
+
+    /// [`open`](Self::open), reading through a buffer of `buffer_size` bytes.
+    pub fn open_with_buffer_size(&self, buffer_size: usize) -> Result<Box<dyn Read>> {
+        match self {
+            Input::Path(path) => {
+                let file = File::open(Path::new(path))
+                    .map_err(|err| io_error(format!("Failed to open input file {}", path), err))?;
+                Ok(Box::new(BufReader::with_capacity(buffer_size, file)))
+            }
+            Input::Stdin => Ok(Box::new(BufReader::with_capacity(buffer_size, std::io::stdin().lock()))),
+            #[cfg(feature = "http")]
+            Input::Url(url) => http::open_url(url, buffer_size),
+        }
+    }
+
+    /// Whether the input looks gzip-compressed based on its `.gz` extension.
+    pub fn is_gzip(&self) -> bool {
+        match self {
+            Input::Path(path) => path.ends_with(".gz"),
+            Input::Stdin => false,
+            #[cfg(feature = "http")]
+            Input::Url(url) => http::redact_url(url).ends_with(".gz"),
+        }
+    }
+}
+
+/// `-` is the conventional name for stdin, and with the `http` feature an `http://` or
+/// `https://` prefix makes a URL; anything else is treated as a file path.
+impl From<&str> for Input {
+    fn from(input: &str) -> Self {
+        if input == "-" {
+            Input::Stdin
+        } else {
+            #[cfg(feature = "http")]
+            if http::is_url(input) {
+                return Input::Url(input.to_string());
+            }
+            Input::Path(input.to_string())
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Path(path) => write!(f, "{}", path),
+            Input::Stdin => write!(f, "<stdin>"),
+            // Signed URLs carry their credentials in the query string
+            #[cfg(feature = "http")]
+            Input::Url(url) => write!(f, "{}", http::redact_url(url)),
+        }
+    }
+}
 
 pub fn convert_avro_to_json(
     input_file: &str,
@@ -21,361 +148,5796 @@ pub fn convert_avro_to_json_with_color(
     as_array: bool,
     color: bool,
 ) -> Result<()> {
-    // Open and read the Avro file
-    let input_path = Path::new(input_file);
-    let file = File::open(input_path)
-        .context(format!("Failed to open input file: {}", input_file))?;
-    
-    let reader = BufReader::new(file);
-    let avro_reader = Reader::new(reader)
-        .context("Failed to create Avro reader")?;
+    let options = ConvertOptions::new()
+        .pretty(pretty)
+        .as_array(as_array)
+        .color(if color { ColorChoice::Auto } else { ColorChoice::Never });
+    convert(input_file, output_file, &options)
+}
 
-    // Collect all records
-    let mut records = Vec::new();
-    
-    for record in avro_reader {
-        let record = record.context("Failed to read Avro record")?;
-        let json_value = avro_value_to_json(&record)?;
-        records.push(json_value);
+/// Output file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// JSON, either newline-delimited or as an array.
+    #[default]
+    Json,
+    /// CSV with a header row; see [`write_csv_output`].
+    Csv,
+    /// YAML, one `---`-separated document per record, or a single document holding a
+    /// sequence when written as an array.
+    #[cfg(feature = "yaml")]
+    Yaml,
+    /// TOML, each record a `[[records]]` table whether or not it is written as an array, since
+    /// a TOML document has to be a table. TOML has no null, so null fields are left out.
+    #[cfg(feature = "toml")]
+    Toml,
+    /// MessagePack, each record a self-delimiting MessagePack value written straight after
+    /// the previous one with no length prefix or separator, or a single MessagePack array
+    /// when written as an array. Objects become maps.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+/// Size in bytes of the buffers inputs are read through and output is written through,
+/// unless [`ConvertOptions::buffer_size`] says otherwise.
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Controls how a whole Avro input is converted and written out.
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    /// Output file format. `pretty`, `as_array`, `jsonl` and `color` only apply to JSON.
+    pub format: OutputFormat,
+    /// Pretty print JSON output.
+    pub pretty: bool,
+    /// Indentation used when `pretty` is set.
+    pub indent: Indent,
+    /// Write a single JSON array instead of newline-delimited JSON. The array is streamed an
+    /// element at a time, so records aren't held in memory.
+    pub as_array: bool,
+    /// Write a single JSON object holding every record under its key instead of
+    /// newline-delimited JSON or an array; see [`records_to_keyed_object`]. Takes precedence
+    /// over `as_array`, and like it only applies to JSON. Records are held in memory until
+    /// the object is complete.
+    pub key_by: Option<KeyBy>,
+    /// Write all records as a single JSON object that also holds the writer schema and the
+    /// record count; see [`Envelope`]. Like `key_by`, every record is held in memory until the
+    /// end, and it only applies to JSON output. Every input must share the same schema.
+    pub envelope: Option<Envelope>,
+    /// Guarantee strict JSON Lines (one record per line) by writing records compactly even
+    /// when `pretty` is set. Without it, `pretty` newline-delimited output is a stream of
+    /// multi-line JSON documents that line-oriented parsers cannot read. Has no effect on
+    /// array output.
+    pub jsonl: bool,
+    /// Leave out the newline after the closing `]` of an array, or after the last
+    /// newline-delimited record, so the output can be embedded in another document. Only
+    /// applies to JSON written to a single output.
+    pub no_final_newline: bool,
+    /// When to colorize JSON output.
+    pub color: ColorChoice,
+    /// Discard this many records before converting any.
+    pub skip: Option<usize>,
+    /// Stop after this many records.
+    pub limit: Option<usize>,
+    /// Only convert the records at these zero-based indexes, counted across inputs like
+    /// `skip`. Reading stops after the highest one; indexes past the end of the input are
+    /// ignored, with a warning logged.
+    pub records: Option<BTreeSet<usize>>,
+    /// Write the output to numbered files of this many records each, named after the output
+    /// file by [`chunk_path`]. Each file is complete on its own: a JSON array, a CSV file with
+    /// its header, or newline-delimited records. Needs an output file.
+    pub split: Option<usize>,
+    /// Write each record to a file for its value at a JSON Pointer, named after the output
+    /// file by [`partition_path`], each newline-delimited records or a JSON array. Only for
+    /// JSON output without `key_by` or `split`, and needs an output file. Files are started
+    /// by the first record of their partition, so no records means no files.
+    pub partition_by: Option<PartitionBy>,
+    /// Report the JSON types each field takes across the written records once the
+    /// conversion is done.
+    pub profile: Option<ProfileOptions>,
+    /// Count the logical types (dates, decimals, UUIDs, ...) in the converted records and
+    /// print a summary to stderr at the end. Records that `filter` or `dedup` go on to drop
+    /// are counted too, since they were converted.
+    pub stats: bool,
+    /// Print the elapsed time, the number of records read and the throughput to stderr once
+    /// the conversion is done; see [`Timing`].
+    pub timing: bool,
+    /// Only convert the blocks of each container that start within this byte range, so
+    /// several workers can share one file; see [`BlockRange`]. Inputs must be uncompressed
+    /// container files.
+    pub block_range: Option<BlockRange>,
+    /// Fail with [`ConvertError::TooManyRecordsInMemory`] rather than hold more than this many
+    /// records in memory for output that can only be written once complete: CSV, YAML and
+    /// MessagePack arrays, and `key_by` objects. Streamed output is unaffected. `None` is unlimited.
+    pub max_records_in_memory: Option<usize>,
+    /// Only write the last this many records. The whole input is still read, since a
+    /// container can't be read from the end, and the kept records are held in memory until
+    /// then. `limit` is ignored when this is set.
+    pub tail: Option<usize>,
+    /// Only write a uniform random sample of this many records, in input order. Like `tail`,
+    /// the whole input is read while only the sample is held in memory, and `limit` is
+    /// ignored. Records are sampled after `filter` and `dedup`.
+    pub sample: Option<Sample>,
+    /// What to do with a record that fails to decode or convert.
+    pub on_error: OnError,
+    /// Only write records matching this condition. It is checked against the whole converted
+    /// record, before `select` and `flatten`; `limit` counts matching records only.
+    pub filter: Option<Filter>,
+    /// Only write the first record for each distinct key. Like `filter`, the key is read from
+    /// the whole converted record. Every key seen is kept in memory until the run ends.
+    pub dedup: Option<Dedup>,
+    /// Write one line per record holding just its value at a JSON Pointer, read from the
+    /// fully converted record: strings without quotes, anything else as compact JSON. Takes
+    /// precedence over `as_array`, `key_by`, `pretty` and `format`.
+    pub extract: Option<Extract>,
+    /// Keep only these dotted field paths in each record. A segment can name a record field by
+    /// any of its aliases in the schema the records are rendered with (the reader schema, if
+    /// given); the field keeps its own name in the output.
+    pub select: Option<Vec<Vec<String>>>,
+    /// Drop null object entries at every level of each record, after `select` and before
+    /// `flatten`. Nulls inside arrays are kept unless `omit_null_items` is also set.
+    pub omit_nulls: bool,
+    /// With `omit_nulls`, drop null array items too, shifting the items after them.
+    pub omit_null_items: bool,
+    /// Write nulls as this string instead, at every level of each record, e.g. `\N` for
+    /// PostgreSQL `COPY`. Applied after `omit_nulls`, which leaves only array items for it.
+    pub null_as: Option<String>,
+    /// Flatten nested objects in each record into joined keys, after `select` is applied.
+    pub flatten: Option<FlattenOptions>,
+    /// Wrap each record with its provenance as `{"<prefix>file": ..., "<prefix>index": ...,
+    /// "<prefix>record": ...}`, where the index is the record's position in its file. The
+    /// prefix (typically `_`) keeps the wrapper keys apart from real fields.
+    pub metadata_prefix: Option<String>,
+    /// Sort object keys alphabetically at every level. Record fields otherwise follow schema
+    /// order, while Avro map keys are always sorted.
+    pub sort_keys: bool,
+    /// Decompress the input with gzip even if it lacks a `.gz` extension.
+    pub input_gzip: bool,
+    /// Compress the output with gzip.
+    pub output_gzip: bool,
+    /// Size in bytes of the buffers the input files are read through and the output is
+    /// written through. Larger buffers mean fewer system calls, which pays off on network
+    /// filesystems. `None` or zero uses [`DEFAULT_BUFFER_SIZE`].
+    pub buffer_size: Option<usize>,
+    /// Flush the output after every this many newline-delimited records, so consumers
+    /// reading from a pipe see records promptly. By default output is only flushed as the
+    /// buffer fills and at the end.
+    pub flush_every: Option<usize>,
+    /// Show a spinner with a running record count on stderr. It is hidden automatically
+    /// when stderr isn't a terminal.
+    pub progress: bool,
+    /// Convert records on this many threads. Output order is preserved; `None` or 1 converts
+    /// on the calling thread. Only converting decoded values to JSON values is parallel:
+    /// decoding, serializing the JSON to text and writing it stay on the calling thread.
+    pub jobs: Option<usize>,
+    /// Resolve container records against this schema instead of using the writer schema
+    /// as-is: fields only the reader knows get their defaults, and fields it lacks are
+    /// dropped. Record fields come out in the reader schema's order, at every level.
+    pub reader_schema: Option<Schema>,
+    /// Treat the input as a stream of single-object encoded messages written with this
+    /// schema, rather than as a container file.
+    pub single_object_schema: Option<Schema>,
+    /// Treat the input as a sequence of binary-encoded datums written with this schema, each
+    /// prefixed by its length as a 4-byte big-endian integer, rather than as a container file.
+    pub framed_schema: Option<Schema>,
+    /// How individual values are rendered.
+    pub json: JsonOptions,
+}
+
+/// Generates a chainable setter for each named field. Fields listed as `Option<T>` get a
+/// setter that takes the `T` itself.
+macro_rules! setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {$(
+        #[doc = concat!("Sets [`", stringify!($field), "`](Self::", stringify!($field), ").")]
+        pub fn $field(mut self, $field: $ty) -> Self {
+            self.$field = $field;
+            self
+        }
+    )*};
+    (optional $($field:ident: $ty:ty),* $(,)?) => {$(
+        #[doc = concat!("Sets [`", stringify!($field), "`](Self::", stringify!($field), ").")]
+        pub fn $field(mut self, $field: $ty) -> Self {
+            self.$field = Some($field);
+            self
+        }
+    )*};
+}
+
+/// Builder-style configuration, for setting just the options that matter in one expression,
+/// e.g. `ConvertOptions::new().pretty(true).limit(10)`.
+impl ConvertOptions {
+    /// The defaults: newline-delimited, compact, uncolored JSON of every record.
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    // Prepare output
-    let output: Box<dyn std::io::Write> = if let Some(output_path) = output_file {
-        let file = File::create(output_path)
-            .context(format!("Failed to create output file: {}", output_path))?;
-        Box::new(BufWriter::new(file))
-    } else {
-        Box::new(std::io::stdout())
+    setters! {
+        format: OutputFormat,
+        pretty: bool,
+        indent: Indent,
+        as_array: bool,
+        jsonl: bool,
+        no_final_newline: bool,
+        color: ColorChoice,
+        stats: bool,
+        timing: bool,
+        on_error: OnError,
+        omit_nulls: bool,
+        omit_null_items: bool,
+        sort_keys: bool,
+        input_gzip: bool,
+        output_gzip: bool,
+        progress: bool,
+        json: JsonOptions,
+    }
+
+    setters! {
+        optional
+        key_by: KeyBy,
+        envelope: Envelope,
+        skip: usize,
+        limit: usize,
+        records: BTreeSet<usize>,
+        split: usize,
+        partition_by: PartitionBy,
+        profile: ProfileOptions,
+        block_range: BlockRange,
+        max_records_in_memory: usize,
+        tail: usize,
+        sample: Sample,
+        filter: Filter,
+        dedup: Dedup,
+        extract: Extract,
+        select: Vec<Vec<String>>,
+        null_as: String,
+        flatten: FlattenOptions,
+        metadata_prefix: String,
+        buffer_size: usize,
+        flush_every: usize,
+        jobs: usize,
+        reader_schema: Schema,
+        single_object_schema: Schema,
+        framed_schema: Schema,
+    }
+}
+
+/// Converts `input` (a file path, `-` for stdin, or a URL with the `http` feature) into
+/// `output_file`, or stdout when there is none, as `options` say. The other conversion
+/// functions are shorthands for this one.
+pub fn convert(input: &str, output_file: Option<&String>, options: &ConvertOptions) -> Result<()> {
+    convert_avro_to_json_with_options(&Input::from(input), output_file, options)
+}
+
+/// Converts an Avro container file to JSON.
+///
+/// Output is streamed record by record, as newline-delimited JSON or as the elements of an
+/// array, so memory use does not grow with the input. CSV output has to hold every converted
+/// record in memory before anything is written.
+pub fn convert_avro_to_json_with_options(
+    input: &Input,
+    output_file: Option<&String>,
+    options: &ConvertOptions,
+) -> Result<()> {
+    convert_inputs(std::slice::from_ref(input), output_file, options)
+}
+
+/// Converts several Avro files, in order, into one output.
+///
+/// In array mode all records end up in a single array; newline-delimited output simply
+/// streams one file after another. Each file is decoded with its own writer schema, so
+/// the files don't need to share a schema. `skip` and `limit` count records across all
+/// files combined.
+pub fn convert_many(
+    inputs: &[String],
+    output_file: Option<&String>,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let inputs: Vec<Input> = inputs.iter().map(|input| Input::from(input.as_str())).collect();
+    convert_inputs(&inputs, output_file, options)
+}
+
+/// Converts an Avro container held in memory to a JSON string, without touching the
+/// filesystem or stdout, e.g. to call from WebAssembly.
+pub fn convert_bytes_to_json_string(avro_bytes: &[u8], pretty: bool, as_array: bool) -> Result<String> {
+    let options = ConvertOptions {
+        pretty,
+        as_array,
+        ..Default::default()
     };
+    convert_bytes_to_json_string_with_options(avro_bytes, &options)
+}
 
-    // Write JSON output
-    write_json_output(output, records, pretty, as_array, color)
-        .context("Failed to write JSON output")?;
+/// [`convert_bytes_to_json_string`] with full control over the conversion. Options that name
+/// or compress the output (`split`, `output_gzip`) don't apply to a string and are ignored,
+/// and `ColorChoice::Auto` never colorizes, since there is no terminal.
+pub fn convert_bytes_to_json_string_with_options(avro_bytes: &[u8], options: &ConvertOptions) -> Result<String> {
+    let mut output = Vec::new();
+    convert_readers([("<memory>".to_string(), Ok(avro_bytes))], &mut output, false, options)?;
+    String::from_utf8(output).map_err(|err| ConvertError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, err)))
+}
 
+fn convert_inputs(inputs: &[Input], output_file: Option<&String>, options: &ConvertOptions) -> Result<()> {
+    let started = Instant::now();
+    let records = write_inputs(inputs, output_file, options)
+        .inspect_err(|err| tracing::debug!("Conversion failed after {:.3}s: {}", started.elapsed().as_secs_f64(), err))?;
+    if options.timing {
+        // Only known when every input is a file; stdin could be any size
+        let input_bytes = inputs
+            .iter()
+            .map(|input| match input {
+                Input::Path(path) => std::fs::metadata(path).ok().map(|metadata| metadata.len()),
+                _ => None,
+            })
+            .sum();
+        eprintln!("{}", Timing { elapsed: started.elapsed(), records, input_bytes });
+    }
     Ok(())
 }
 
-pub fn avro_value_to_json(avro_value: &apache_avro::types::Value) -> Result<Value> {
-    use apache_avro::types::Value as AvroValue;
+/// Converts the inputs into the output, returning how many records were read.
+fn write_inputs(inputs: &[Input], output_file: Option<&String>, options: &ConvertOptions) -> Result<u64> {
+    let buffer_size = options.buffer_size.filter(|&size| size > 0).unwrap_or(DEFAULT_BUFFER_SIZE);
+    // Inputs are opened one at a time, only once the previous one is done
+    let readers = inputs
+        .iter()
+        .map(|input| {
+            tracing::debug!("Opening input {}", input);
+            let reader = match options.block_range {
+                Some(range) => open_block_range(input, range, options.input_gzip, buffer_size),
+                None => open_avro_input(input, options.input_gzip, buffer_size),
+            };
+            (input.to_string(), reader)
+        });
 
-    let json_value = match avro_value {
-        AvroValue::Null => Value::Null,
-        AvroValue::Boolean(b) => Value::Bool(*b),
-        AvroValue::Int(i) => Value::Number((*i).into()),
-        AvroValue::Long(l) => Value::Number((*l).into()),
-        AvroValue::Float(f) => {
-            serde_json::Number::from_f64(*f as f64)
-                .map(Value::Number)
-                .unwrap_or(Value::Null)
-        }
-        AvroValue::Double(d) => {
-            serde_json::Number::from_f64(*d)
-                .map(Value::Number)
-                .unwrap_or(Value::Null)
-        }
-        AvroValue::Bytes(bytes) => {
-            // Convert bytes to base64 string for JSON representation
-            Value::String(base64_encode(bytes))
-        }
-        AvroValue::String(s) => Value::String(s.clone()),
-        AvroValue::Fixed(_, bytes) => {
-            // Convert fixed bytes to base64 string
-            Value::String(base64_encode(bytes))
-        }
-        AvroValue::Enum(_, symbol) => Value::String(symbol.clone()),
-        AvroValue::Union(_, boxed_value) => avro_value_to_json(boxed_value)?,
-        AvroValue::Array(arr) => {
-            let mut json_arr = Vec::new();
-            for item in arr {
-                json_arr.push(avro_value_to_json(item)?);
-            }
-            Value::Array(json_arr)
-        }
-        AvroValue::Map(map) => {
-            let mut json_obj = serde_json::Map::new();
-            for (key, value) in map {
-                json_obj.insert(key.clone(), avro_value_to_json(value)?);
-            }
-            Value::Object(json_obj)
-        }
-        AvroValue::Record(fields) => {
-            let mut json_obj = serde_json::Map::new();
-            for (name, value) in fields {
-                json_obj.insert(name.clone(), avro_value_to_json(value)?);
-            }
-            Value::Object(json_obj)
-        }
-        AvroValue::Date(days) => {
-            // Convert days since epoch to ISO date string
-            Value::String(format!("days-since-epoch:{}", days))
-        }
-        AvroValue::TimeMillis(millis) => {
-            Value::String(format!("time-millis:{}", millis))
-        }
-        AvroValue::TimeMicros(micros) => {
-            Value::String(format!("time-micros:{}", micros))
-        }
-        AvroValue::TimestampMillis(millis) => {
-            Value::String(format!("timestamp-millis:{}", millis))
-        }
-        AvroValue::TimestampMicros(micros) => {
-            Value::String(format!("timestamp-micros:{}", micros))
-        }
-        AvroValue::Decimal(decimal) => {
-            // Convert decimal to debug string representation
-            Value::String(format!("decimal:{:?}", decimal))
-        }
-        AvroValue::Uuid(uuid) => Value::String(uuid.to_string()),
-        AvroValue::Duration(duration) => {
-            Value::String(format!("duration:{:?}:{:?}:{:?}", duration.months(), duration.days(), duration.millis()))
-        }
-        AvroValue::LocalTimestampMillis(millis) => {
-            Value::String(format!("local-timestamp-millis:{}", millis))
-        }
-        AvroValue::LocalTimestampMicros(micros) => {
-            Value::String(format!("local-timestamp-micros:{}", micros))
+    if let Some(every) = options.split.filter(|&every| every > 0) {
+        let Some(output_path) = output_file else {
+            return Err(ConvertError::InvalidOptions(
+                "Splitting the output needs an output file to name the chunks after".to_string(),
+            ));
+        };
+        let mut files = SplitFiles::create(Path::new(output_path), options.output_gzip, buffer_size)?;
+        let destination = Destination::Split { files: &mut files, every };
+        let records = write_records(readers, destination, false, options);
+        return finish_output(records, || files.finish());
+    }
+
+    if let Some(partition_by) = &options.partition_by {
+        let Some(output_path) = output_file else {
+            return Err(ConvertError::InvalidOptions(
+                "Partitioning the output needs an output file to name the partitions after".to_string(),
+            ));
+        };
+        if options.format != OutputFormat::Json || options.key_by.is_some() || options.envelope.is_some() {
+            return Err(ConvertError::InvalidOptions(
+                "Only newline-delimited JSON and JSON arrays can be partitioned".to_string(),
+            ));
         }
+        let mut files =
+            PartitionFiles::new(Path::new(output_path), partition_by.clone(), options.output_gzip, buffer_size);
+        let records = write_records(readers, Destination::Partitioned(&mut files), false, options);
+        return finish_output(records, || files.finish());
+    }
+
+    let Some(output_path) = output_file else {
+        use std::io::IsTerminal;
+        let is_terminal = std::io::stdout().is_terminal();
+        let mut stdout = BufWriter::with_capacity(buffer_size, std::io::stdout());
+        return write_output(readers, &mut stdout, is_terminal, options);
     };
 
-    Ok(json_value)
+    // Pipes, devices and the like can't be replaced by a rename, so they are written in place.
+    // Files are never terminals, whatever stdout happens to be.
+    if std::fs::metadata(output_path).is_ok_and(|metadata| !metadata.is_file()) {
+        let file = File::options()
+            .write(true)
+            .open(output_path)
+            .map_err(|err| io_error(format!("Failed to open output file {}", output_path), err))?;
+        return write_output(readers, &mut BufWriter::with_capacity(buffer_size, file), false, options);
+    }
+
+    let mut file = AtomicFile::create(Path::new(output_path), buffer_size)?;
+    let records = write_output(readers, &mut file, false, options);
+    finish_output(records, || file.commit())
 }
 
-pub fn base64_encode(bytes: &[u8]) -> String {
-    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-    let mut result = String::new();
-    
-    for chunk in bytes.chunks(3) {
-        let mut buf = [0u8; 3];
-        for (i, &b) in chunk.iter().enumerate() {
-            buf[i] = b;
-        }
-        
-        let b0 = buf[0] as usize;
-        let b1 = buf[1] as usize;
-        let b2 = buf[2] as usize;
-        
-        result.push(CHARS[b0 >> 2] as char);
-        result.push(CHARS[((b0 & 3) << 4) | (b1 >> 4)] as char);
-        
-        if chunk.len() > 1 {
-            result.push(CHARS[((b1 & 15) << 2) | (b2 >> 6)] as char);
-        } else {
-            result.push('=');
-        }
-        
-        if chunk.len() > 2 {
-            result.push(CHARS[b2 & 63] as char);
-        } else {
-            result.push('=');
-        }
+/// Converts the inputs into `output`, gzip'ing it when asked. Returns how many records were
+/// read.
+fn write_output<R: Read>(
+    readers: impl IntoIterator<Item = (String, Result<R>)>,
+    output: &mut dyn Write,
+    is_terminal: bool,
+    options: &ConvertOptions,
+) -> Result<u64> {
+    if !options.output_gzip {
+        return convert_readers(readers, output, is_terminal, options);
     }
-    
-    result
+    // The gzip trailer is only written by `finish`, so the encoder can't just be dropped
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    let records = convert_readers(readers, &mut encoder, false, options);
+    finish_output(records, || {
+        encoder
+            .finish()
+            .and_then(|output| output.flush())
+            .map_err(|err| io_error("Failed to write JSON output", err))
+    })
 }
 
-fn colorize_json(json_str: &str) -> String {
-    use colored::Colorize;
-    
-    let mut result = String::new();
-    let mut in_string = false;
-    let mut escape_next = false;
-    let mut chars = json_str.chars().peekable();
-    
-    while let Some(ch) = chars.next() {
-        match ch {
-            '"' if !escape_next => {
-                in_string = !in_string;
-                result.push_str(&ch.to_string().green().to_string());
-            }
-            '\\' if in_string && !escape_next => {
-                escape_next = true;
-                result.push_str(&ch.to_string().yellow().to_string());
-            }
-            _ if in_string => {
-                escape_next = false;
-                result.push_str(&ch.to_string().green().to_string());
-            }
-            ':' => {
-                result.push_str(&ch.to_string().cyan().to_string());
-            }
-            ',' => {
-                result.push_str(&ch.to_string().white().to_string());
-            }
-            '{' | '}' | '[' | ']' => {
-                result.push_str(&ch.to_string().blue().to_string());
-            }
-            _ if ch.is_ascii_digit() || ch == '.' || ch == '-' => {
-                // Look ahead to get the full number
-                let mut number = ch.to_string();
-                while let Some(&next_ch) = chars.peek() {
-                    if next_ch.is_ascii_digit() || next_ch == '.' || next_ch == 'e' || next_ch == 'E' || next_ch == '+' || next_ch == '-' {
-                        number.push(chars.next().unwrap());
-                    } else {
-                        break;
-                    }
-                }
-                result.push_str(&number.yellow().to_string());
-            }
-            _ if ch.is_alphabetic() => {
-                // Handle keywords like true, false, null
-                let mut keyword = ch.to_string();
-                while let Some(&next_ch) = chars.peek() {
-                    if next_ch.is_alphabetic() {
-                        keyword.push(chars.next().unwrap());
-                    } else {
-                        break;
-                    }
-                }
-                match keyword.as_str() {
-                    "true" | "false" => result.push_str(&keyword.red().to_string()),
-                    "null" => result.push_str(&keyword.purple().to_string()),
-                    _ => result.push_str(&keyword),
-                }
-            }
-            _ => {
-                result.push(ch);
-            }
-        }
+/// Opens an input, transparently decompressing it when it is gzip'd.
+fn open_avro_input(input: &Input, gzip: bool, buffer_size: usize) -> Result<Box<dyn Read>> {
+    let reader = input.open_with_buffer_size(buffer_size)?;
+    if gzip || input.is_gzip() {
+        Ok(Box::new(BufReader::new(GzipInput(MultiGzDecoder::new(reader)))))
+    } else {
+        Ok(reader)
     }
-    
-    result
 }
 
-pub fn write_json_output(
-    mut output: Box<dyn std::io::Write>,
-    records: Vec<Value>,
-    pretty: bool,
-    as_array: bool,
-    color: bool,
-) -> Result<()> {
-    use std::io::{self, IsTerminal, Write};
-    
-    // Determine if we should use colors (only if outputting to terminal and color is requested)
-    let use_color = color && io::stdout().is_terminal();
-    
-    if as_array {
-        // Output as a single JSON array
-        let json_array = Value::Array(records);
-        let json_str = if pretty {
-            serde_json::to_string_pretty(&json_array)?
-        } else {
-            serde_json::to_string(&json_array)?
-        };
-        
-        if use_color {
-            let colored = colorize_json(&json_str);
-            writeln!(output, "{}", colored)?;
-        } else {
-            writeln!(output, "{}", json_str)?;
-        }
-    } else {
-        // Output as newline-delimited JSON (NDJSON)
-        for record in records {
-            let json_str = if pretty {
-                serde_json::to_string_pretty(&record)?
-            } else {
-                serde_json::to_string(&record)?
-            };
-            
-            if use_color {
-                let colored = colorize_json(&json_str);
-                writeln!(output, "{}", colored)?;
-            } else {
-                writeln!(output, "{}", json_str)?;
+/// A gzip decompressor whose error for a stream that ends early carries [`GzipStreamEnded`],
+/// so a cut `.gz` file is reported as such rather than as a cut container. The error is no
+/// longer an `UnexpectedEof`, which apache-avro takes for the end of the file when it comes
+/// between blocks, so a stream cut there doesn't pass for a complete one.
+struct GzipInput<R>(MultiGzDecoder<R>);
+
+impl<R: Read> Read for GzipInput<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf).map_err(|err| match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => std::io::Error::new(std::io::ErrorKind::InvalidData, GzipStreamEnded),
+            _ => err,
+        })
+    }
+}
+
+/// Reads and parses an Avro schema (`.avsc`) file.
+pub fn read_schema_file(path: &str) -> Result<Schema> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|err| io_error(format!("Failed to read schema file {}", path), err))?;
+    let mut json: Value = serde_json::from_str(&json).map_err(apache_avro::Error::ParseSchemaJson)?;
+    mark_fixed_uuids(&mut json);
+    Ok(Schema::parse(&json)?)
+}
+
+/// Custom attribute that stands in for `"logicalType": "uuid"` on a `fixed` type. The
+/// specification allows UUIDs backed by `fixed(16)`, but apache_avro refuses to parse such a
+/// schema, so the logical type is moved out of its way and picked up again when rendering.
+const FIXED_UUID_ATTRIBUTE: &str = "avro-to-json.uuid";
+
+/// Replaces the `uuid` logical type of every `fixed` type in a schema with
+/// [`FIXED_UUID_ATTRIBUTE`], returning whether there were any.
+fn mark_fixed_uuids(schema: &mut Value) -> bool {
+    match schema {
+        Value::Object(object) => {
+            let is_fixed_uuid = object.get("type").is_some_and(|kind| kind == "fixed")
+                && object.get("logicalType").is_some_and(|logical_type| logical_type == "uuid");
+            if is_fixed_uuid {
+                object.remove("logicalType");
+                object.insert(FIXED_UUID_ATTRIBUTE.to_string(), Value::Bool(true));
             }
+            object.values_mut().fold(is_fixed_uuid, |marked, child| mark_fixed_uuids(child) | marked)
         }
+        Value::Array(items) => items.iter_mut().fold(false, |marked, item| mark_fixed_uuids(item) | marked),
+        _ => false,
     }
-    
-    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Starts reading an Avro container, resolving against `reader_schema` when one is given.
+fn open_container<'a, R: Read>(reader: R, reader_schema: Option<&'a Schema>) -> Result<Reader<'a, impl Read>> {
+    let reader = mark_header_fixed_uuids(reader)?;
+    let avro_reader = match reader_schema {
+        Some(schema) => Reader::with_schema(schema, reader),
+        None => Reader::new(reader),
+    };
+    avro_reader.map_err(container_error)
+}
+
+/// Rewrites a container header whose writer schema has `fixed` UUIDs, as
+/// [`mark_fixed_uuids`] does, so that `Reader` can parse it. Any other input is replayed
+/// untouched.
+fn mark_header_fixed_uuids<R: Read>(reader: R) -> Result<impl Read> {
     use apache_avro::types::Value as AvroValue;
 
-    #[test]
-    fn test_avro_null_to_json() {
-        let avro_value = AvroValue::Null;
-        let json_value = avro_value_to_json(&avro_value).unwrap();
-        assert_eq!(json_value, Value::Null);
+    let mut recorder = RecordingReader {
+        inner: reader,
+        recorded: Vec::new(),
+    };
+    let mut magic = [0u8; 4];
+    // Not a container; leave the error to `Reader`, which explains it properly
+    if recorder.read_exact(&mut magic).is_err() || magic != *b"Obj\x01" {
+        return Ok(std::io::Cursor::new(recorder.recorded).chain(recorder.inner));
     }
+    let map_schema = Schema::Map(Box::new(Schema::Bytes));
+    let mut metadata = match apache_avro::from_avro_datum(&map_schema, &mut recorder, None)? {
+        AvroValue::Map(metadata) => metadata,
+        _ => HashMap::new(),
+    };
+    let mut sync = [0u8; 16];
+    recorder.read_exact(&mut sync)?;
 
-    #[test]
-    fn test_avro_boolean_to_json() {
-        let avro_value = AvroValue::Boolean(true);
-        let json_value = avro_value_to_json(&avro_value).unwrap();
-        assert_eq!(json_value, Value::Bool(true));
+    let mut schema = match metadata.get("avro.schema") {
+        Some(AvroValue::Bytes(schema)) => serde_json::from_slice::<Value>(schema).unwrap_or_default(),
+        _ => Value::Null,
+    };
+    if !mark_fixed_uuids(&mut schema) {
+        return Ok(std::io::Cursor::new(recorder.recorded).chain(recorder.inner));
     }
+    metadata.insert("avro.schema".to_string(), AvroValue::Bytes(serde_json::to_vec(&schema)?));
+    let mut header = magic.to_vec();
+    header.extend(apache_avro::to_avro_datum(&map_schema, AvroValue::Map(metadata))?);
+    header.extend(sync);
+    Ok(std::io::Cursor::new(header).chain(recorder.inner))
+}
 
-    #[test]
-    fn test_avro_int_to_json() {
-        let avro_value = AvroValue::Int(42);
-        let json_value = avro_value_to_json(&avro_value).unwrap();
-        assert_eq!(json_value, Value::Number(42.into()));
+/// Turns apache_avro's opaque "codec not supported" into an error naming the cargo feature
+/// that enables the codec.
+fn container_error(err: apache_avro::Error) -> ConvertError {
+    match err {
+        apache_avro::Error::CodecNotSupported(codec) => {
+            let feature = match codec.as_str() {
+                "snappy" => Some("snappy"),
+                "zstandard" => Some("zstandard"),
+                "bzip2" => Some("bzip"),
+                "xz" => Some("xz"),
+                _ => None,
+            };
+            ConvertError::UnsupportedCodec { codec, feature }
+        }
+        other => other.into(),
     }
+}
 
-    #[test]
-    fn test_avro_string_to_json() {
-        let avro_value = AvroValue::String("hello".to_string());
-        let json_value = avro_value_to_json(&avro_value).unwrap();
-        assert_eq!(json_value, Value::String("hello".to_string()));
+/// Returns the writer schema embedded in an Avro container's header as pretty-printed JSON.
+/// Only the header is read; records are left untouched.
+pub fn print_avro_schema(input_file: &str) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&read_writer_schema(input_file)?)?)
+}
+
+/// Hash used to fingerprint a schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingerprintAlgorithm {
+    /// The 64-bit Rabin fingerprint (CRC-64-AVRO) that single-object encoding and schema
+    /// registries use, in its little-endian byte order.
+    Rabin,
+    Sha256,
+}
+
+/// Fingerprints the parsing canonical form of the writer schema embedded in an Avro
+/// container, returning it as lowercase hex. Like [`print_avro_schema`], only the header is
+/// read.
+pub fn schema_fingerprint(input_file: &str, algorithm: FingerprintAlgorithm) -> Result<String> {
+    let schema = read_writer_schema(input_file)?;
+    let fingerprint = match algorithm {
+        FingerprintAlgorithm::Rabin => schema.fingerprint::<apache_avro::rabin::Rabin>(),
+        FingerprintAlgorithm::Sha256 => schema.fingerprint::<sha2::Sha256>(),
+    };
+    Ok(fingerprint.to_string())
+}
+
+/// Reads the user metadata from an Avro container's header: every entry besides the `avro.`
+/// ones the format reserves, such as the schema and codec. Returns a JSON object with the
+/// keys in sorted order. Values that are valid UTF-8 become strings; anything else is
+/// base64-encoded. Only the header is read.
+pub fn read_metadata(input_file: &str) -> Result<Value> {
+    let reader = open_avro_input(&Input::from(input_file), false, DEFAULT_BUFFER_SIZE)?;
+    let avro_reader = open_container(reader, None)?;
+    let mut entries: Vec<_> = avro_reader.user_metadata().iter().collect();
+    entries.sort();
+    let metadata = entries
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match std::str::from_utf8(value) {
+                Ok(text) => text.to_string(),
+                Err(_) => base64_encode(value),
+            };
+            (key.clone(), Value::String(value))
+        })
+        .collect();
+    Ok(Value::Object(metadata))
+}
+
+/// Reads the writer schema from an Avro container's header.
+fn read_writer_schema(input_file: &str) -> Result<Schema> {
+    let reader = open_avro_input(&Input::from(input_file), false, DEFAULT_BUFFER_SIZE)?;
+    let avro_reader = open_container(reader, None)?;
+    Ok(avro_reader.writer_schema().clone())
+}
+
+/// Counts the records in an Avro container without converting them to JSON. Every record
+/// is still decoded, so a corrupt record fails the count.
+pub fn count_records(input_file: &str) -> Result<u64> {
+    let reader = open_avro_input(&Input::from(input_file), false, DEFAULT_BUFFER_SIZE)?;
+    let mut count = 0;
+    for record in open_container(reader, None)? {
+        record?;
+        count += 1;
     }
+    Ok(count)
+}
 
-    #[test]
-    fn test_avro_array_to_json() {
+/// A summary of an Avro container, as reported by `--info`.
+#[derive(Debug, Clone)]
+pub struct AvroInfo {
+    /// The writer schema embedded in the header.
+    pub schema: Schema,
+    /// The block compression codec named in the header (`null` when uncompressed).
+    pub codec: String,
+    /// Number of records in the container.
+    pub record_count: u64,
+}
+
+impl AvroInfo {
+    /// The Avro type name of the top-level schema, e.g. `record`.
+    pub fn schema_type(&self) -> &'static str {
+        avro_type_name(SchemaKind::from(&self.schema))
+    }
+
+    /// Field names of a top-level record schema; empty for any other type.
+    pub fn field_names(&self) -> Vec<&str> {
+        match &self.schema {
+            Schema::Record(record) => record.fields.iter().map(|field| field.name.as_str()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::json!({
+            "type": self.schema_type(),
+            "name": self.schema.name().map(|name| name.fullname(None)),
+            "fields": self.field_names(),
+            "codec": self.codec,
+            "records": self.record_count,
+        })
+    }
+}
+
+impl fmt::Display for AvroInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Type:    {}", self.schema_type())?;
+        if let Some(name) = self.schema.name() {
+            writeln!(f, "Name:    {}", name.fullname(None))?;
+        }
+        if let Schema::Record(_) = self.schema {
+            writeln!(f, "Fields:  {}", self.field_names().join(", "))?;
+        }
+        writeln!(f, "Codec:   {}", self.codec)?;
+        write!(f, "Records: {}", self.record_count)
+    }
+}
+
+/// Reads an Avro container's schema, codec and record count without converting anything.
+pub fn avro_info(input_file: &str) -> Result<AvroInfo> {
+    let reader = open_avro_input(&Input::from(input_file), false, DEFAULT_BUFFER_SIZE)?;
+    let (metadata, reader) = read_container_metadata(reader)?;
+    let codec = match metadata.get("avro.codec") {
+        Some(codec) => String::from_utf8_lossy(codec).into_owned(),
+        None => "null".to_string(),
+    };
+
+    let avro_reader = open_container(reader, None)?;
+    let schema = avro_reader.writer_schema().clone();
+    let mut record_count = 0;
+    for record in avro_reader {
+        record?;
+        record_count += 1;
+    }
+
+    Ok(AvroInfo {
+        schema,
+        codec,
+        record_count,
+    })
+}
+
+/// Parses the metadata map from a container header, which `Reader` doesn't expose in full.
+/// The bytes consumed are replayed in front of the rest of the input, so the returned
+/// reader still starts at the beginning of the container.
+fn read_container_metadata<R: Read>(reader: R) -> Result<(HashMap<String, Vec<u8>>, impl Read)> {
+    use apache_avro::types::Value as AvroValue;
+
+    let mut recorder = RecordingReader {
+        inner: reader,
+        recorded: Vec::new(),
+    };
+    let mut magic = [0u8; 4];
+    recorder.read_exact(&mut magic)?;
+
+    let mut metadata = HashMap::new();
+    // Not a container; leave the error to `Reader`, which explains it properly
+    if magic == *b"Obj\x01" {
+        let map_schema = Schema::Map(Box::new(Schema::Bytes));
+        if let AvroValue::Map(entries) = apache_avro::from_avro_datum(&map_schema, &mut recorder, None)? {
+            for (key, value) in entries {
+                if let AvroValue::Bytes(bytes) = value {
+                    metadata.insert(key, bytes);
+                }
+            }
+        }
+    }
+
+    Ok((metadata, std::io::Cursor::new(recorder.recorded).chain(recorder.inner)))
+}
+
+/// Keeps a copy of everything read through it.
+struct RecordingReader<R> {
+    inner: R,
+    recorded: Vec<u8>,
+}
+
+impl<R: Read> Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.recorded.extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Checks every record in an Avro container against the container's writer schema,
+/// returning the number of records on success. The first record that doesn't conform is
+/// reported as [`ConvertError::InvalidRecord`] with its zero-based index.
+pub fn validate_avro(input_file: &str) -> Result<u64> {
+    let reader = open_avro_input(&Input::from(input_file), false, DEFAULT_BUFFER_SIZE)?;
+    let avro_reader = open_container(reader, None)?;
+    let schema = avro_reader.writer_schema().clone();
+
+    // Re-encoding through a writer is the only public way to get apache_avro's reason for a
+    // validation failure; the encoded bytes are thrown away
+    let mut validator = apache_avro::Writer::new(&schema, std::io::sink());
+    let mut count = 0;
+    for record in avro_reader {
+        validate_record(&mut validator, count, &record?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn validate_record<W: Write>(
+    validator: &mut apache_avro::Writer<'_, W>,
+    index: u64,
+    record: &apache_avro::types::Value,
+) -> Result<()> {
+    match validator.append_value_ref(record) {
+        Ok(_) => Ok(()),
+        Err(apache_avro::Error::ValidationWithReason(reason)) => Err(ConvertError::InvalidRecord { index, reason }),
+        Err(err) => Err(ConvertError::InvalidRecord {
+            index,
+            reason: err.to_string(),
+        }),
+    }
+}
+
+/// Decodes an Avro container lazily, yielding one JSON value per record.
+///
+/// The header is read up front, so an invalid container fails immediately. After that,
+/// a record that fails to decode is yielded as an `Err` item; the underlying reader stops
+/// after its first error, so that is also the last item.
+pub fn avro_json_records<R: Read>(reader: R) -> Result<impl Iterator<Item = Result<Value>>> {
+    avro_json_records_with_options(reader, &JsonOptions::default())
+}
+
+pub fn avro_json_records_with_options<R: Read>(
+    reader: R,
+    options: &JsonOptions,
+) -> Result<impl Iterator<Item = Result<Value>>> {
+    let avro_reader = open_container(reader, None)?;
+    let writer_schema = SchemaContext::new(avro_reader.writer_schema())?;
+    let options = options.clone();
+
+    Ok(avro_reader.map(move |record| writer_schema.value_to_json(&record?, &options)))
+}
+
+/// Named types of a schema, used to follow `Schema::Ref`s.
+type Names = HashMap<Name, Schema>;
+
+/// A container's writer schema along with its named types, kept around so values can be
+/// rendered with details only the schema knows, such as decimal scale.
+struct SchemaContext {
+    schema: Schema,
+    names: Names,
+}
+
+impl SchemaContext {
+    fn new(schema: &Schema) -> Result<Self> {
+        let names = ResolvedSchema::try_from(schema)?
+            .get_names()
+            .iter()
+            .map(|(name, schema)| (name.clone(), (*schema).clone()))
+            .collect();
+
+        Ok(SchemaContext {
+            schema: schema.clone(),
+            names,
+        })
+    }
+
+    /// The schema's type, and its full name when it is a named type, for log messages.
+    fn describe(&self) -> String {
+        let kind = avro_type_name(SchemaKind::from(&self.schema));
+        match self.schema.name() {
+            Some(name) => format!("{} ({})", kind, name.fullname(None)),
+            None => kind.to_string(),
+        }
+    }
+
+    fn value_to_json(&self, value: &apache_avro::types::Value, options: &JsonOptions) -> Result<Value> {
+        value_to_json(value, Some(&self.schema), &self.names, options)
+    }
+
+    /// Rewrites dotted field paths so that a segment naming a record field by one of its
+    /// aliases uses the field's name instead, following each path down through the schema.
+    /// Field names are renamed to `key_style`, as they are in the output. Segments the schema
+    /// doesn't know are kept as they are.
+    fn resolve_field_aliases(&self, paths: &[Vec<String>], key_style: KeyStyle) -> Vec<Vec<String>> {
+        paths
+            .iter()
+            .map(|path| {
+                let mut schema = Some(&self.schema);
+                path.iter()
+                    .map(|segment| {
+                        let (name, child) = resolve_path_segment(schema, segment, &self.names, key_style);
+                        schema = child;
+                        name
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Follows one segment of a field path from `schema`, returning the segment with a field
+/// alias replaced by the field's name, and the schema of what it leads to. Array and map
+/// segments (an index or a key) lead to the item or value schema; union branches are tried
+/// in turn.
+fn resolve_path_segment<'s>(
+    schema: Option<&'s Schema>,
+    segment: &str,
+    names: &'s Names,
+    key_style: KeyStyle,
+) -> (String, Option<&'s Schema>) {
+    match resolve_schema(schema, names) {
+        // `lookup` holds every field's aliases as well as its name
+        Some(Schema::Record(record)) => match record.lookup.get(segment) {
+            Some(&position) => {
+                let field = &record.fields[position];
+                (rename_key(&field.name, key_style), Some(&field.schema))
+            }
+            None => (segment.to_string(), None),
+        },
+        Some(Schema::Array(items)) => (segment.to_string(), Some(items.as_ref())),
+        Some(Schema::Map(values)) => (segment.to_string(), Some(values.as_ref())),
+        Some(Schema::Union(union)) => union
+            .variants()
+            .iter()
+            .map(|variant| resolve_path_segment(Some(variant), segment, names, key_style))
+            .find(|(_, child)| child.is_some())
+            .unwrap_or_else(|| (segment.to_string(), None)),
+        _ => (segment.to_string(), None),
+    }
+}
+
+/// Converts the records of several containers, one after another, into `output`.
+/// `is_terminal` says whether `output` is an interactive terminal, which decides automatic
+/// coloring. Returns how many records were read, including any skipped or filtered out.
+fn convert_readers<R: Read>(
+    readers: impl IntoIterator<Item = (String, Result<R>)>,
+    output: &mut dyn Write,
+    is_terminal: bool,
+    options: &ConvertOptions,
+) -> Result<u64> {
+    if options.no_final_newline && options.format == OutputFormat::Json {
+        let mut output = WithoutFinalNewline::new(output);
+        return write_records(readers, Destination::Single(&mut output), is_terminal, options);
+    }
+    write_records(readers, Destination::Single(output), is_terminal, options)
+}
+
+/// [`convert_readers`] into either a single output or split files.
+fn write_records<R: Read>(
+    readers: impl IntoIterator<Item = (String, Result<R>)>,
+    output: Destination<'_>,
+    is_terminal: bool,
+    options: &ConvertOptions,
+) -> Result<u64> {
+    let skip = options.skip.unwrap_or(0);
+    let limit = match (options.tail, &options.sample) {
+        (None, None) => options.limit.unwrap_or(usize::MAX),
+        _ => usize::MAX,
+    };
+    // Nothing past the highest wanted index needs to be read
+    let last_index = options.records.as_ref().and_then(|records| records.last()).copied();
+    let mut writer = RecordWriter::new(output, is_terminal, options);
+    let mut index: usize = 0;
+    let mut seen_keys = HashSet::new();
+    let mut skipped = 0;
+    let mut tail = options.tail.map(|_| VecDeque::new());
+    let mut reservoir = options.sample.as_ref().map(Reservoir::new);
+    // Single-object and framed streams have no blocks to end partway through
+    let reads_containers = options.single_object_schema.is_none() && options.framed_schema.is_none();
+    let mut stats = options.stats.then(LogicalTypeStats::new);
+    // A truncated input ends the conversion, but only once what was read before the cut is
+    // written out
+    let mut truncated = None;
+
+    // Without a pool, batches are a single record, which keeps plain streaming as before
+    let pool = match options.jobs {
+        Some(jobs) if jobs > 1 => Some(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build()
+                .map_err(|err| std::io::Error::other(format!("Failed to start worker threads: {}", err)))?,
+        ),
+        _ => None,
+    };
+    let batch_size = pool.as_ref().map_or(1, |pool| pool.current_num_threads() * RECORDS_PER_JOB);
+    let progress = options.progress.then(progress_spinner);
+
+    'inputs: for (name, reader) in readers {
+        let (mut records, writer_schema) = open_records(reader?, options)?;
+        tracing::info!("Reading {} with a {} schema", name, writer_schema.describe());
+        writer.start_input(&writer_schema.schema)?;
+        let select = options
+            .select
+            .as_ref()
+            .map(|paths| writer_schema.resolve_field_aliases(paths, options.json.key_style));
+        let mut file_index = 0;
+
+        loop {
+            // Records are pulled one at a time, so a limit stops reading as soon as it is reached
+            let mut batch = Vec::new();
+            let mut decode_failed = false;
+            let wanted = |index: usize| last_index.is_none_or(|last| index <= last);
+            while batch.len() < batch_size && writer.written + batch.len() < limit && wanted(index) {
+                let Some(record) = records.next() else {
+                    break;
+                };
+                index += 1;
+                file_index += 1;
+                if index.is_multiple_of(LOG_INTERVAL) {
+                    tracing::debug!("Read {} records", index);
+                }
+                let record = match record {
+                    Ok(record) => record,
+                    Err(err) => {
+                        // Told apart from corrupt data, and never skipped, so partial files can't
+                        // pass unnoticed
+                        if let Some(kind) = err.truncation().filter(|_| reads_containers) {
+                            truncated = Some(ConvertError::Truncated {
+                                input: name.clone(),
+                                kind,
+                                records: file_index as u64 - 1,
+                                source: Box::new(err),
+                            });
+                            decode_failed = true;
+                            break;
+                        }
+                        if options.on_error != OnError::Skip {
+                            return Err(err);
+                        }
+                        // Decoding can't resume past a corrupt record, so the rest of this input is lost
+                        let err = ConvertError::Record {
+                            index: index as u64 - 1,
+                            source: Box::new(err),
+                        };
+                        report_skipped_record(progress.as_ref(), &name, &err);
+                        skipped += 1;
+                        decode_failed = true;
+                        break;
+                    }
+                };
+
+                // Skipped records are still decoded to advance the reader, but never converted
+                if index > skip && options.records.as_ref().is_none_or(|records| records.contains(&(index - 1))) {
+                    batch.push((index - 1, file_index - 1, record));
+                }
+            }
+            if batch.is_empty() && !decode_failed {
+                if writer.written < limit && wanted(index) {
+                    continue 'inputs;
+                }
+                break 'inputs;
+            }
+
+            if let Some(stats) = &mut stats {
+                for (_, _, record) in &batch {
+                    stats.add(record);
+                }
+            }
+
+            let convert = |(index, file_index, record): &(usize, usize, apache_avro::types::Value)| {
+                let json_value =
+                    convert_record(&writer_schema, record, select.as_deref(), options).map_err(|err| {
+                        ConvertError::Record {
+                            index: *index as u64,
+                            source: Box::new(err),
+                        }
+                    })?;
+                Ok(json_value.map(|mut converted| {
+                    if let Some(prefix) = &options.metadata_prefix {
+                        converted.json = with_metadata(converted.json, prefix, &name, *file_index);
+                    }
+                    converted
+                }))
+            };
+            // Indexed parallel iterators collect in input order, so output order is unchanged
+            let converted: Vec<Result<Option<ConvertedRecord>>> = match &pool {
+                Some(pool) => pool.install(|| batch.par_iter().map(convert).collect()),
+                None => batch.iter().map(convert).collect(),
+            };
+            for converted in converted {
+                let converted = match converted {
+                    Ok(Some(converted)) => converted,
+                    Ok(None) => continue,
+                    Err(err) if options.on_error == OnError::Skip => {
+                        report_skipped_record(progress.as_ref(), &name, &err);
+                        skipped += 1;
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                };
+                // Deduplication needs to see records in order, so it happens here rather than
+                // in the workers
+                if let Some(dedup) = &options.dedup {
+                    let drop = match converted.dedup_key {
+                        Some(key) => !seen_keys.insert(key),
+                        None => dedup.drop_missing,
+                    };
+                    if drop {
+                        continue;
+                    }
+                }
+                if let (Some(tail), Some(tail_len)) = (&mut tail, options.tail) {
+                    if tail.len() == tail_len {
+                        tail.pop_front();
+                    }
+                    if tail_len > 0 {
+                        tail.push_back(converted.json);
+                    }
+                    continue;
+                }
+                if let Some(reservoir) = &mut reservoir {
+                    reservoir.add(converted.json);
+                    continue;
+                }
+                writer.write(converted.json)?;
+                if let Some(progress) = &progress {
+                    if writer.written.is_multiple_of(PROGRESS_INTERVAL) {
+                        progress.set_position(writer.written as u64);
+                    }
+                }
+            }
+            if truncated.is_some() {
+                break 'inputs;
+            }
+            if decode_failed {
+                continue 'inputs;
+            }
+        }
+    }
+
+    for json_value in tail.into_iter().flatten() {
+        writer.write(json_value)?;
+    }
+    for json_value in reservoir.map(Reservoir::into_items).into_iter().flatten() {
+        writer.write(json_value)?;
+    }
+    if let Some(progress) = &progress {
+        progress.set_position(writer.written as u64);
+        progress.finish();
+    }
+    if let Some(records) = &options.records {
+        let missing: Vec<String> = records.range(index..).map(usize::to_string).collect();
+        if !missing.is_empty() {
+            let plural = if missing.len() == 1 { "" } else { "s" };
+            tracing::warn!("Input ends before record{} {}", plural, missing.join(", "));
+        }
+    }
+    let written = writer.written;
+    let profile = writer.finish()?;
+    tracing::info!("Read {} records and wrote {}", index, written);
+    if let (Some(profile), Some(profile_options)) = (profile, &options.profile) {
+        write_profile(&profile, profile_options)?;
+    }
+    if let Some(stats) = stats {
+        eprintln!("{}", stats);
+    }
+    if options.on_error == OnError::Skip {
+        tracing::warn!("Skipped {} record{}", skipped, if skipped == 1 { "" } else { "s" });
+    }
+    match truncated {
+        Some(err) => Err(err),
+        None => Ok(index as u64),
+    }
+}
+
+/// Runs `finish` to complete an output when the conversion succeeded or only found its
+/// input truncated, so the records read before the cut are kept as well-formed output. The
+/// conversion's own result is returned either way.
+fn finish_output<T>(result: Result<T>, finish: impl FnOnce() -> Result<()>) -> Result<T> {
+    match result {
+        Err(err) if !matches!(err, ConvertError::Truncated { .. }) => Err(err),
+        result => {
+            finish()?;
+            result
+        }
+    }
+}
+
+/// Writes a field profile as pretty JSON to its sidecar file, or to stderr.
+fn write_profile(profile: &FieldProfile, options: &ProfileOptions) -> Result<()> {
+    let report = serde_json::to_string_pretty(&profile.to_json())?;
+    match &options.output {
+        Some(path) => {
+            let mut file = AtomicFile::create(Path::new(path), DEFAULT_BUFFER_SIZE)?;
+            writeln!(file, "{}", report)?;
+            file.commit()
+        }
+        None => {
+            eprintln!("{}", report);
+            Ok(())
+        }
+    }
+}
+
+/// Logs a record dropped under [`OnError::Skip`] as a warning, clearing the progress spinner
+/// out of the way first.
+fn report_skipped_record(progress: Option<&indicatif::ProgressBar>, input: &str, err: &ConvertError) {
+    let report = || tracing::warn!("Skipping a record from {}: {}", input, err);
+    match progress {
+        Some(progress) => progress.suspend(report),
+        None => report(),
+    }
+}
+
+/// How many records pass between progress updates.
+const PROGRESS_INTERVAL: usize = 1000;
+
+/// How many records pass between debug log lines counting them.
+const LOG_INTERVAL: usize = 100_000;
+
+/// A spinner on stderr, so it never mixes with JSON written to stdout. indicatif draws
+/// nothing when stderr isn't a terminal.
+fn progress_spinner() -> indicatif::ProgressBar {
+    let progress = indicatif::ProgressBar::with_draw_target(None, indicatif::ProgressDrawTarget::stderr());
+    progress.set_style(
+        indicatif::ProgressStyle::with_template("{spinner} {human_pos} records converted ({per_sec}, {elapsed})")
+            .expect("progress template is valid"),
+    );
+    progress.enable_steady_tick(std::time::Duration::from_millis(100));
+    progress
+}
+
+/// Wraps a converted record as `{"<prefix>file": ..., "<prefix>index": ..., "<prefix>record": ...}`.
+fn with_metadata(record: Value, prefix: &str, file: &str, index: usize) -> Value {
+    let mut wrapper = serde_json::Map::new();
+    wrapper.insert(format!("{}file", prefix), Value::from(file));
+    wrapper.insert(format!("{}index", prefix), Value::from(index));
+    wrapper.insert(format!("{}record", prefix), record);
+    Value::Object(wrapper)
+}
+
+/// How many records each worker gets per batch with `--jobs`. Large enough to keep the
+/// workers busy, small enough that memory stays bounded. Workers return JSON values, which
+/// are serialized to text on the writing thread.
+const RECORDS_PER_JOB: usize = 1024;
+
+/// A converted record, along with the key it is deduplicated by.
+struct ConvertedRecord {
+    json: Value,
+    dedup_key: Option<String>,
+}
+
+/// Converts one decoded record to JSON and applies the per-record transforms, or returns
+/// `None` when the record is filtered out.
+/// `select` stands in for `options.select`, with field aliases already resolved.
+fn convert_record(
+    schema: &SchemaContext,
+    record: &apache_avro::types::Value,
+    select: Option<&[Vec<String>]>,
+    options: &ConvertOptions,
+) -> Result<Option<ConvertedRecord>> {
+    let mut json_value = schema.value_to_json(record, &options.json)?;
+    if let Some(filter) = &options.filter {
+        if !record_matches(&json_value, filter) {
+            return Ok(None);
+        }
+    }
+    let dedup_key = options.dedup.as_ref().and_then(|dedup| dedup_key(&json_value, dedup));
+    if let Some(paths) = select {
+        json_value = project_value(&json_value, paths);
+    }
+    if options.omit_nulls {
+        json_value = omit_nulls_with_options(json_value, options.omit_null_items);
+    }
+    if let Some(repr) = &options.null_as {
+        json_value = replace_nulls(json_value, repr);
+    }
+    if let Some(flatten) = &options.flatten {
+        json_value = flatten_value_with_options(json_value, flatten);
+    }
+    if options.sort_keys {
+        json_value = sort_keys(json_value);
+    }
+    // The record is replaced by its line, which the writer prints as it is
+    if let Some(extract) = &options.extract {
+        let value = extract_pointer(&json_value, &extract.pointer);
+        if value.is_none() && extract.skip_missing {
+            return Ok(None);
+        }
+        json_value = Value::String(extracted_line(value.as_ref()));
+    }
+    Ok(Some(ConvertedRecord {
+        json: json_value,
+        dedup_key,
+    }))
+}
+
+type Records<'a> = Box<dyn Iterator<Item = Result<apache_avro::types::Value>> + 'a>;
+
+/// Starts decoding one input, returning its records along with the schema they were
+/// written with.
+fn open_records<'a, R: Read + 'a>(reader: R, options: &'a ConvertOptions) -> Result<(Records<'a>, SchemaContext)> {
+    if let Some(schema) = &options.framed_schema {
+        let records = FramedDatums {
+            reader: BufReader::new(reader),
+            schema,
+            offset: 0,
+        };
+        return Ok((Box::new(records), SchemaContext::new(schema)?));
+    }
+    match &options.single_object_schema {
+        Some(schema) => {
+            let records = SingleObjects {
+                reader: BufReader::new(reader),
+                decoder: apache_avro::GenericSingleObjectReader::new(schema.clone())?,
+            };
+            Ok((Box::new(records), SchemaContext::new(schema)?))
+        }
+        None => {
+            // Resolved records follow the reader schema, so that's the one to render them with
+            let (avro_reader, schema) = match &options.reader_schema {
+                Some(reader_schema) => {
+                    (open_container(reader, Some(reader_schema))?, SchemaContext::new(reader_schema)?)
+                }
+                None => {
+                    let avro_reader = open_container(reader, None)?;
+                    let writer_schema = SchemaContext::new(avro_reader.writer_schema())?;
+                    (avro_reader, writer_schema)
+                }
+            };
+            Ok((Box::new(avro_reader.map(|record| Ok(record?))), schema))
+        }
+    }
+}
+
+/// Decodes back-to-back single-object encoded messages (`C3 01`, the schema's 8-byte Rabin
+/// fingerprint, then the datum) until the input runs out.
+struct SingleObjects<R> {
+    reader: BufReader<R>,
+    decoder: apache_avro::GenericSingleObjectReader,
+}
+
+impl<R: Read> Iterator for SingleObjects<R> {
+    type Item = Result<apache_avro::types::Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use std::io::BufRead;
+
+        // Only a clean end between messages ends the stream; a truncated message is an error
+        match self.reader.fill_buf() {
+            Ok([]) => None,
+            Ok(_) => Some(self.decoder.read_value(&mut self.reader).map_err(Into::into)),
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+/// Decodes datums that each follow their length as a 4-byte big-endian integer, until the
+/// input runs out.
+struct FramedDatums<'s, R> {
+    reader: BufReader<R>,
+    schema: &'s Schema,
+    /// Where the next frame starts, for error messages.
+    offset: u64,
+}
+
+impl<R: Read> FramedDatums<'_, R> {
+    fn read_frame(&mut self) -> Result<apache_avro::types::Value> {
+        let truncated = |expected: u64, found: u64, what: &str| ConvertError::InvalidFrame {
+            offset: self.offset,
+            reason: format!("input ends after {} of the {} bytes of its {}", found, expected, what),
+        };
+        let mut prefix = Vec::with_capacity(4);
+        (&mut self.reader).take(4).read_to_end(&mut prefix)?;
+        if prefix.len() < 4 {
+            return Err(truncated(4, prefix.len() as u64, "length prefix"));
+        }
+        let len = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as u64;
+        // Read rather than allocated up front, so a corrupt length can't demand gigabytes
+        let mut datum = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut datum)?;
+        if (datum.len() as u64) < len {
+            return Err(truncated(len, datum.len() as u64, "datum"));
+        }
+
+        let mut remaining = &datum[..];
+        let value = apache_avro::from_avro_datum(self.schema, &mut remaining, None)?;
+        if !remaining.is_empty() {
+            return Err(ConvertError::InvalidFrame {
+                offset: self.offset,
+                reason: format!("datum ends {} bytes before the end of its {}-byte frame", remaining.len(), len),
+            });
+        }
+        self.offset += 4 + len;
+        Ok(value)
+    }
+}
+
+impl<R: Read> Iterator for FramedDatums<'_, R> {
+    type Item = Result<apache_avro::types::Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use std::io::BufRead;
+
+        // Only a clean end between frames ends the stream; a truncated frame is an error
+        match self.reader.fill_buf() {
+            Ok([]) => None,
+            Ok(_) => Some(self.read_frame()),
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+/// Where a [`RecordWriter`] writes to.
+enum Destination<'w> {
+    Single(&'w mut dyn Write),
+    /// A new file every `every` records.
+    Split { files: &'w mut SplitFiles, every: usize },
+    /// A file per partition, picked by each record's value.
+    Partitioned(&'w mut PartitionFiles),
+}
+
+impl Destination<'_> {
+    fn output(&mut self) -> &mut dyn Write {
+        match self {
+            Destination::Single(output) => *output,
+            Destination::Split { files, .. } => files.current(),
+            Destination::Partitioned(_) => unreachable!("partitioned output is picked by each record"),
+        }
+    }
+}
+
+/// Writes converted records out in the requested layout.
+struct RecordWriter<'w> {
+    destination: Destination<'w>,
+    /// Indentation for pretty output; `None` writes compact JSON.
+    pretty: Option<Indent>,
+    use_color: bool,
+    format: OutputFormat,
+    flush_every: Option<usize>,
+    /// Records held back for CSV, YAML array and keyed output, which can only be written
+    /// once complete.
+    buffered: Option<Vec<Value>>,
+    max_buffered: Option<usize>,
+    key_by: Option<KeyBy>,
+    envelope: Option<Envelope>,
+    /// The schema shown in the envelope, from the first input.
+    envelope_schema: Option<Schema>,
+    /// For a JSON array, the number of elements written to the current output so far.
+    array_len: Option<usize>,
+    /// Records are lines already rendered by [`ConvertOptions::extract`], written as they are.
+    raw_lines: bool,
+    profile: Option<FieldProfile>,
+    written: usize,
+}
+
+impl<'w> RecordWriter<'w> {
+    fn new(destination: Destination<'w>, is_terminal: bool, options: &ConvertOptions) -> Self {
+        let raw_lines = options.extract.is_some();
+        let key_by = options.key_by.clone().filter(|_| options.format == OutputFormat::Json && !raw_lines);
+        let envelope = options
+            .envelope
+            .clone()
+            .filter(|_| options.format == OutputFormat::Json && !raw_lines && key_by.is_none());
+        RecordWriter {
+            destination,
+            // Strict JSON Lines needs every record on a single line
+            pretty: (options.pretty && (options.as_array || !options.jsonl)).then_some(options.indent),
+            use_color: should_colorize(options.color, is_terminal),
+            format: options.format,
+            flush_every: options.flush_every.filter(|&every| every > 0),
+            // A CSV header has to list the columns of every record, a YAML sequence is a
+            // single document, and a MessagePack array starts with its length
+            buffered: (!raw_lines
+                && (options.format == OutputFormat::Csv
+                    || options.as_array && (is_yaml(options.format) || is_msgpack(options.format))
+                    || key_by.is_some()
+                    || envelope.is_some()))
+            .then(Vec::new),
+            max_buffered: options.max_records_in_memory,
+            // JSON arrays are streamed like NDJSON records, between brackets and commas
+            array_len: (options.as_array
+                && key_by.is_none()
+                && envelope.is_none()
+                && options.format == OutputFormat::Json
+                && !raw_lines)
+                .then_some(0),
+            key_by,
+            envelope,
+            envelope_schema: None,
+            raw_lines,
+            profile: options.profile.as_ref().map(|profile| FieldProfile::new(profile.deep)),
+            written: 0,
+        }
+    }
+
+    /// Records the schema of the next input, for the envelope. Inputs with different schemas
+    /// can't share one.
+    fn start_input(&mut self, schema: &Schema) -> Result<()> {
+        if self.envelope.is_none() {
+            return Ok(());
+        }
+        match &self.envelope_schema {
+            Some(envelope_schema) if envelope_schema != schema => Err(ConvertError::InvalidOptions(
+                "Inputs with different schemas can't share an envelope".to_string(),
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.envelope_schema = Some(schema.clone());
+                Ok(())
+            }
+        }
+    }
+
+    fn write(&mut self, record: Value) -> Result<()> {
+        if let Destination::Split { every, .. } = self.destination {
+            if self.written > 0 && self.written.is_multiple_of(every) {
+                self.finish_chunk()?;
+                if let Destination::Split { files, .. } = &mut self.destination {
+                    files.next_chunk()?;
+                }
+            }
+        }
+
+        self.written += 1;
+        if let Some(profile) = &mut self.profile {
+            profile.add(&record);
+        }
+        match &mut self.buffered {
+            Some(records) => {
+                if let Some(max) = self.max_buffered.filter(|&max| records.len() >= max) {
+                    return Err(ConvertError::TooManyRecordsInMemory(max));
+                }
+                records.push(record);
+            }
+            // NDJSON records and JSON array elements are written as soon as they are
+            // decoded, so memory use stays flat regardless of input size
+            None => {
+                let (output, array_len) = match &mut self.destination {
+                    Destination::Partitioned(files) => {
+                        let (output, records) = files.partition(&record)?.output();
+                        (output, self.array_len.is_some().then_some(records))
+                    }
+                    destination => (destination.output(), self.array_len.as_mut()),
+                };
+                match array_len {
+                    Some(len) => {
+                        write_json_array_element(output, &record, *len, self.pretty, self.use_color)?;
+                        *len += 1;
+                    }
+                    None if self.raw_lines => writeln!(output, "{}", record.as_str().unwrap_or_default())?,
+                    None => write_record(output, &record, self.format, self.pretty, self.use_color)?,
+                }
+                if self.flush_every.is_some_and(|every| self.written.is_multiple_of(every)) {
+                    output.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes out whatever is buffered, completing the current output.
+    fn finish_chunk(&mut self) -> Result<()> {
+        // Partitions are only ever streamed to, never buffered
+        if let Destination::Partitioned(files) = &mut self.destination {
+            for partition in files.partitions_mut() {
+                let (output, records) = partition.output();
+                if self.array_len.is_some() {
+                    write_json_array_end(output, *records, self.pretty, self.use_color)?;
+                }
+                output.flush()?;
+            }
+            return Ok(());
+        }
+        let output = self.destination.output();
+        match self.buffered.as_mut().map(std::mem::take) {
+            Some(records) if self.format == OutputFormat::Csv => write_csv_values(output, records),
+            Some(records) => match (&self.key_by, &self.envelope) {
+                (Some(key_by), _) => {
+                    let object = records_to_keyed_object(records, key_by)?;
+                    write_record(output, &object, self.format, self.pretty, self.use_color)?;
+                    Ok(output.flush()?)
+                }
+                (None, Some(envelope)) => {
+                    let schema = serde_json::to_value(&self.envelope_schema)?;
+                    let object = records_to_envelope(schema, records, envelope);
+                    write_record(output, &object, self.format, self.pretty, self.use_color)?;
+                    Ok(output.flush()?)
+                }
+                (None, None) => write_values(output, records, self.format, self.pretty, true, self.use_color),
+            },
+            None => {
+                if let Some(len) = &mut self.array_len {
+                    write_json_array_end(output, std::mem::take(len), self.pretty, self.use_color)?;
+                }
+                Ok(output.flush()?)
+            }
+        }
+    }
+
+    /// Completes the output, returning the profile of what was written when one was asked for.
+    fn finish(mut self) -> Result<Option<FieldProfile>> {
+        self.finish_chunk()?;
+        Ok(self.profile)
+    }
+}
+
+/// Controls how individual Avro values are rendered as JSON.
+#[derive(Debug, Clone, Default)]
+pub struct JsonOptions {
+    /// Render logical types using the legacy tagged strings
+    /// (e.g. `"timestamp-millis:1700000000000"`) instead of ISO-8601.
+    pub legacy_logical_types: bool,
+    /// Text encoding used for `bytes` and `fixed` values.
+    pub bytes_encoding: BytesEncoding,
+    /// Alphabet and padding used for `bytes` and `fixed` values encoded as base64.
+    pub base64_mode: Base64Mode,
+    /// Prefix hex-encoded `bytes` and `fixed` values with `0x`.
+    pub hex_prefix: bool,
+    /// Render `bytes` values that hold valid UTF-8 as plain strings. Anything else still
+    /// uses `bytes_encoding`.
+    pub bytes_as_text: bool,
+    /// Like `bytes_as_text`, but for `fixed` values.
+    pub fixed_as_text: bool,
+    /// Render `bytes` and `fixed` values as `{"base64": "...", "len": N}` instead of a bare
+    /// string, so their length is visible. The key names the encoding used: `base64`, `hex`,
+    /// or `text` for values passed through by `bytes_as_text`/`fixed_as_text`.
+    pub bytes_verbose: bool,
+    /// How values of union types are represented.
+    pub union_mode: UnionMode,
+    /// How `duration` logical values are represented.
+    pub duration_format: DurationFormat,
+    /// How enum values are represented.
+    pub enum_mode: EnumMode,
+    /// Emit `long` values beyond ±(2^53 - 1) as strings, since JavaScript and other
+    /// consumers that parse numbers as doubles would silently round them.
+    pub large_longs_as_string: bool,
+    /// What to do with NaN and infinite `float`/`double` values, which JSON can't represent.
+    pub float_policy: FloatPolicy,
+    /// Round `float` and `double` values to this many decimal places, so binary noise such as
+    /// `0.30000000000000004` comes out as `0.3`. `None` keeps full precision.
+    pub round_floats: Option<u8>,
+    /// Fail with [`ConvertError::ReplacementCharacter`] on a string holding U+FFFD, which
+    /// decoders substitute for invalid UTF-8, instead of passing it through.
+    pub strict_utf8: bool,
+    /// Scales of top-level record fields to render as decimals, for files whose schema
+    /// stores them as plain `bytes` or `fixed` without the `decimal` logical type. A matching
+    /// field's bytes are read as a big-endian two's complement unscaled value, as the Avro
+    /// spec lays decimals out, and come out as a decimal string like `"123.45"`. Overrides
+    /// whatever the schema says; a nullable field's union is unwrapped.
+    pub decimal_fields: BTreeMap<String, usize>,
+    /// chrono format string (e.g. `%Y/%m/%d`) for `date` values, instead of ISO-8601. Check it
+    /// with [`check_date_format`] first, since a bad one fails rendering.
+    pub date_format: Option<String>,
+    /// chrono format string (e.g. `%+`) for `timestamp-*` values, formatted in UTC, instead of
+    /// RFC 3339. Check it with [`check_timestamp_format`] first.
+    pub timestamp_format: Option<String>,
+    /// Zone that `local-timestamp-*` values are known to be in. When set they are rendered
+    /// with that zone's offset instead of as naive wall-clock times.
+    pub local_timezone: Option<Tz>,
+    /// Naming convention applied to record field names.
+    pub key_style: KeyStyle,
+    /// Apply `key_style` to the keys of Avro maps too. Off by default, since map keys are
+    /// data rather than schema field names.
+    pub rename_map_keys: bool,
+    /// Wrap Avro maps as `{"$map": {...}}` so they can be told apart from records, which
+    /// otherwise also become plain JSON objects.
+    pub tag_maps: bool,
+    /// How deeply arrays, maps and records may nest before conversion fails with
+    /// [`ConvertError::MaxDepthExceeded`] instead of risking a stack overflow. Defaults to
+    /// [`DEFAULT_MAX_DEPTH`].
+    pub max_depth: Option<usize>,
+    /// Emit the canonical Avro JSON encoding instead of the friendlier default rendering.
+    /// Every other option except `float_policy`, `strict_utf8` and `max_depth` is ignored; see
+    /// [`avro_value_to_avro_json`].
+    pub avro_json: bool,
+}
+
+/// Nesting limit used when [`JsonOptions::max_depth`] isn't set. Far deeper than real data
+/// goes, yet shallow enough to stay clear of the stack limits of worker threads.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Descends one level into an array, map or record, failing once the nesting limit is passed.
+fn nested_depth(depth: usize, options: &JsonOptions) -> Result<usize> {
+    let max_depth = options.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+    if depth >= max_depth {
+        return Err(ConvertError::MaxDepthExceeded(max_depth));
+    }
+    Ok(depth + 1)
+}
+
+/// The largest integer a double can hold exactly (`Number.MAX_SAFE_INTEGER` in JavaScript).
+const MAX_SAFE_INTEGER: u64 = 9_007_199_254_740_991;
+
+/// Handling of NaN and infinite floats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatPolicy {
+    /// Emit `null`, indistinguishable from a real null.
+    #[default]
+    Null,
+    /// Emit the strings `"NaN"`, `"Infinity"` and `"-Infinity"`.
+    String,
+    /// Fail the conversion with [`ConvertError::NonFiniteFloat`].
+    Error,
+}
+
+/// Rounds to `places` decimal places. Going through the decimal text gives the double
+/// nearest the rounded decimal, which prints as exactly that decimal, where scaling by a power
+/// of ten would bring new binary noise.
+fn round_float(value: f64, places: Option<u8>) -> f64 {
+    match places {
+        Some(places) if value.is_finite() => format!("{:.*}", places as usize, value).parse().unwrap_or(value),
+        _ => value,
+    }
+}
+
+fn float_to_json(value: f64, policy: FloatPolicy) -> Result<Value> {
+    if let Some(number) = serde_json::Number::from_f64(value) {
+        return Ok(Value::Number(number));
+    }
+    match policy {
+        FloatPolicy::Null => Ok(Value::Null),
+        FloatPolicy::String if value.is_nan() => Ok(Value::from("NaN")),
+        FloatPolicy::String if value > 0.0 => Ok(Value::from("Infinity")),
+        FloatPolicy::String => Ok(Value::from("-Infinity")),
+        FloatPolicy::Error => Err(ConvertError::NonFiniteFloat(value)),
+    }
+}
+
+/// Representation of Avro enum values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnumMode {
+    /// Emit the bare symbol string.
+    #[default]
+    Symbol,
+    /// Emit `{"enum": "<full enum name>", "symbol": "<symbol>"}`, so symbols shared by
+    /// several enums stay distinguishable. Needs the writer schema to know the enum's name;
+    /// without it the bare symbol is emitted.
+    Named,
+    /// Emit the symbol's zero-based position in the enum's symbol list, for compact numeric
+    /// exports.
+    Index,
+}
+
+/// Representation of Avro `duration` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DurationFormat {
+    /// `{"months": m, "days": d, "millis": ms}`, keeping the three components separate since
+    /// months and days have no fixed length.
+    #[default]
+    Object,
+    /// An ISO-8601 duration string such as `P1M2DT3.5S`.
+    Iso8601,
+}
+
+/// Representation of values whose schema is a union.
+///
+/// Conversion recurses through unions like any other value, so in `Tagged` mode every union
+/// is wrapped where it occurs, including unions nested inside arrays, maps and records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnionMode {
+    /// Emit the value of the resolved branch directly.
+    #[default]
+    Unwrap,
+    /// Wrap non-null values as `{"<branch type name>": value}`, following the Avro JSON
+    /// encoding. Named types use their full name. Null branches still render as `null`.
+    /// This keeps e.g. the `int` 5 and the `string` "5" of `["int", "string"]` apart.
+    Tagged,
+}
+
+pub fn avro_value_to_json(avro_value: &apache_avro::types::Value) -> Result<Value> {
+    avro_value_to_json_with_options(avro_value, &JsonOptions::default())
+}
+
+pub fn avro_value_to_json_with_options(
+    avro_value: &apache_avro::types::Value,
+    options: &JsonOptions,
+) -> Result<Value> {
+    value_to_json(avro_value, None, &Names::new(), options)
+}
+
+/// A converted Avro value that serializes as its JSON rendering, for embedding in larger
+/// serde structures: `json!({"payload": AvroJson::try_from(&value)?})`. Converts with the
+/// default [`JsonOptions`] and no schema, like [`avro_value_to_json`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(transparent)]
+pub struct AvroJson(pub Value);
+
+impl TryFrom<&apache_avro::types::Value> for AvroJson {
+    type Error = ConvertError;
+
+    fn try_from(avro_value: &apache_avro::types::Value) -> Result<Self> {
+        avro_value_to_json(avro_value).map(AvroJson)
+    }
+}
+
+impl From<AvroJson> for Value {
+    fn from(json: AvroJson) -> Self {
+        json.0
+    }
+}
+
+/// Converts a value using its schema, which supplies what the value alone can't: decimal
+/// scale, union branch and enum names, and logical types. A plain `int`, `long`, `bytes`
+/// or `fixed` value whose schema carries a logical type is rendered as that logical type,
+/// so e.g. an `Int` under a `date` schema still comes out as a date string.
+pub fn avro_value_to_json_with_schema(avro_value: &apache_avro::types::Value, schema: &Schema) -> Result<Value> {
+    avro_value_to_json_with_schema_and_options(avro_value, schema, &JsonOptions::default())
+}
+
+pub fn avro_value_to_json_with_schema_and_options(
+    avro_value: &apache_avro::types::Value,
+    schema: &Schema,
+    options: &JsonOptions,
+) -> Result<Value> {
+    SchemaContext::new(schema)?.value_to_json(avro_value, options)
+}
+
+/// Converts with the recursive converter that the default, iterative one replaced, taking
+/// the same options. Only there for tests and benchmarks to compare the two, and only built
+/// with the `bench-internals` feature.
+#[cfg(any(test, feature = "bench-internals"))]
+#[doc(hidden)]
+pub fn avro_value_to_json_recursive(
+    avro_value: &apache_avro::types::Value,
+    schema: Option<&Schema>,
+    options: &JsonOptions,
+) -> Result<Value> {
+    let names = match schema {
+        Some(schema) => SchemaContext::new(schema)?.names,
+        None => Names::new(),
+    };
+    nested_value_to_json(avro_value, schema, &names, options, 0)
+}
+
+/// Decodes a single binary-encoded Avro datum, without any container framing, and converts
+/// it to JSON. Suits message payloads, such as Kafka values, that carry bare datums.
+pub fn convert_datum(schema: &Schema, bytes: &[u8]) -> Result<Value> {
+    convert_datum_with_options(schema, bytes, &JsonOptions::default())
+}
+
+pub fn convert_datum_with_options(schema: &Schema, mut bytes: &[u8], options: &JsonOptions) -> Result<Value> {
+    let avro_value = apache_avro::from_avro_datum(schema, &mut bytes, None)?;
+    avro_value_to_json_with_schema_and_options(&avro_value, schema, options)
+}
+
+/// Reinterprets a raw primitive as the logical type its schema declares. Returns `None` when
+/// the value already is a logical value or the schema has no logical type.
+fn as_logical_value(
+    avro_value: &apache_avro::types::Value,
+    schema: &Schema,
+) -> Option<apache_avro::types::Value> {
+    use apache_avro::types::Value as AvroValue;
+
+    let logical_value = match (avro_value, schema) {
+        (AvroValue::Int(days), Schema::Date) => AvroValue::Date(*days),
+        (AvroValue::Int(millis), Schema::TimeMillis) => AvroValue::TimeMillis(*millis),
+        (AvroValue::Long(micros), Schema::TimeMicros) => AvroValue::TimeMicros(*micros),
+        (AvroValue::Long(millis), Schema::TimestampMillis) => AvroValue::TimestampMillis(*millis),
+        (AvroValue::Long(micros), Schema::TimestampMicros) => AvroValue::TimestampMicros(*micros),
+        (AvroValue::Long(millis), Schema::LocalTimestampMillis) => AvroValue::LocalTimestampMillis(*millis),
+        (AvroValue::Long(micros), Schema::LocalTimestampMicros) => AvroValue::LocalTimestampMicros(*micros),
+        (AvroValue::Bytes(bytes) | AvroValue::Fixed(_, bytes), Schema::Decimal(_)) => {
+            AvroValue::Decimal(apache_avro::Decimal::from(bytes))
+        }
+        (AvroValue::Fixed(16, bytes), Schema::Fixed(fixed)) if fixed.attributes.contains_key(FIXED_UUID_ATTRIBUTE) => {
+            AvroValue::Uuid(uuid::Uuid::from_slice(bytes).ok()?)
+        }
+        (AvroValue::Fixed(12, bytes), Schema::Duration) => {
+            AvroValue::Duration(<[u8; 12]>::try_from(bytes.as_slice()).ok()?.into())
+        }
+        _ => return None,
+    };
+    Some(logical_value)
+}
+
+/// Follows a `Schema::Ref` to the named schema it points at.
+fn resolve_schema<'s>(schema: Option<&'s Schema>, names: &'s Names) -> Option<&'s Schema> {
+    match schema {
+        Some(Schema::Ref { name }) => names.get(name),
+        other => other,
+    }
+}
+
+/// Converts a value while walking its schema alongside it. The schema is optional: when it
+/// is missing (or doesn't line up with the value) conversion falls back to what the value
+/// alone can tell us.
+fn value_to_json<'s>(
+    avro_value: &'s apache_avro::types::Value,
+    schema: Option<&'s Schema>,
+    names: &'s Names,
+    options: &JsonOptions,
+) -> Result<Value> {
+    let json_value = if options.avro_json {
+        value_to_avro_json(avro_value, schema, names, options, 0)
+    } else {
+        iterative_value_to_json(avro_value, schema, names, options)
+    };
+    json_value.map_err(|err| match err {
+        ConvertError::ReplacementCharacter { position, .. } => ConvertError::ReplacementCharacter {
+            field: replacement_character_path(avro_value).unwrap_or_default(),
+            position,
+        },
+        err => err,
+    })
+}
+
+/// A container that [`iterative_value_to_json`] is partway through, holding what has been
+/// converted of it so far.
+enum Frame<'a> {
+    Array {
+        items: std::slice::Iter<'a, apache_avro::types::Value>,
+        schema: Option<&'a Schema>,
+        depth: usize,
+        json: Vec<Value>,
+    },
+    Map {
+        entries: std::vec::IntoIter<(&'a String, &'a apache_avro::types::Value)>,
+        schema: Option<&'a Schema>,
+        depth: usize,
+        key: String,
+        json: serde_json::Map<String, Value>,
+    },
+    Record {
+        fields: std::slice::Iter<'a, (String, apache_avro::types::Value)>,
+        schema: Option<&'a Schema>,
+        depth: usize,
+        key: String,
+        json: serde_json::Map<String, Value>,
+    },
+    /// A union branch to wrap as `{"<branch>": value}` under [`UnionMode::Tagged`].
+    TaggedUnion { tag: String, json: Value },
+}
+
+impl<'a> Frame<'a> {
+    /// Takes the converted value of the child last returned by [`Frame::next_child`].
+    fn accept(&mut self, value: Value) {
+        match self {
+            Frame::Array { json, .. } => json.push(value),
+            Frame::Map { key, json, .. } | Frame::Record { key, json, .. } => {
+                json.insert(std::mem::take(key), value);
+            }
+            Frame::TaggedUnion { json, .. } => *json = value,
+        }
+    }
+
+    /// The next child to convert, with its schema and depth, or `None` once all are done.
+    fn next_child(
+        &mut self,
+        options: &JsonOptions,
+    ) -> Option<(&'a apache_avro::types::Value, Option<&'a Schema>, usize)> {
+        match self {
+            Frame::Array { items, schema, depth, .. } => items.next().map(|item| (item, *schema, *depth)),
+            Frame::Map { entries, schema, depth, key, .. } => {
+                let (map_key, value) = entries.next()?;
+                *key = if options.rename_map_keys {
+                    rename_key(map_key, options.key_style)
+                } else {
+                    map_key.clone()
+                };
+                Some((value, *schema, *depth))
+            }
+            Frame::Record { fields, schema, depth, key, json } => {
+                for (name, value) in fields.by_ref() {
+                    if let Some(decimal) = decimal_field_override(name, value, *depth, options) {
+                        json.insert(rename_key(name, options.key_style), decimal);
+                        continue;
+                    }
+                    let field_schema = match schema {
+                        Some(Schema::Record(record)) => {
+                            record.lookup.get(name).map(|&position| &record.fields[position].schema)
+                        }
+                        _ => None,
+                    };
+                    *key = rename_key(name, options.key_style);
+                    return Some((value, field_schema, *depth));
+                }
+                None
+            }
+            Frame::TaggedUnion { .. } => None,
+        }
+    }
+
+    /// The finished container.
+    fn finish(self, options: &JsonOptions) -> Value {
+        match self {
+            Frame::Array { json, .. } => Value::Array(json),
+            Frame::Map { json, .. } if options.tag_maps => {
+                let mut tagged = serde_json::Map::new();
+                tagged.insert("$map".to_string(), Value::Object(json));
+                Value::Object(tagged)
+            }
+            Frame::Map { json, .. } | Frame::Record { json, .. } => Value::Object(json),
+            Frame::TaggedUnion { tag, json } => {
+                let mut tagged = serde_json::Map::new();
+                tagged.insert(tag, json);
+                Value::Object(tagged)
+            }
+        }
+    }
+}
+
+/// [`value_to_json`] with an explicit stack of the containers being converted instead of
+/// recursion, so nesting costs a heap-allocated [`Frame`] per level rather than a call frame.
+/// Produces exactly what [`nested_value_to_json`] does, failing with the same first error.
+fn iterative_value_to_json<'a>(
+    avro_value: &'a apache_avro::types::Value,
+    schema: Option<&'a Schema>,
+    names: &'a Names,
+    options: &JsonOptions,
+) -> Result<Value> {
+    let mut stack: Vec<Frame<'a>> = Vec::new();
+    let mut child = Some((avro_value, schema, 0));
+    loop {
+        let mut json_value = match child.take() {
+            Some((avro_value, schema, depth)) => descend(avro_value, schema, depth, &mut stack, names, options)?,
+            None => None,
+        };
+        // Hand finished values up to their containers until one has another child to convert
+        loop {
+            let Some(frame) = stack.last_mut() else {
+                // The stack only empties once the outermost value is finished
+                return Ok(json_value.unwrap_or_default());
+            };
+            if let Some(json_value) = json_value.take() {
+                frame.accept(json_value);
+            }
+            match frame.next_child(options) {
+                Some(next) => {
+                    child = Some(next);
+                    break;
+                }
+                None => json_value = stack.pop().map(|frame| frame.finish(options)),
+            }
+        }
+    }
+}
+
+/// Starts converting `avro_value`: a scalar is converted right away, while a container is
+/// pushed onto `stack` and `None` returned, for its children to be converted next. Untagged
+/// unions are looked through.
+fn descend<'a>(
+    mut avro_value: &'a apache_avro::types::Value,
+    mut schema: Option<&'a Schema>,
+    depth: usize,
+    stack: &mut Vec<Frame<'a>>,
+    names: &'a Names,
+    options: &JsonOptions,
+) -> Result<Option<Value>> {
+    use apache_avro::types::Value as AvroValue;
+
+    loop {
+        let resolved = resolve_schema(schema, names);
+        let frame = match avro_value {
+            AvroValue::Union(index, boxed_value) => {
+                let branch = match resolved {
+                    Some(Schema::Union(union)) => union.variants().get(*index as usize),
+                    _ => None,
+                };
+                if options.union_mode == UnionMode::Tagged && **boxed_value != AvroValue::Null {
+                    stack.push(Frame::TaggedUnion {
+                        tag: union_branch_name(branch, boxed_value),
+                        json: Value::Null,
+                    });
+                }
+                avro_value = boxed_value;
+                schema = branch;
+                continue;
+            }
+            AvroValue::Array(items) => Frame::Array {
+                items: items.iter(),
+                schema: match resolved {
+                    Some(Schema::Array(items)) => Some(items.as_ref()),
+                    _ => None,
+                },
+                depth: nested_depth(depth, options)?,
+                json: Vec::with_capacity(items.len()),
+            },
+            AvroValue::Map(map) => Frame::Map {
+                entries: sorted_entries(map).into_iter(),
+                schema: match resolved {
+                    Some(Schema::Map(values)) => Some(values.as_ref()),
+                    _ => None,
+                },
+                depth: nested_depth(depth, options)?,
+                key: String::new(),
+                json: serde_json::Map::new(),
+            },
+            AvroValue::Record(fields) => Frame::Record {
+                fields: fields.iter(),
+                schema: resolved,
+                depth: nested_depth(depth, options)?,
+                key: String::new(),
+                json: serde_json::Map::new(),
+            },
+            _ => return scalar_to_json(avro_value, resolved, options).map(Some),
+        };
+        stack.push(frame);
+        return Ok(None);
+    }
+}
+
+/// The recursive converter that [`iterative_value_to_json`] replaced, for a value nested
+/// `depth` arrays, maps and records deep. Kept as the reference the iterative one is tested
+/// and benchmarked against; see [`avro_value_to_json_recursive`]. Only values that contain
+/// other values are handled here; everything else goes to [`scalar_to_json`], which keeps
+/// the stack frames of this recursion small.
+#[cfg(any(test, feature = "bench-internals"))]
+fn nested_value_to_json<'s>(
+    avro_value: &apache_avro::types::Value,
+    schema: Option<&'s Schema>,
+    names: &'s Names,
+    options: &JsonOptions,
+    depth: usize,
+) -> Result<Value> {
+    use apache_avro::types::Value as AvroValue;
+
+    let schema = resolve_schema(schema, names);
+    let json_value = match avro_value {
+        AvroValue::Union(index, boxed_value) => {
+            let branch = match schema {
+                Some(Schema::Union(union)) => union.variants().get(*index as usize),
+                _ => None,
+            };
+            let json_value = nested_value_to_json(boxed_value, branch, names, options, depth)?;
+            match options.union_mode {
+                UnionMode::Tagged if **boxed_value != AvroValue::Null => {
+                    let mut tagged = serde_json::Map::new();
+                    tagged.insert(union_branch_name(branch, boxed_value), json_value);
+                    Value::Object(tagged)
+                }
+                _ => json_value,
+            }
+        }
+        AvroValue::Array(arr) => {
+            let depth = nested_depth(depth, options)?;
+            let items = match schema {
+                Some(Schema::Array(items)) => Some(items.as_ref()),
+                _ => None,
+            };
+            let mut json_arr = Vec::new();
+            for item in arr {
+                json_arr.push(nested_value_to_json(item, items, names, options, depth)?);
+            }
+            Value::Array(json_arr)
+        }
+        AvroValue::Map(map) => {
+            let depth = nested_depth(depth, options)?;
+            let values = match schema {
+                Some(Schema::Map(values)) => Some(values.as_ref()),
+                _ => None,
+            };
+            let mut json_obj = serde_json::Map::new();
+            for (key, value) in sorted_entries(map) {
+                let key = if options.rename_map_keys {
+                    rename_key(key, options.key_style)
+                } else {
+                    key.clone()
+                };
+                json_obj.insert(key, nested_value_to_json(value, values, names, options, depth)?);
+            }
+            if options.tag_maps {
+                let mut tagged = serde_json::Map::new();
+                tagged.insert("$map".to_string(), Value::Object(json_obj));
+                Value::Object(tagged)
+            } else {
+                Value::Object(json_obj)
+            }
+        }
+        AvroValue::Record(fields) => {
+            let depth = nested_depth(depth, options)?;
+            let mut json_obj = serde_json::Map::new();
+            for (name, value) in fields {
+                let field_schema = match schema {
+                    Some(Schema::Record(record)) => record
+                        .lookup
+                        .get(name)
+                        .map(|&position| &record.fields[position].schema),
+                    _ => None,
+                };
+                let json_value = match decimal_field_override(name, value, depth, options) {
+                    Some(decimal) => decimal,
+                    None => nested_value_to_json(value, field_schema, names, options, depth)?,
+                };
+                json_obj.insert(rename_key(name, options.key_style), json_value);
+            }
+            Value::Object(json_obj)
+        }
+        _ => scalar_to_json(avro_value, schema, options)?,
+    };
+
+    Ok(json_value)
+}
+
+/// Renders a top-level field named in [`JsonOptions::decimal_fields`] as a decimal string.
+/// `depth` is that of the record's fields, so 1 for the top level. Returns `None` for other
+/// fields and for values that aren't bytes, leaving those to the usual conversion.
+fn decimal_field_override(
+    name: &str,
+    avro_value: &apache_avro::types::Value,
+    depth: usize,
+    options: &JsonOptions,
+) -> Option<Value> {
+    use apache_avro::types::Value as AvroValue;
+
+    if depth != 1 {
+        return None;
+    }
+    let scale = *options.decimal_fields.get(name)?;
+    match avro_value {
+        AvroValue::Bytes(bytes) | AvroValue::Fixed(_, bytes) => {
+            Some(Value::String(format_decimal(&BigInt::from_signed_bytes_be(bytes), scale)))
+        }
+        AvroValue::Union(_, boxed_value) => decimal_field_override(name, boxed_value, depth, options),
+        _ => None,
+    }
+}
+
+/// Converts a value that holds no other values.
+fn scalar_to_json(
+    avro_value: &apache_avro::types::Value,
+    schema: Option<&Schema>,
+    options: &JsonOptions,
+) -> Result<Value> {
+    use apache_avro::types::Value as AvroValue;
+
+    // The schema has the final say on logical types, whatever the value was built as
+    if let Some(schema) = schema {
+        if let Some(logical_value) = as_logical_value(avro_value, schema) {
+            return scalar_to_json(&logical_value, Some(schema), options);
+        }
+    }
+
+    let json_value = match avro_value {
+        AvroValue::Null => Value::Null,
+        AvroValue::Boolean(b) => Value::Bool(*b),
+        AvroValue::Int(i) => Value::Number((*i).into()),
+        AvroValue::Long(l) if options.large_longs_as_string && l.unsigned_abs() > MAX_SAFE_INTEGER => {
+            Value::String(l.to_string())
+        }
+        AvroValue::Long(l) => Value::Number((*l).into()),
+        AvroValue::Float(f) => float_to_json(round_float(*f as f64, options.round_floats), options.float_policy)?,
+        AvroValue::Double(d) => float_to_json(round_float(*d, options.round_floats), options.float_policy)?,
+        AvroValue::Bytes(bytes) => bytes_to_json(bytes, options.bytes_as_text, options),
+        AvroValue::String(s) => {
+            check_utf8(s, options)?;
+            Value::String(s.clone())
+        }
+        AvroValue::Fixed(_, bytes) => bytes_to_json(bytes, options.fixed_as_text, options),
+        AvroValue::Enum(index, _) if options.enum_mode == EnumMode::Index => Value::from(*index),
+        AvroValue::Enum(_, symbol) => match schema {
+            Some(Schema::Enum(enum_schema)) if options.enum_mode == EnumMode::Named => serde_json::json!({
+                "enum": enum_schema.name.fullname(None),
+                "symbol": symbol,
+            }),
+            _ => Value::String(symbol.clone()),
+        },
+        AvroValue::Date(days) if options.legacy_logical_types => {
+            Value::String(format!("days-since-epoch:{}", days))
+        }
+        AvroValue::Date(days) => match &options.date_format {
+            Some(format) => Value::String(format_date_days(*days, format)?),
+            None => Value::String(date_days_to_iso(*days)),
+        },
+        AvroValue::TimeMillis(millis) => {
+            Value::String(format!("time-millis:{}", millis))
+        }
+        AvroValue::TimeMicros(micros) => {
+            Value::String(format!("time-micros:{}", micros))
+        }
+        AvroValue::TimestampMillis(millis) if options.legacy_logical_types => {
+            Value::String(format!("timestamp-millis:{}", millis))
+        }
+        AvroValue::TimestampMicros(micros) if options.legacy_logical_types => {
+            Value::String(format!("timestamp-micros:{}", micros))
+        }
+        AvroValue::TimestampMillis(millis) => match &options.timestamp_format {
+            Some(format) => Value::String(format_timestamp(*millis, false, format)?),
+            None => Value::String(timestamp_to_rfc3339(*millis, false)),
+        },
+        AvroValue::TimestampMicros(micros) => match &options.timestamp_format {
+            Some(format) => Value::String(format_timestamp(*micros, true, format)?),
+            None => Value::String(timestamp_to_rfc3339(*micros, true)),
+        },
+        AvroValue::Decimal(decimal) if options.legacy_logical_types => {
+            Value::String(format!("decimal:{:?}", decimal))
+        }
+        AvroValue::Decimal(decimal) => {
+            // The scale lives only in the schema; without it the unscaled value is emitted.
+            // Rendered as a string so no precision is lost to floating point.
+            let scale = match schema {
+                Some(Schema::Decimal(decimal_schema)) => decimal_schema.scale,
+                _ => 0,
+            };
+            let bytes = Vec::<u8>::try_from(decimal)?;
+            Value::String(format_decimal(&BigInt::from_signed_bytes_be(&bytes), scale))
+        }
+        AvroValue::Uuid(uuid) => Value::String(uuid.to_string()),
+        AvroValue::Duration(duration) if options.legacy_logical_types => {
+            Value::String(format!("duration:{:?}:{:?}:{:?}", duration.months(), duration.days(), duration.millis()))
+        }
+        AvroValue::Duration(duration) => {
+            let months: u32 = duration.months().into();
+            let days: u32 = duration.days().into();
+            let millis: u32 = duration.millis().into();
+            match options.duration_format {
+                DurationFormat::Object => serde_json::json!({
+                    "months": months,
+                    "days": days,
+                    "millis": millis,
+                }),
+                DurationFormat::Iso8601 => Value::String(duration_to_iso8601(months, days, millis)),
+            }
+        }
+        AvroValue::LocalTimestampMillis(millis) if options.legacy_logical_types => {
+            Value::String(format!("local-timestamp-millis:{}", millis))
+        }
+        AvroValue::LocalTimestampMicros(micros) if options.legacy_logical_types => {
+            Value::String(format!("local-timestamp-micros:{}", micros))
+        }
+        AvroValue::LocalTimestampMillis(millis) => match options.local_timezone {
+            Some(timezone) => Value::String(local_timestamp_in_timezone(*millis, false, timezone)),
+            None => Value::String(local_timestamp_to_iso8601(*millis, false)),
+        },
+        AvroValue::LocalTimestampMicros(micros) => match options.local_timezone {
+            Some(timezone) => Value::String(local_timestamp_in_timezone(*micros, true, timezone)),
+            None => Value::String(local_timestamp_to_iso8601(*micros, true)),
+        },
+        AvroValue::Union(..) | AvroValue::Array(_) | AvroValue::Map(_) | AvroValue::Record(_) => {
+            unreachable!("nested values are converted by iterative_value_to_json")
+        }
+    };
+
+    Ok(json_value)
+}
+
+/// The entries of a decoded map, sorted by key. The decoder collects maps into a `HashMap`,
+/// which forgets the order they were written in and iterates in a different order every run,
+/// so sorting is what keeps the output reproducible.
+fn sorted_entries(
+    map: &HashMap<String, apache_avro::types::Value>,
+) -> Vec<(&String, &apache_avro::types::Value)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+/// Converts a value to the canonical JSON encoding from the Avro specification, which tools
+/// such as `avro-tools tojson` read and write:
+///
+/// - non-null union values are wrapped as `{"<branch type name>": value}`
+/// - `bytes` and `fixed` become strings with one code point (U+0000 to U+00FF) per byte
+/// - enums are their symbol
+/// - logical types are written as the type they annotate: dates and times as numbers,
+///   decimals and durations as bytes, UUIDs as strings
+///
+/// Unlike the default rendering this loses nothing, so the value can be turned back into the
+/// same Avro datum given its schema.
+pub fn avro_value_to_avro_json(avro_value: &apache_avro::types::Value, schema: &Schema) -> Result<Value> {
+    let options = JsonOptions {
+        avro_json: true,
+        ..Default::default()
+    };
+    avro_value_to_json_with_schema_and_options(avro_value, schema, &options)
+}
+
+/// Only `float_policy` and `max_depth` are taken from `options`.
+fn value_to_avro_json<'s>(
+    avro_value: &apache_avro::types::Value,
+    schema: Option<&'s Schema>,
+    names: &'s Names,
+    options: &JsonOptions,
+    depth: usize,
+) -> Result<Value> {
+    use apache_avro::types::Value as AvroValue;
+
+    let schema = resolve_schema(schema, names);
+    let json_value = match avro_value {
+        AvroValue::Null => Value::Null,
+        AvroValue::Boolean(b) => Value::Bool(*b),
+        AvroValue::Int(i) | AvroValue::Date(i) | AvroValue::TimeMillis(i) => Value::Number((*i).into()),
+        AvroValue::Long(l)
+        | AvroValue::TimeMicros(l)
+        | AvroValue::TimestampMillis(l)
+        | AvroValue::TimestampMicros(l)
+        | AvroValue::LocalTimestampMillis(l)
+        | AvroValue::LocalTimestampMicros(l) => Value::Number((*l).into()),
+        AvroValue::Float(f) => float_to_json(*f as f64, options.float_policy)?,
+        AvroValue::Double(d) => float_to_json(*d, options.float_policy)?,
+        AvroValue::Bytes(bytes) | AvroValue::Fixed(_, bytes) => Value::String(latin1_encode(bytes)),
+        AvroValue::String(s) => {
+            check_utf8(s, options)?;
+            Value::String(s.clone())
+        }
+        AvroValue::Enum(_, s) => Value::String(s.clone()),
+        AvroValue::Uuid(uuid) => Value::String(uuid.to_string()),
+        AvroValue::Decimal(decimal) => Value::String(latin1_encode(&Vec::<u8>::try_from(decimal)?)),
+        AvroValue::Duration(duration) => Value::String(latin1_encode(&<[u8; 12]>::from(*duration))),
+        AvroValue::Union(_, boxed_value) if **boxed_value == AvroValue::Null => Value::Null,
+        AvroValue::Union(index, boxed_value) => {
+            let branch = match schema {
+                Some(Schema::Union(union)) => union.variants().get(*index as usize),
+                _ => None,
+            };
+            let mut tagged = serde_json::Map::new();
+            let json_value = value_to_avro_json(boxed_value, branch, names, options, depth)?;
+            tagged.insert(union_branch_name(branch, boxed_value), json_value);
+            Value::Object(tagged)
+        }
+        AvroValue::Array(arr) => {
+            let depth = nested_depth(depth, options)?;
+            let items = match schema {
+                Some(Schema::Array(items)) => Some(items.as_ref()),
+                _ => None,
+            };
+            let mut json_arr = Vec::new();
+            for item in arr {
+                json_arr.push(value_to_avro_json(item, items, names, options, depth)?);
+            }
+            Value::Array(json_arr)
+        }
+        AvroValue::Map(map) => {
+            let depth = nested_depth(depth, options)?;
+            let values = match schema {
+                Some(Schema::Map(values)) => Some(values.as_ref()),
+                _ => None,
+            };
+            let mut json_obj = serde_json::Map::new();
+            for (key, value) in sorted_entries(map) {
+                json_obj.insert(key.clone(), value_to_avro_json(value, values, names, options, depth)?);
+            }
+            Value::Object(json_obj)
+        }
+        AvroValue::Record(fields) => {
+            let depth = nested_depth(depth, options)?;
+            let mut json_obj = serde_json::Map::new();
+            for (name, value) in fields {
+                let field_schema = match schema {
+                    Some(Schema::Record(record)) => record
+                        .lookup
+                        .get(name)
+                        .map(|&position| &record.fields[position].schema),
+                    _ => None,
+                };
+                json_obj.insert(name.clone(), value_to_avro_json(value, field_schema, names, options, depth)?);
+            }
+            Value::Object(json_obj)
+        }
+    };
+
+    Ok(json_value)
+}
+
+/// Fails on a string holding the U+FFFD replacement character under
+/// [`JsonOptions::strict_utf8`].
+fn check_utf8(s: &str, options: &JsonOptions) -> Result<()> {
+    match s.find(char::REPLACEMENT_CHARACTER) {
+        Some(position) if options.strict_utf8 => Err(ConvertError::ReplacementCharacter {
+            field: String::new(),
+            position,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// The path (e.g. `/tags/0`) to the first string in `value` that holds U+FFFD, in the order
+/// conversion visits them. Only worked out once conversion has failed on one, so the
+/// recursion that converts values doesn't have to track where it is.
+fn replacement_character_path(value: &apache_avro::types::Value) -> Option<String> {
+    use apache_avro::types::Value as AvroValue;
+
+    let within = |segment: &str, value| replacement_character_path(value).map(|path| format!("/{}{}", segment, path));
+    match value {
+        AvroValue::String(s) => s.contains(char::REPLACEMENT_CHARACTER).then(String::new),
+        AvroValue::Union(_, value) => replacement_character_path(value),
+        AvroValue::Array(items) => items.iter().enumerate().find_map(|(index, item)| within(&index.to_string(), item)),
+        AvroValue::Map(map) => sorted_entries(map).into_iter().find_map(|(key, value)| within(key, value)),
+        AvroValue::Record(fields) => fields.iter().find_map(|(name, value)| within(name, value)),
+        _ => None,
+    }
+}
+
+/// Maps each byte to the code point of the same value, as the Avro JSON encoding does for
+/// `bytes` and `fixed`.
+fn latin1_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| char::from(byte)).collect()
+}
+
+/// Names a union branch the way the Avro JSON encoding does: named types by their full
+/// name, everything else by its underlying Avro type. When the branch schema is unknown the
+/// name is derived from the value itself.
+fn union_branch_name(branch: Option<&Schema>, value: &apache_avro::types::Value) -> String {
+    match branch {
+        Some(Schema::Decimal(decimal)) => union_branch_name(Some(&decimal.inner), value),
+        Some(schema) => match schema.name() {
+            Some(name) => name.fullname(None),
+            None => avro_type_name(SchemaKind::from(schema)).to_string(),
+        },
+        None => avro_type_name(SchemaKind::from(value)).to_string(),
+    }
+}
+
+/// The Avro type name for a schema kind; logical types map to the type they annotate.
+fn avro_type_name(kind: SchemaKind) -> &'static str {
+    match kind {
+        SchemaKind::Null => "null",
+        SchemaKind::Boolean => "boolean",
+        SchemaKind::Int | SchemaKind::Date | SchemaKind::TimeMillis => "int",
+        SchemaKind::Long
+        | SchemaKind::TimeMicros
+        | SchemaKind::TimestampMillis
+        | SchemaKind::TimestampMicros
+        | SchemaKind::LocalTimestampMillis
+        | SchemaKind::LocalTimestampMicros => "long",
+        SchemaKind::Float => "float",
+        SchemaKind::Double => "double",
+        SchemaKind::Bytes | SchemaKind::Decimal => "bytes",
+        SchemaKind::String | SchemaKind::Uuid => "string",
+        SchemaKind::Array => "array",
+        SchemaKind::Map => "map",
+        SchemaKind::Union => "union",
+        SchemaKind::Record | SchemaKind::Ref => "record",
+        SchemaKind::Enum => "enum",
+        SchemaKind::Fixed | SchemaKind::Duration => "fixed",
+    }
+}
+
+/// Renders an unscaled big integer as a base-10 decimal literal with `scale` fractional
+/// digits, e.g. `12345` with scale 2 becomes `123.45`.
+pub fn format_decimal(unscaled: &BigInt, scale: usize) -> String {
+    let digits = unscaled.magnitude().to_string();
+    let sign = if unscaled.sign() == num_bigint::Sign::Minus { "-" } else { "" };
+    if scale == 0 {
+        return format!("{}{}", sign, digits);
+    }
+
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    format!("{}{}.{}", sign, integer, fraction)
+}
+
+/// Number of days from 0001-01-01 (CE day 1) to the Unix epoch, 1970-01-01.
+const UNIX_EPOCH_DAYS_FROM_CE: i32 = 719_163;
+
+/// Formats a count of days since the Unix epoch as an ISO `YYYY-MM-DD` date. Values
+/// outside chrono's representable range fall back to the raw number.
+pub fn date_days_to_iso(days: i32) -> String {
+    days.checked_add(UNIX_EPOCH_DAYS_FROM_CE)
+        .and_then(NaiveDate::from_num_days_from_ce_opt)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| days.to_string())
+}
+
+/// Formats a count of days since the Unix epoch with a chrono format string. Values outside
+/// chrono's representable range fall back to the raw number.
+pub fn format_date_days(days: i32, format: &str) -> Result<String> {
+    let date = days.checked_add(UNIX_EPOCH_DAYS_FROM_CE).and_then(NaiveDate::from_num_days_from_ce_opt);
+    match date {
+        Some(date) => format_with(date.format(format), format),
+        None => Ok(days.to_string()),
+    }
+}
+
+/// Formats a millisecond (or, when `micros` is set, microsecond) Unix timestamp in UTC with a
+/// chrono format string. Values outside chrono's range fall back to the raw number.
+pub fn format_timestamp(timestamp: i64, micros: bool, format: &str) -> Result<String> {
+    match timestamp_to_datetime(timestamp, micros) {
+        Some(dt) => format_with(dt.format(format), format),
+        None => Ok(timestamp.to_string()),
+    }
+}
+
+/// Renders a chrono `format` result, which fails instead of panicking when the format string
+/// is invalid or asks for fields the value doesn't have.
+fn format_with(formatted: impl fmt::Display, format: &str) -> Result<String> {
+    use std::fmt::Write as _;
+
+    let mut rendered = String::new();
+    write!(rendered, "{}", formatted).map_err(|_| ConvertError::InvalidFormat(format.to_string()))?;
+    Ok(rendered)
+}
+
+/// Checks that a chrono format string can render `date` values, e.g. that it doesn't ask for
+/// the hour. For [`JsonOptions::date_format`].
+pub fn check_date_format(format: &str) -> Result<()> {
+    format_date_days(0, format).map(drop)
+}
+
+/// Checks that a chrono format string is valid, for [`JsonOptions::timestamp_format`].
+pub fn check_timestamp_format(format: &str) -> Result<()> {
+    format_timestamp(0, false, format).map(drop)
+}
+
+fn timestamp_to_datetime(timestamp: i64, micros: bool) -> Option<DateTime<Utc>> {
+    if micros {
+        DateTime::from_timestamp_micros(timestamp)
+    } else {
+        DateTime::from_timestamp_millis(timestamp)
+    }
+}
+
+/// Formats a millisecond (or, when `micros` is set, microsecond) Unix timestamp as an
+/// RFC-3339 UTC string such as `2023-11-14T22:13:20Z`. Sub-second digits are only emitted
+/// when non-zero, so microsecond precision is preserved. Values outside chrono's range
+/// fall back to the raw number.
+pub fn timestamp_to_rfc3339(timestamp: i64, micros: bool) -> String {
+    match timestamp_to_datetime(timestamp, micros) {
+        Some(dt) => dt.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Like [`timestamp_to_rfc3339`], but renders the naive wall-clock time without an offset,
+/// as required for Avro's `local-timestamp-*` logical types.
+pub fn local_timestamp_to_iso8601(timestamp: i64, micros: bool) -> String {
+    match timestamp_to_datetime(timestamp, micros) {
+        Some(dt) => dt.naive_utc().format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Renders an Avro duration as an ISO-8601 duration, e.g. `P1M2DT3.5S`. Milliseconds are
+/// written as (fractional) seconds; a zero duration is `PT0S`.
+pub fn duration_to_iso8601(months: u32, days: u32, millis: u32) -> String {
+    let mut result = String::from("P");
+    if months > 0 {
+        result.push_str(&format!("{}M", months));
+    }
+    if days > 0 {
+        result.push_str(&format!("{}D", days));
+    }
+    if millis > 0 || (months == 0 && days == 0) {
+        let (secs, frac) = (millis / 1000, millis % 1000);
+        if frac == 0 {
+            result.push_str(&format!("T{}S", secs));
+        } else {
+            let frac = format!("{:03}", frac);
+            result.push_str(&format!("T{}.{}S", secs, frac.trim_end_matches('0')));
+        }
+    }
+    result
+}
+
+/// Interprets a `local-timestamp-*` value as wall-clock time in `timezone` and renders it
+/// as RFC 3339 with that zone's offset. A time repeated by a DST change resolves to its
+/// first occurrence; a time skipped by one has no offset and falls back to the naive form.
+pub fn local_timestamp_in_timezone(timestamp: i64, micros: bool, timezone: Tz) -> String {
+    use chrono::TimeZone;
+
+    let Some(naive) = timestamp_to_datetime(timestamp, micros).map(|dt| dt.naive_utc()) else {
+        return timestamp.to_string();
+    };
+    match timezone.from_local_datetime(&naive).earliest() {
+        Some(dt) => dt.to_rfc3339_opts(SecondsFormat::AutoSi, false),
+        None => local_timestamp_to_iso8601(timestamp, micros),
+    }
+}
+
+/// Text encoding used to render `bytes` and `fixed` values, which JSON has no type for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BytesEncoding {
+    /// Base64, using the alphabet chosen by [`Base64Mode`].
+    #[default]
+    Base64,
+    /// Lowercase hexadecimal, two digits per byte. Handy for short `fixed` hashes and IDs.
+    Hex,
+}
+
+/// Renders raw bytes as a string, or as an object with their length when
+/// [`JsonOptions::bytes_verbose`] is set.
+fn bytes_to_json(bytes: &[u8], as_text: bool, options: &JsonOptions) -> Value {
+    let text = bytes_to_string(bytes, as_text, options);
+    if !options.bytes_verbose {
+        return Value::String(text);
+    }
+    let key = match options.bytes_encoding {
+        _ if as_text && std::str::from_utf8(bytes).is_ok() => "text",
+        BytesEncoding::Base64 => "base64",
+        BytesEncoding::Hex => "hex",
+    };
+    serde_json::json!({ key: text, "len": bytes.len() })
+}
+
+/// Renders raw bytes as text, passing valid UTF-8 through as-is when `as_text` is set.
+fn bytes_to_string(bytes: &[u8], as_text: bool, options: &JsonOptions) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if as_text => text.to_string(),
+        _ => encode_bytes(bytes, options),
+    }
+}
+
+fn encode_bytes(bytes: &[u8], options: &JsonOptions) -> String {
+    match options.bytes_encoding {
+        BytesEncoding::Base64 => base64_encode_with_mode(bytes, options.base64_mode),
+        BytesEncoding::Hex if options.hex_prefix => format!("0x{}", hex_encode(bytes)),
+        BytesEncoding::Hex => hex_encode(bytes),
+    }
+}
+
+/// Encodes bytes as lowercase hexadecimal without a prefix.
+pub fn hex_encode(bytes: &[u8]) -> String {
+    use fmt::Write as _;
+
+    let mut result = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(result, "{:02x}", byte);
+    }
+    result
+}
+
+/// Alphabet and padding used when rendering `bytes` and `fixed` values as base64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Base64Mode {
+    /// RFC 4648 standard alphabet (`+` and `/`) with `=` padding.
+    #[default]
+    Standard,
+    /// RFC 4648 URL-safe alphabet (`-` and `_`) with `=` padding.
+    UrlSafe,
+    /// URL-safe alphabet without padding.
+    UrlSafeNoPad,
+}
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+    base64_encode_with_mode(bytes, Base64Mode::Standard)
+}
+
+pub fn base64_encode_with_mode(bytes: &[u8], mode: Base64Mode) -> String {
+    const STANDARD: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    const URL_SAFE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let (chars, pad) = match mode {
+        Base64Mode::Standard => (STANDARD, true),
+        Base64Mode::UrlSafe => (URL_SAFE, true),
+        Base64Mode::UrlSafeNoPad => (URL_SAFE, false),
+    };
+    let mut result = String::new();
+    
+    for chunk in bytes.chunks(3) {
+        let mut buf = [0u8; 3];
+        for (i, &b) in chunk.iter().enumerate() {
+            buf[i] = b;
+        }
+        
+        let b0 = buf[0] as usize;
+        let b1 = buf[1] as usize;
+        let b2 = buf[2] as usize;
+        
+        result.push(chars[b0 >> 2] as char);
+        result.push(chars[((b0 & 3) << 4) | (b1 >> 4)] as char);
+        
+        if chunk.len() > 1 {
+            result.push(chars[((b1 & 15) << 2) | (b2 >> 6)] as char);
+        } else if pad {
+            result.push('=');
+        }
+        
+        if chunk.len() > 2 {
+            result.push(chars[b2 & 63] as char);
+        } else if pad {
+            result.push('=');
+        }
+    }
+    
+    result
+}
+
+/// Decodes base64 in either the standard or the URL-safe alphabet, so it reverses every
+/// [`Base64Mode`]. Padding is optional, but when present it must be correct; invalid
+/// characters, misplaced or excess padding, and leftover bits are all rejected.
+pub fn base64_decode(encoded: &str) -> Result<Vec<u8>> {
+    let data = encoded.trim_end_matches('=');
+    let padding = encoded.len() - data.len();
+    if padding > 0 && (!encoded.len().is_multiple_of(4) || padding > 2) {
+        return Err(ConvertError::InvalidBase64(format!("bad padding in {:?}", encoded)));
+    }
+    if data.len() % 4 == 1 {
+        return Err(ConvertError::InvalidBase64(format!("truncated input {:?}", encoded)));
+    }
+
+    let mut result = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for (position, c) in data.char_indices() {
+        let sextet = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            _ => return Err(ConvertError::InvalidBase64(format!("invalid character {:?} at position {}", c, position))),
+        };
+        buffer = (buffer << 6) | sextet;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    // A canonical encoding leaves the unused low bits of the last character zero
+    if buffer != 0 {
+        return Err(ConvertError::InvalidBase64(format!("non-zero trailing bits in {:?}", encoded)));
+    }
+
+    Ok(result)
+}
+
+/// The terminal color given to each kind of JSON token.
+#[cfg(feature = "color")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Paint {
+    Key,
+    String,
+    Escape,
+    Number,
+    Boolean,
+    Null,
+    Colon,
+    Comma,
+    Bracket,
+}
+
+#[cfg(feature = "color")]
+impl Paint {
+    /// Wraps `text` in the ANSI escape codes for this color. The codes are written out
+    /// directly, rather than through a color crate whose output hangs on a process-wide
+    /// switch, so colorizing never touches state shared with the rest of the program.
+    fn paint(self, text: &str) -> String {
+        let code = match self {
+            Paint::Key => "1;34",
+            Paint::String => "32",
+            Paint::Escape | Paint::Number => "33",
+            Paint::Boolean => "31",
+            Paint::Null => "35",
+            Paint::Colon => "36",
+            Paint::Comma => "37",
+            Paint::Bracket => "34",
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+}
+
+/// Adds terminal colors to serialized JSON. Object keys and string values get different
+/// colors; whitespace, including the indentation of pretty output, is passed through as-is.
+#[cfg(feature = "color")]
+fn colorize_json(json_str: &str) -> String {
+    let mut result = String::new();
+    let mut chars = json_str.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => {
+                // A string is colored once it is complete, since only the `:` after it tells
+                // a key from a value. Escapes are kept as separate segments.
+                let mut segments = vec![(ch.to_string(), false)];
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        '\\' => {
+                            // A backslash escapes exactly the next character, or `\u` plus four
+                            // hex digits; the whole sequence is colored as one unit
+                            let mut escape = ch.to_string();
+                            if let Some(escaped) = chars.next() {
+                                escape.push(escaped);
+                                if escaped == 'u' {
+                                    for _ in 0..4 {
+                                        match chars.next_if(char::is_ascii_hexdigit) {
+                                            Some(hex) => escape.push(hex),
+                                            None => break,
+                                        }
+                                    }
+                                }
+                            }
+                            segments.push((escape, true));
+                        }
+                        _ => {
+                            match segments.last_mut() {
+                                Some((text, false)) => text.push(ch),
+                                _ => segments.push((ch.to_string(), false)),
+                            }
+                            if ch == '"' {
+                                break;
+                            }
+                        }
+                    }
+                }
+                let is_key = chars.clone().find(|next_ch| !next_ch.is_whitespace()) == Some(':');
+                for (text, is_escape) in segments {
+                    let paint = if is_escape {
+                        Paint::Escape
+                    } else if is_key {
+                        Paint::Key
+                    } else {
+                        Paint::String
+                    };
+                    result.push_str(&paint.paint(&text));
+                }
+            }
+            ':' => {
+                result.push_str(&Paint::Colon.paint(":"));
+            }
+            ',' => {
+                result.push_str(&Paint::Comma.paint(","));
+            }
+            '{' | '}' | '[' | ']' => {
+                result.push_str(&Paint::Bracket.paint(&ch.to_string()));
+            }
+            _ if ch.is_ascii_digit() || ch == '-' => {
+                // Look ahead to get the full number
+                let mut number = ch.to_string();
+                while let Some(next_ch) =
+                    chars.next_if(|&next_ch| next_ch.is_ascii_digit() || matches!(next_ch, '.' | 'e' | 'E' | '+' | '-'))
+                {
+                    number.push(next_ch);
+                }
+                result.push_str(&Paint::Number.paint(&number));
+            }
+            _ if ch.is_alphabetic() => {
+                // Handle keywords like true, false, null
+                let mut keyword = ch.to_string();
+                while let Some(next_ch) = chars.next_if(|next_ch| next_ch.is_alphabetic()) {
+                    keyword.push(next_ch);
+                }
+                match keyword.as_str() {
+                    "true" | "false" => result.push_str(&Paint::Boolean.paint(&keyword)),
+                    "null" => result.push_str(&Paint::Null.paint(&keyword)),
+                    _ => result.push_str(&keyword),
+                }
+            }
+            _ => {
+                result.push(ch);
+            }
+        }
+    }
+
+    result
+}
+
+/// When JSON output should be colorized. Builds without the `color` feature never colorize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Colorize when the output is a terminal and `NO_COLOR` is not set.
+    Auto,
+    /// Always colorize, even when piping (e.g. into `less -R`).
+    Always,
+    /// Never colorize.
+    #[default]
+    Never,
+}
+
+#[cfg(not(feature = "color"))]
+fn should_colorize(_color: ColorChoice, _is_terminal: bool) -> bool {
+    false
+}
+
+#[cfg(feature = "color")]
+fn should_colorize(color: ColorChoice, is_terminal: bool) -> bool {
+    match color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        // https://no-color.org: any non-empty NO_COLOR disables color
+        ColorChoice::Auto => {
+            std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && is_terminal
+        }
+    }
+}
+
+/// Indentation used for pretty-printed JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    /// This many spaces per level.
+    Spaces(usize),
+    /// One tab per level.
+    Tab,
+}
+
+impl Indent {
+    /// The widest indentation accepted when parsing; anything wider is almost certainly a typo.
+    pub const MAX_SPACES: usize = 16;
+}
+
+/// Two spaces, matching `serde_json::to_string_pretty`.
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(2)
+    }
+}
+
+/// Parses a number of spaces (`0` to [`Indent::MAX_SPACES`]) or `tab`.
+impl std::str::FromStr for Indent {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("tab") {
+            return Ok(Indent::Tab);
+        }
+        match s.parse::<usize>() {
+            Ok(spaces) if spaces <= Indent::MAX_SPACES => Ok(Indent::Spaces(spaces)),
+            _ => Err(format!(
+                "expected a number of spaces from 0 to {} or `tab`, got `{}`",
+                Indent::MAX_SPACES,
+                s
+            )),
+        }
+    }
+}
+
+fn to_string_indented(value: &Value, indent: Indent) -> Result<String> {
+    use serde::Serialize;
+
+    let indent = indent_str(indent);
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(Vec::new(), formatter);
+    value.serialize(&mut serializer)?;
+    Ok(String::from_utf8(serializer.into_inner()).expect("serde_json output is UTF-8"))
+}
+
+fn indent_str(indent: Indent) -> String {
+    match indent {
+        Indent::Spaces(spaces) => " ".repeat(spaces),
+        Indent::Tab => "\t".to_string(),
+    }
+}
+
+/// Serializes a single value followed by a newline.
+fn write_json_record(
+    output: &mut dyn Write,
+    record: &Value,
+    pretty: Option<Indent>,
+    use_color: bool,
+) -> Result<()> {
+    let json_str = match pretty {
+        Some(indent) => to_string_indented(record, indent)?,
+        None => serde_json::to_string(record)?,
+    };
+    write_json_str(output, json_str, use_color)?;
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Writes one element of a JSON array that is streamed rather than built in memory, preceded
+/// by the opening bracket or a separator. Along with [`write_json_array_end`] this writes the
+/// same bytes as [`write_json_record`] would for the whole array.
+fn write_json_array_element(
+    output: &mut dyn Write,
+    record: &Value,
+    index: usize,
+    pretty: Option<Indent>,
+    use_color: bool,
+) -> Result<()> {
+    let opening = if index == 0 { "[" } else { "," };
+    let json_str = match pretty {
+        Some(indent) => {
+            // The element sits one level in; JSON strings can't hold raw newlines, so every
+            // line break is indentation
+            let element = to_string_indented(record, indent)?;
+            let indent = indent_str(indent);
+            format!("{}\n{}{}", opening, indent, element.replace('\n', &format!("\n{}", indent)))
+        }
+        None => format!("{}{}", opening, serde_json::to_string(record)?),
+    };
+    write_json_str(output, json_str, use_color)
+}
+
+/// Closes a streamed JSON array of `len` elements.
+fn write_json_array_end(output: &mut dyn Write, len: usize, pretty: Option<Indent>, use_color: bool) -> Result<()> {
+    let closing = match (len, pretty) {
+        (0, _) => "[]",
+        (_, Some(_)) => "\n]",
+        (_, None) => "]",
+    };
+    write_json_str(output, closing.to_string(), use_color)?;
+    writeln!(output)?;
+    Ok(())
+}
+
+fn write_json_str(output: &mut dyn Write, json_str: String, use_color: bool) -> Result<()> {
+    #[cfg(feature = "color")]
+    let json_str = if use_color { colorize_json(&json_str) } else { json_str };
+    #[cfg(not(feature = "color"))]
+    let _ = use_color;
+    output.write_all(json_str.as_bytes())?;
+    Ok(())
+}
+
+/// Writes already-converted records. `is_terminal` says whether `output` is an interactive
+/// terminal, which decides whether `ColorChoice::Auto` colorizes.
+pub fn write_json_output(
+    mut output: Box<dyn std::io::Write>,
+    records: Vec<Value>,
+    pretty: bool,
+    as_array: bool,
+    color: ColorChoice,
+    is_terminal: bool,
+) -> Result<()> {
+    let pretty = pretty.then_some(Indent::default());
+    write_values(&mut output, records, OutputFormat::Json, pretty, as_array, should_colorize(color, is_terminal))
+}
+
+/// Writes already-converted records in any [`OutputFormat`], compactly and without color.
+/// `as_array` writes a JSON array or a single YAML sequence; see [`OutputFormat`] for how
+/// each format lays out its records.
+pub fn write_formatted_output(
+    mut output: Box<dyn Write>,
+    records: Vec<Value>,
+    format: OutputFormat,
+    as_array: bool,
+) -> Result<()> {
+    match format {
+        OutputFormat::Csv => write_csv_values(&mut output, records),
+        _ => write_values(&mut output, records, format, None, as_array, false),
+    }
+}
+
+fn write_values(
+    output: &mut dyn Write,
+    records: Vec<Value>,
+    format: OutputFormat,
+    pretty: Option<Indent>,
+    as_array: bool,
+    use_color: bool,
+) -> Result<()> {
+    if as_array && format == OutputFormat::Json {
+        // Output as a single JSON array, serialized an element at a time
+        for (index, record) in records.iter().enumerate() {
+            write_json_array_element(output, record, index, pretty, use_color)?;
+        }
+        write_json_array_end(output, records.len(), pretty, use_color)?;
+    } else if as_array && !is_toml(format) {
+        // Output as a single YAML sequence or MessagePack array
+        write_record(output, &Value::Array(records), format, pretty, use_color)?;
+    } else {
+        // Output as newline-delimited JSON (NDJSON), or one document per record
+        for record in records {
+            write_record(output, &record, format, pretty, use_color)?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Serializes one record, or the array of all of them, in `format`. Indentation and color
+/// only apply to JSON.
+fn write_record(
+    output: &mut dyn Write,
+    record: &Value,
+    format: OutputFormat,
+    pretty: Option<Indent>,
+    use_color: bool,
+) -> Result<()> {
+    match format {
+        OutputFormat::Json => write_json_record(output, record, pretty, use_color),
+        OutputFormat::Csv => unreachable!("CSV records are buffered and written by write_csv_values"),
+        #[cfg(feature = "yaml")]
+        OutputFormat::Yaml => {
+            writeln!(output, "---")?;
+            Ok(serde_yaml::to_writer(output, record)?)
+        }
+        #[cfg(feature = "toml")]
+        OutputFormat::Toml => {
+            // Emitting each record as its own `[[records]]` table keeps TOML output streaming
+            let table = match without_nulls(record) {
+                Value::Object(fields) => fields,
+                other => serde_json::Map::from_iter([("value".to_string(), other)]),
+            };
+            let document = serde_json::json!({ "records": [table] });
+            write!(output, "{}", toml::to_string(&document)?)?;
+            Ok(())
+        }
+        #[cfg(feature = "msgpack")]
+        OutputFormat::MessagePack => Ok(rmp_serde::encode::write(output, record)?),
+    }
+}
+
+fn is_yaml(format: OutputFormat) -> bool {
+    match format {
+        #[cfg(feature = "yaml")]
+        OutputFormat::Yaml => true,
+        _ => false,
+    }
+}
+
+fn is_msgpack(format: OutputFormat) -> bool {
+    match format {
+        #[cfg(feature = "msgpack")]
+        OutputFormat::MessagePack => true,
+        _ => false,
+    }
+}
+
+fn is_toml(format: OutputFormat) -> bool {
+    match format {
+        #[cfg(feature = "toml")]
+        OutputFormat::Toml => true,
+        _ => false,
+    }
+}
+
+/// Drops null object fields, all the way down, for TOML which has no null. Nulls inside
+/// arrays can't be dropped without shifting positions, so serializing them still fails.
+#[cfg(feature = "toml")]
+fn without_nulls(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key.clone(), without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(without_nulls).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Writes already-converted records as CSV.
+///
+/// The header is the union of the records' top-level keys, in the order they are first
+/// seen. A record lacking a column gets an empty cell, and nested objects and arrays are
+/// written as JSON text. Records are expected to be flat (see [`flatten_value`]); a record
+/// that isn't an object is written under a `value` column.
+pub fn write_csv_output(mut output: Box<dyn Write>, records: Vec<Value>) -> Result<()> {
+    write_csv_values(&mut output, records)
+}
+
+fn write_csv_values(output: &mut dyn Write, records: Vec<Value>) -> Result<()> {
+    let rows: Vec<serde_json::Map<String, Value>> = records
+        .into_iter()
+        .map(|record| match record {
+            Value::Object(obj) => obj,
+            other => serde_json::Map::from_iter([("value".to_string(), other)]),
+        })
+        .collect();
+
+    let mut columns: Vec<&str> = Vec::new();
+    for row in &rows {
+        for key in row.keys() {
+            if !columns.contains(&key.as_str()) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(output);
+    // Without any columns there's no header, and an empty record would be a stray blank line
+    if !columns.is_empty() {
+        writer.write_record(&columns)?;
+        for row in &rows {
+            writer.write_record(columns.iter().map(|column| csv_cell(row.get(*column))))?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn csv_cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::temp_output_path;
+    use apache_avro::types::Value as AvroValue;
+
+    #[test]
+    fn test_avro_null_to_json() {
+        let avro_value = AvroValue::Null;
+        let json_value = avro_value_to_json(&avro_value).unwrap();
+        assert_eq!(json_value, Value::Null);
+    }
+
+    #[test]
+    fn test_avro_boolean_to_json() {
+        let avro_value = AvroValue::Boolean(true);
+        let json_value = avro_value_to_json(&avro_value).unwrap();
+        assert_eq!(json_value, Value::Bool(true));
+    }
+
+    #[test]
+    fn test_avro_int_to_json() {
+        let avro_value = AvroValue::Int(42);
+        let json_value = avro_value_to_json(&avro_value).unwrap();
+        assert_eq!(json_value, Value::Number(42.into()));
+    }
+
+    #[test]
+    fn test_avro_string_to_json() {
+        let avro_value = AvroValue::String("hello".to_string());
+        let json_value = avro_value_to_json(&avro_value).unwrap();
+        assert_eq!(json_value, Value::String("hello".to_string()));
+    }
+
+    #[test]
+    fn test_avro_array_to_json() {
         let avro_array = vec![
             AvroValue::String("item1".to_string()),
             AvroValue::String("item2".to_string()),
         ];
-        let avro_value = AvroValue::Array(avro_array);
-        let json_value = avro_value_to_json(&avro_value).unwrap();
-        
-        match json_value {
-            Value::Array(arr) => {
-                assert_eq!(arr.len(), 2);
-                assert_eq!(arr[0], Value::String("item1".to_string()));
-                assert_eq!(arr[1], Value::String("item2".to_string()));
+        let avro_value = AvroValue::Array(avro_array);
+        let json_value = avro_value_to_json(&avro_value).unwrap();
+        
+        match json_value {
+            Value::Array(arr) => {
+                assert_eq!(arr.len(), 2);
+                assert_eq!(arr[0], Value::String("item1".to_string()));
+                assert_eq!(arr[1], Value::String("item2".to_string()));
+            }
+            _ => panic!("Expected array"),
+        }
+    }
+
+    #[test]
+    #[allow(clippy::vec_init_then_push)]
+    fn test_avro_record_to_json() {
+        let mut fields = Vec::new();
+        fields.push(("name".to_string(), AvroValue::String("John".to_string())));
+        fields.push(("age".to_string(), AvroValue::Int(30)));
+        
+        let avro_value = AvroValue::Record(fields);
+        let json_value = avro_value_to_json(&avro_value).unwrap();
+        
+        match json_value {
+            Value::Object(obj) => {
+                assert_eq!(obj.len(), 2);
+                assert_eq!(obj["name"], Value::String("John".to_string()));
+                assert_eq!(obj["age"], Value::Number(30.into()));
+            }
+            _ => panic!("Expected object"),
+        }
+    }
+
+    #[test]
+    fn test_base64_encode() {
+        let input = b"hello";
+        let encoded = base64_encode(input);
+        assert_eq!(encoded, "aGVsbG8=");
+        
+        let input2 = b"hello world";
+        let encoded2 = base64_encode(input2);
+        assert_eq!(encoded2, "aGVsbG8gd29ybGQ=");
+    }
+
+    #[test]
+    fn test_base64_decode_round_trip() {
+        // A small LCG keeps the "random" inputs reproducible without a dependency
+        let mut state: u32 = 12345;
+        let mut next_byte = move || {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        };
+
+        for len in (0..=3).chain([16, 100, 257]) {
+            let bytes: Vec<u8> = (0..len).map(|_| next_byte()).collect();
+            for mode in [Base64Mode::Standard, Base64Mode::UrlSafe, Base64Mode::UrlSafeNoPad] {
+                let encoded = base64_encode_with_mode(&bytes, mode);
+                assert_eq!(base64_decode(&encoded).unwrap(), bytes, "{:?} {:?}", mode, encoded);
+            }
+            assert_eq!(base64_decode(&base64_encode(&bytes)).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_base64_decode_rejects_invalid_input() {
+        assert!(base64_decode("SGVsbG8*").is_err());
+        assert!(base64_decode("YQ=").is_err());
+        assert!(base64_decode("Y===").is_err());
+        assert!(base64_decode("Y=Q=").is_err());
+        assert!(base64_decode("YWJjZ").is_err());
+        // `YR==` decodes to the same byte as `YQ==` but with stray bits set
+        assert!(base64_decode("YR==").is_err());
+        assert!(matches!(base64_decode("@@@@"), Err(ConvertError::InvalidBase64(_))));
+    }
+
+    #[test]
+    fn test_hex_bytes_encoding() {
+        use apache_avro::types::Value as AvroValue;
+
+        assert_eq!(hex_encode(&[0xDE, 0xAD]), "dead");
+        assert_eq!(hex_encode(&[0x00, 0x0F, 0xFF]), "000fff");
+        assert_eq!(hex_encode(&[]), "");
+
+        let mut options = JsonOptions {
+            bytes_encoding: BytesEncoding::Hex,
+            ..Default::default()
+        };
+        let bytes = AvroValue::Bytes(vec![0xDE, 0xAD]);
+        let fixed = AvroValue::Fixed(2, vec![0xDE, 0xAD]);
+        assert_eq!(avro_value_to_json_with_options(&bytes, &options).unwrap(), Value::from("dead"));
+        assert_eq!(avro_value_to_json_with_options(&fixed, &options).unwrap(), Value::from("dead"));
+
+        options.hex_prefix = true;
+        assert_eq!(avro_value_to_json_with_options(&fixed, &options).unwrap(), Value::from("0xdead"));
+
+        // Base64 stays the default
+        assert_eq!(avro_value_to_json(&bytes).unwrap(), Value::from("3q0="));
+    }
+
+    #[test]
+    fn test_bytes_verbose() {
+        use apache_avro::types::Value as AvroValue;
+
+        let mut options = JsonOptions {
+            bytes_verbose: true,
+            ..Default::default()
+        };
+        let bytes = AvroValue::Bytes(vec![0xDE, 0xAD, 0xBE]);
+        assert_eq!(
+            avro_value_to_json_with_options(&bytes, &options).unwrap(),
+            serde_json::json!({"base64": "3q2+", "len": 3})
+        );
+        let fixed = AvroValue::Fixed(4, vec![0x00, 0x01, 0x02, 0x03]);
+        assert_eq!(
+            avro_value_to_json_with_options(&fixed, &options).unwrap(),
+            serde_json::json!({"base64": "AAECAw==", "len": 4})
+        );
+        let empty = AvroValue::Bytes(vec![]);
+        assert_eq!(
+            avro_value_to_json_with_options(&empty, &options).unwrap(),
+            serde_json::json!({"base64": "", "len": 0})
+        );
+
+        options.bytes_encoding = BytesEncoding::Hex;
+        assert_eq!(
+            avro_value_to_json_with_options(&fixed, &options).unwrap(),
+            serde_json::json!({"hex": "00010203", "len": 4})
+        );
+        options.fixed_as_text = true;
+        let text = AvroValue::Fixed(2, "héy".as_bytes()[..2].to_vec());
+        assert_eq!(
+            avro_value_to_json_with_options(&text, &options).unwrap(),
+            serde_json::json!({"hex": "68c3", "len": 2})
+        );
+        let text = AvroValue::Fixed(4, "héy".as_bytes().to_vec());
+        assert_eq!(
+            avro_value_to_json_with_options(&text, &options).unwrap(),
+            serde_json::json!({"text": "héy", "len": 4})
+        );
+
+        // A bare string stays the default
+        assert_eq!(avro_value_to_json(&bytes).unwrap(), Value::from("3q2+"));
+    }
+
+    #[test]
+    fn test_bytes_as_text() {
+        use apache_avro::types::Value as AvroValue;
+
+        let options = JsonOptions {
+            bytes_as_text: true,
+            ..Default::default()
+        };
+        let text = AvroValue::Bytes("héllo".as_bytes().to_vec());
+        assert_eq!(avro_value_to_json_with_options(&text, &options).unwrap(), Value::from("héllo"));
+
+        // Invalid UTF-8 falls back to the configured encoding
+        let binary = AvroValue::Bytes(vec![0xFF, 0xFE]);
+        assert_eq!(avro_value_to_json_with_options(&binary, &options).unwrap(), Value::from("//4="));
+
+        // Fixed values are only decoded when asked for separately
+        let fixed = AvroValue::Fixed(2, b"hi".to_vec());
+        assert_eq!(avro_value_to_json_with_options(&fixed, &options).unwrap(), Value::from("aGk="));
+        let options = JsonOptions {
+            fixed_as_text: true,
+            ..options
+        };
+        assert_eq!(avro_value_to_json_with_options(&fixed, &options).unwrap(), Value::from("hi"));
+    }
+
+    #[test]
+    fn test_base64_encode_with_mode() {
+        // 0xfb 0xff exercises the two characters that differ between alphabets
+        let input = [0xfb, 0xff];
+        assert_eq!(base64_encode_with_mode(&input, Base64Mode::Standard), "+/8=");
+        assert_eq!(base64_encode_with_mode(&input, Base64Mode::UrlSafe), "-_8=");
+        assert_eq!(base64_encode_with_mode(&input, Base64Mode::UrlSafeNoPad), "-_8");
+
+        assert_eq!(base64_encode_with_mode(b"a", Base64Mode::UrlSafeNoPad), "YQ");
+        assert_eq!(base64_encode_with_mode(b"abc", Base64Mode::UrlSafeNoPad), "YWJj");
+        assert_eq!(base64_encode_with_mode(b"", Base64Mode::Standard), "");
+    }
+
+    #[test]
+    fn test_avro_bytes_to_json() {
+        let bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f]; // "Hello" in bytes
+        let avro_value = AvroValue::Bytes(bytes);
+        let json_value = avro_value_to_json(&avro_value).unwrap();
+        assert_eq!(json_value, Value::String("SGVsbG8=".to_string()));
+    }
+
+    #[test]
+    fn test_avro_timestamp_to_json() {
+        let json_value = avro_value_to_json(&AvroValue::TimestampMillis(1_700_000_000_000)).unwrap();
+        assert_eq!(json_value, Value::String("2023-11-14T22:13:20Z".to_string()));
+
+        let json_value = avro_value_to_json(&AvroValue::TimestampMicros(1_700_000_000_123_456)).unwrap();
+        assert_eq!(json_value, Value::String("2023-11-14T22:13:20.123456Z".to_string()));
+
+        let json_value = avro_value_to_json(&AvroValue::LocalTimestampMillis(1_700_000_000_500)).unwrap();
+        assert_eq!(json_value, Value::String("2023-11-14T22:13:20.500".to_string()));
+    }
+
+    #[test]
+    fn test_timestamp_to_rfc3339_before_epoch() {
+        assert_eq!(timestamp_to_rfc3339(-1, false), "1969-12-31T23:59:59.999Z");
+        assert_eq!(timestamp_to_rfc3339(-1, true), "1969-12-31T23:59:59.999999Z");
+        assert_eq!(local_timestamp_to_iso8601(-86_400_000, false), "1969-12-31T00:00:00");
+    }
+
+    #[test]
+    fn test_legacy_logical_types() {
+        let options = JsonOptions {
+            legacy_logical_types: true,
+            ..Default::default()
+        };
+        let json_value =
+            avro_value_to_json_with_options(&AvroValue::TimestampMillis(1_700_000_000_000), &options)
+                .unwrap();
+        assert_eq!(json_value, Value::String("timestamp-millis:1700000000000".to_string()));
+    }
+
+    #[test]
+    fn test_duration_formats() {
+        use apache_avro::{Days, Duration, Millis, Months};
+
+        let duration = AvroValue::Duration(Duration::new(Months::new(1), Days::new(2), Millis::new(3_500)));
+        assert_eq!(
+            avro_value_to_json(&duration).unwrap(),
+            serde_json::json!({"months": 1, "days": 2, "millis": 3500})
+        );
+
+        let options = JsonOptions {
+            duration_format: DurationFormat::Iso8601,
+            ..Default::default()
+        };
+        assert_eq!(avro_value_to_json_with_options(&duration, &options).unwrap(), Value::from("P1M2DT3.5S"));
+
+        assert_eq!(duration_to_iso8601(0, 0, 0), "PT0S");
+        assert_eq!(duration_to_iso8601(0, 7, 0), "P7D");
+        assert_eq!(duration_to_iso8601(0, 0, 61_010), "PT61.01S");
+    }
+
+    #[test]
+    fn test_local_timestamp_in_timezone() {
+        let new_york: Tz = "America/New_York".parse().unwrap();
+        // 2023-11-14T22:13:20 as wall-clock time
+        assert_eq!(local_timestamp_in_timezone(1_700_000_000_000, false, new_york), "2023-11-14T22:13:20-05:00");
+        assert_eq!(
+            local_timestamp_in_timezone(1_700_000_000_123_456, true, new_york),
+            "2023-11-14T22:13:20.123456-05:00"
+        );
+        assert_eq!(local_timestamp_in_timezone(1_689_000_000_000, false, new_york), "2023-07-10T14:40:00-04:00");
+        // 2023-03-12T02:30:00 doesn't exist in New York
+        assert_eq!(local_timestamp_in_timezone(1_678_588_200_000, false, new_york), "2023-03-12T02:30:00");
+
+        let options = JsonOptions {
+            local_timezone: Some(new_york),
+            ..Default::default()
+        };
+        let json_value =
+            avro_value_to_json_with_options(&AvroValue::LocalTimestampMillis(1_700_000_000_000), &options).unwrap();
+        assert_eq!(json_value, Value::from("2023-11-14T22:13:20-05:00"));
+    }
+
+    #[test]
+    fn test_custom_date_and_timestamp_formats() {
+        use apache_avro::types::Value as AvroValue;
+
+        let options = JsonOptions {
+            date_format: Some("%Y/%m/%d".to_string()),
+            timestamp_format: Some("%d %b %Y %H:%M:%S%.3f".to_string()),
+            ..Default::default()
+        };
+        let render = |value: AvroValue| avro_value_to_json_with_options(&value, &options).unwrap();
+        assert_eq!(render(AvroValue::Date(19_000)), Value::from("2022/01/08"));
+        assert_eq!(render(AvroValue::TimestampMillis(1_700_000_000_123)), Value::from("14 Nov 2023 22:13:20.123"));
+        assert_eq!(render(AvroValue::TimestampMicros(1_700_000_000_123_456)), Value::from("14 Nov 2023 22:13:20.123"));
+        // Local timestamps keep their own rendering
+        assert_eq!(render(AvroValue::LocalTimestampMillis(0)), Value::from("1970-01-01T00:00:00"));
+
+        let options = JsonOptions {
+            timestamp_format: Some("%+".to_string()),
+            ..Default::default()
+        };
+        let json = avro_value_to_json_with_options(&AvroValue::TimestampMillis(1_700_000_000_000), &options);
+        assert_eq!(json.unwrap(), Value::from("2023-11-14T22:13:20+00:00"));
+
+        assert!(check_date_format("%d.%m.%Y").is_ok());
+        assert!(check_timestamp_format("%s").is_ok());
+        assert!(matches!(check_date_format("%Y %H:%M"), Err(ConvertError::InvalidFormat(_))));
+        assert!(matches!(check_timestamp_format("%Q"), Err(ConvertError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn test_date_days_to_iso() {
+        assert_eq!(date_days_to_iso(0), "1970-01-01");
+        assert_eq!(date_days_to_iso(19000), "2022-01-08");
+        assert_eq!(date_days_to_iso(-1), "1969-12-31");
+        assert_eq!(date_days_to_iso(-719_162), "0001-01-01");
+        assert_eq!(date_days_to_iso(2_932_896), "9999-12-31");
+        assert_eq!(date_days_to_iso(i32::MAX), i32::MAX.to_string());
+    }
+
+    #[test]
+    fn test_avro_date_to_json() {
+        let json_value = avro_value_to_json(&AvroValue::Date(19000)).unwrap();
+        assert_eq!(json_value, Value::String("2022-01-08".to_string()));
+    }
+
+    #[test]
+    fn test_format_decimal() {
+        assert_eq!(format_decimal(&BigInt::from(12345), 2), "123.45");
+        assert_eq!(format_decimal(&BigInt::from(-5), 3), "-0.005");
+        assert_eq!(format_decimal(&BigInt::from(0), 2), "0.00");
+        assert_eq!(format_decimal(&BigInt::from(42), 0), "42");
+    }
+
+    #[test]
+    fn test_avro_decimal_uses_schema_scale() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Price", "fields": [
+                {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}}
+            ]}"#,
+        )
+        .unwrap();
+        let record = AvroValue::Record(vec![(
+            "amount".to_string(),
+            AvroValue::Decimal(apache_avro::Decimal::from(12345u32.to_be_bytes())),
+        )]);
+
+        let json_value = value_to_json(&record, Some(&schema), &Names::new(), &JsonOptions::default()).unwrap();
+        assert_eq!(json_value["amount"], Value::String("123.45".to_string()));
+
+        // Without a schema the scale is unknown, so the unscaled value is emitted
+        let json_value = avro_value_to_json(&record).unwrap();
+        assert_eq!(json_value["amount"], Value::String("12345".to_string()));
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("avro-to-json-{}-{}", std::process::id(), name))
+    }
+
+    /// A container file holding `records`, written with the schema parsed from `schema_json`.
+    fn container(schema_json: &str, records: impl IntoIterator<Item = AvroValue>) -> Vec<u8> {
+        container_blocks(schema_json, [records])
+    }
+
+    /// [`container`] with each group of records in a block of its own.
+    fn container_blocks<B: IntoIterator<Item = AvroValue>>(
+        schema_json: &str,
+        blocks: impl IntoIterator<Item = B>,
+    ) -> Vec<u8> {
+        let schema = Schema::parse_str(schema_json).unwrap();
+        let mut writer = apache_avro::Writer::new(&schema, Vec::new());
+        for block in blocks {
+            for record in block {
+                writer.append(record).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        writer.into_inner().unwrap()
+    }
+
+    const EVENT_SCHEMA: &str = r#"{"type": "record", "name": "Event", "fields": [{"name": "id", "type": "long"}]}"#;
+
+    fn event_schema() -> Schema {
+        Schema::parse_str(EVENT_SCHEMA).unwrap()
+    }
+
+    fn event(id: i64) -> AvroValue {
+        AvroValue::Record(vec![("id".to_string(), AvroValue::Long(id))])
+    }
+
+    /// A container file of `Event` records, one per id.
+    fn event_container(ids: std::ops::Range<i64>) -> Vec<u8> {
+        container(EVENT_SCHEMA, ids.map(event))
+    }
+
+    /// A container file of `Feature` records, one per weight.
+    fn feature_container(weights: &[f64]) -> Vec<u8> {
+        container(
+            r#"{"type": "record", "name": "Feature", "fields": [{"name": "weight", "type": "double"}]}"#,
+            weights.iter().map(|&weight| AvroValue::Record(vec![("weight".to_string(), AvroValue::Double(weight))])),
+        )
+    }
+
+    #[test]
+    fn test_convert_streams_ndjson_records() {
+        let output_path = temp_path("streamed.json");
+        let output = output_path.to_string_lossy().to_string();
+        convert_avro_to_json("test_users.avro", Some(&output), false, false).unwrap();
+
+        let written = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        let lines: Vec<Value> = written
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["name"], Value::String("Alice Johnson".to_string()));
+        assert_eq!(lines[2]["email"], Value::Null);
+    }
+
+    #[test]
+    fn test_failed_conversion_leaves_output_file_untouched() {
+        let output_path = temp_path("atomic.json");
+        let output = output_path.to_string_lossy().to_string();
+        std::fs::write(&output_path, "previous run\n").unwrap();
+
+        assert!(convert_avro_to_json("does_not_exist.avro", Some(&output), false, false).is_err());
+        assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "previous run\n");
+        assert!(!temp_output_path(&output_path).exists());
+
+        convert_avro_to_json("test_users.avro", Some(&output), false, false).unwrap();
+        let written = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(written.lines().count(), 3);
+        assert!(!temp_output_path(&output_path).exists());
+    }
+
+    #[test]
+    fn test_split_output() {
+        let input_path = temp_path("split-input.avro");
+        std::fs::write(&input_path, event_container(0..250_000)).unwrap();
+
+        let output_path = temp_path("split.json");
+        let options = ConvertOptions {
+            as_array: true,
+            split: Some(100_000),
+            ..Default::default()
+        };
+        let input = Input::Path(input_path.to_string_lossy().to_string());
+        convert_avro_to_json_with_options(&input, Some(&output_path.to_string_lossy().to_string()), &options).unwrap();
+        std::fs::remove_file(&input_path).unwrap();
+
+        let mut first_ids = Vec::new();
+        for (index, expected_len) in [100_000, 100_000, 50_000].into_iter().enumerate() {
+            let chunk = chunk_path(&output_path, index);
+            let json: Value = serde_json::from_slice(&std::fs::read(&chunk).unwrap()).unwrap();
+            std::fs::remove_file(&chunk).unwrap();
+            assert_eq!(json.as_array().unwrap().len(), expected_len);
+            first_ids.push(json[0]["id"].clone());
+        }
+        assert_eq!(first_ids, vec![Value::from(0), Value::from(100_000), Value::from(200_000)]);
+        assert!(!chunk_path(&output_path, 3).exists());
+        assert!(!output_path.exists());
+    }
+
+    #[test]
+    fn test_partition_output() {
+        let records = [(1, "US"), (2, "EU"), (3, "US")].map(|(id, region)| {
+            AvroValue::Record(vec![
+                ("id".to_string(), AvroValue::Long(id)),
+                ("region".to_string(), AvroValue::String(region.to_string())),
+            ])
+        });
+        let bytes = container(
+            r#"{"type": "record", "name": "Event", "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "region", "type": "string"}
+            ]}"#,
+            records,
+        );
+        let input_path = temp_path("partition-input.avro");
+        std::fs::write(&input_path, bytes).unwrap();
+        let input = Input::Path(input_path.to_string_lossy().to_string());
+        let output_path = temp_path("partition.json");
+        let output = Some(output_path.to_string_lossy().to_string());
+
+        let options = ConvertOptions {
+            partition_by: Some(PartitionBy {
+                pointer: "/region".to_string(),
+                max_partitions: None,
+            }),
+            ..Default::default()
+        };
+        convert_avro_to_json_with_options(&input, output.as_ref(), &options).unwrap();
+        let us_path = partition_path(&output_path, "/region", Some(&Value::from("US")));
+        let eu_path = partition_path(&output_path, "/region", Some(&Value::from("EU")));
+        assert_eq!(
+            std::fs::read_to_string(&us_path).unwrap(),
+            "{\"id\":1,\"region\":\"US\"}\n{\"id\":3,\"region\":\"US\"}\n"
+        );
+        assert_eq!(std::fs::read_to_string(&eu_path).unwrap(), "{\"id\":2,\"region\":\"EU\"}\n");
+
+        let options = ConvertOptions {
+            as_array: true,
+            ..options
+        };
+        convert_avro_to_json_with_options(&input, output.as_ref(), &options).unwrap();
+        let json: Value = serde_json::from_slice(&std::fs::read(&us_path).unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!([{"id": 1, "region": "US"}, {"id": 3, "region": "US"}]));
+        std::fs::remove_file(&us_path).unwrap();
+        std::fs::remove_file(&eu_path).unwrap();
+
+        let options = ConvertOptions {
+            partition_by: Some(PartitionBy {
+                pointer: "/id".to_string(),
+                max_partitions: Some(2),
+            }),
+            ..options
+        };
+        let err = convert_avro_to_json_with_options(&input, output.as_ref(), &options).unwrap_err();
+        std::fs::remove_file(&input_path).unwrap();
+        assert!(matches!(err, ConvertError::TooManyPartitions(2)));
+        // A failed run leaves no partitions behind
+        assert!(!partition_path(&output_path, "/id", Some(&Value::from(1))).exists());
+        assert!(!output_path.exists());
+    }
+
+    #[test]
+    fn test_block_range() {
+        // Ten blocks of ten records
+        let bytes = container_blocks(EVENT_SCHEMA, (0..10).map(|block| (block * 10..block * 10 + 10).map(event)));
+        let len = bytes.len() as u64;
+        let input_path = temp_path("block-range.avro");
+        std::fs::write(&input_path, bytes).unwrap();
+        let input = Input::Path(input_path.to_string_lossy().to_string());
+
+        let output_path = temp_path("block-range.json");
+        let output = output_path.to_string_lossy().to_string();
+        let convert_range = |start: u64, end: u64| {
+            let options = ConvertOptions {
+                block_range: Some(BlockRange { start, end }),
+                ..Default::default()
+            };
+            convert_avro_to_json_with_options(&input, Some(&output), &options).unwrap();
+            let output = std::fs::read_to_string(&output_path).unwrap();
+            output
+                .lines()
+                .map(|line| serde_json::from_str::<Value>(line).unwrap()["id"].as_i64().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // Three workers splitting the file evenly see every record once, in whole blocks
+        let mut ids = Vec::new();
+        for (start, end) in [(0, len / 3), (len / 3, len * 2 / 3), (len * 2 / 3, u64::MAX)] {
+            let shard = convert_range(start, end);
+            assert!(!shard.is_empty());
+            assert_eq!(shard.len() % 10, 0);
+            ids.extend(shard);
+        }
+        assert_eq!(ids, (0..100).collect::<Vec<_>>());
+
+        assert!(convert_range(len, u64::MAX).is_empty());
+        assert!(convert_range(len / 2, len / 2).is_empty());
+        std::fs::remove_file(&input_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+    }
+
+    #[test]
+    fn test_input_from_str() {
+        assert_eq!(Input::from("-"), Input::Stdin);
+        assert_eq!(Input::from("data.avro"), Input::Path("data.avro".to_string()));
+    }
+
+    #[test]
+    fn test_convert_from_non_seekable_reader() {
+        // A byte slice can only be read forwards, like a pipe
+        let bytes = std::fs::read("test_users.avro").unwrap();
+        let mut output = Vec::new();
+        let options = ConvertOptions {
+            as_array: true,
+            ..Default::default()
+        };
+        convert_bytes(&bytes[..], &mut output, &options).unwrap();
+
+        let json: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_gzip_input_and_output_round_trip() {
+        let gz_input = temp_path("users.avro.gz");
+        let mut encoder = GzEncoder::new(File::create(&gz_input).unwrap(), Compression::default());
+        encoder.write_all(&std::fs::read("test_users.avro").unwrap()).unwrap();
+        encoder.finish().unwrap();
+
+        let plain_output = temp_path("users-plain.json").to_string_lossy().to_string();
+        let gz_output = temp_path("users-gz.json").to_string_lossy().to_string();
+        let options = ConvertOptions::default();
+        convert_avro_to_json_with_options(&Input::from("test_users.avro"), Some(&plain_output), &options).unwrap();
+        convert_avro_to_json_with_options(&Input::Path(gz_input.to_string_lossy().to_string()), Some(&gz_output), &options).unwrap();
+        assert_eq!(std::fs::read(&plain_output).unwrap(), std::fs::read(&gz_output).unwrap());
+
+        let options = ConvertOptions {
+            output_gzip: true,
+            ..Default::default()
+        };
+        convert_avro_to_json_with_options(&Input::from("test_users.avro"), Some(&gz_output), &options).unwrap();
+        let mut decompressed = Vec::new();
+        MultiGzDecoder::new(File::open(&gz_output).unwrap()).read_to_end(&mut decompressed).unwrap();
+        assert_eq!(std::fs::read(&plain_output).unwrap(), decompressed);
+
+        for path in [gz_input.to_string_lossy().to_string(), plain_output, gz_output] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_count_records() {
+        assert_eq!(count_records("test_users.avro").unwrap(), 3);
+        assert!(count_records("does_not_exist.avro").is_err());
+    }
+
+    #[test]
+    fn test_streamed_array_matches_buffered() {
+        let arrays = [
+            vec![],
+            vec![serde_json::json!(1)],
+            vec![
+                serde_json::json!({"id": 1, "tags": ["a", "b"], "empty": {}, "nested": {"list": []}}),
+                serde_json::json!({"id": 2, "note": "line\nbreak", "tags": []}),
+                serde_json::json!(null),
+            ],
+        ];
+        for records in arrays {
+            for pretty in [None, Some(Indent::default()), Some(Indent::Spaces(0)), Some(Indent::Tab)] {
+                let mut streamed = Vec::new();
+                write_values(&mut streamed, records.clone(), OutputFormat::Json, pretty, true, false).unwrap();
+                let mut buffered = Vec::new();
+                write_json_record(&mut buffered, &Value::Array(records.clone()), pretty, false).unwrap();
+                assert_eq!(String::from_utf8(streamed).unwrap(), String::from_utf8(buffered).unwrap());
+            }
+        }
+
+        #[cfg(feature = "color")]
+        {
+            let records = vec![serde_json::json!({"a": "x", "b": [1, -2.5]}), serde_json::json!("y")];
+            let mut streamed = Vec::new();
+            write_values(&mut streamed, records.clone(), OutputFormat::Json, Some(Indent::default()), true, true)
+                .unwrap();
+            let mut buffered = Vec::new();
+            write_json_record(&mut buffered, &Value::Array(records), Some(Indent::default()), true).unwrap();
+            assert_eq!(streamed, buffered);
+        }
+
+        let output = convert_users(&ConvertOptions {
+            as_array: true,
+            pretty: true,
+            ..Default::default()
+        });
+        let json: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), serde_json::to_string_pretty(&json).unwrap() + "\n");
+    }
+
+    #[test]
+    fn test_write_csv_values() {
+        let records = vec![
+            serde_json::json!({"id": 1, "name": "Alice, Jr.", "tags": ["a", "b"]}),
+            serde_json::json!({"id": 2, "email": "bob@example.com", "name": null}),
+        ];
+        let mut output = Vec::new();
+        write_csv_values(&mut output, records).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "id,name,tags,email\n1,\"Alice, Jr.\",\"[\"\"a\"\",\"\"b\"\"]\",\n2,,,bob@example.com\n"
+        );
+
+        let output = convert_users(&ConvertOptions {
+            format: OutputFormat::Csv,
+            limit: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "id,name,email,age,active\n1,Alice Johnson,alice@example.com,28,true\n"
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_output() {
+        let output = convert_users(&ConvertOptions {
+            format: OutputFormat::Yaml,
+            limit: Some(2),
+            select: Some(parse_field_paths("id,name")),
+            ..Default::default()
+        });
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "---\nid: 1\nname: Alice Johnson\n---\nid: 2\nname: Bob Smith\n"
+        );
+
+        let output = convert_users(&ConvertOptions {
+            format: OutputFormat::Yaml,
+            as_array: true,
+            limit: Some(2),
+            select: Some(parse_field_paths("id")),
+            ..Default::default()
+        });
+        assert_eq!(String::from_utf8(output).unwrap(), "---\n- id: 1\n- id: 2\n");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_output() {
+        let records = vec![
+            serde_json::json!({"id": 1, "email": null, "address": {"city": "Paris", "zip": null}}),
+            serde_json::json!("not a record"),
+        ];
+        let mut output = Vec::new();
+        write_values(&mut output, records, OutputFormat::Toml, None, true, false).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let document: toml::Table = output.parse().unwrap();
+        assert_eq!(document["records"][0]["id"].as_integer(), Some(1));
+        assert!(document["records"][0].get("email").is_none());
+        assert_eq!(document["records"][0]["address"]["city"].as_str(), Some("Paris"));
+        assert_eq!(document["records"][1]["value"].as_str(), Some("not a record"));
+
+        let output = convert_users(&ConvertOptions {
+            format: OutputFormat::Toml,
+            ..Default::default()
+        });
+        let document: toml::Table = String::from_utf8(output).unwrap().parse().unwrap();
+        assert_eq!(document["records"].as_array().map(Vec::len), Some(3));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_output() {
+        let json: Vec<Value> = String::from_utf8(convert_users(&ConvertOptions::default()))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        let output = convert_users(&ConvertOptions {
+            format: OutputFormat::MessagePack,
+            ..Default::default()
+        });
+        let mut decoder = rmp_serde::Deserializer::new(&output[..]);
+        let records: Vec<Value> = (0..3).map(|_| serde::Deserialize::deserialize(&mut decoder).unwrap()).collect();
+        assert_eq!(records, json);
+        assert!(decoder.into_inner().is_empty());
+
+        let output = convert_users(&ConvertOptions {
+            format: OutputFormat::MessagePack,
+            as_array: true,
+            ..Default::default()
+        });
+        assert_eq!(rmp_serde::from_slice::<Value>(&output).unwrap(), Value::Array(json));
+    }
+
+    #[test]
+    fn test_large_longs_as_string() {
+        let options = JsonOptions {
+            large_longs_as_string: true,
+            ..Default::default()
+        };
+        let convert = |l: i64| avro_value_to_json_with_options(&AvroValue::Long(l), &options).unwrap();
+
+        assert_eq!(convert(9_007_199_254_740_991), Value::from(9_007_199_254_740_991_i64));
+        assert_eq!(convert(-9_007_199_254_740_991), Value::from(-9_007_199_254_740_991_i64));
+        assert_eq!(convert(9_007_199_254_740_992), Value::from("9007199254740992"));
+        assert_eq!(convert(-9_007_199_254_740_992), Value::from("-9007199254740992"));
+        assert_eq!(convert(i64::MIN), Value::from(i64::MIN.to_string()));
+        assert_eq!(convert(42), Value::from(42));
+
+        // Off by default
+        assert_eq!(avro_value_to_json(&AvroValue::Long(i64::MAX)).unwrap(), Value::from(i64::MAX));
+    }
+
+    #[test]
+    fn test_float_policy() {
+        let nan = AvroValue::Double(f64::NAN);
+        let infinity = AvroValue::Float(f32::INFINITY);
+        let neg_infinity = AvroValue::Double(f64::NEG_INFINITY);
+        assert_eq!(avro_value_to_json(&nan).unwrap(), Value::Null);
+
+        let options = JsonOptions {
+            float_policy: FloatPolicy::String,
+            ..Default::default()
+        };
+        assert_eq!(avro_value_to_json_with_options(&nan, &options).unwrap(), Value::from("NaN"));
+        assert_eq!(avro_value_to_json_with_options(&infinity, &options).unwrap(), Value::from("Infinity"));
+        assert_eq!(avro_value_to_json_with_options(&neg_infinity, &options).unwrap(), Value::from("-Infinity"));
+        assert_eq!(avro_value_to_json_with_options(&AvroValue::Double(1.5), &options).unwrap(), Value::from(1.5));
+
+        let options = JsonOptions {
+            float_policy: FloatPolicy::Error,
+            ..Default::default()
+        };
+        assert!(matches!(
+            avro_value_to_json_with_options(&nan, &options),
+            Err(ConvertError::NonFiniteFloat(_))
+        ));
+    }
+
+    #[test]
+    fn test_round_floats() {
+        let options = JsonOptions {
+            round_floats: Some(2),
+            ..Default::default()
+        };
+        let round = |value: &AvroValue| avro_value_to_json_with_options(value, &options).unwrap();
+        assert_eq!(round(&AvroValue::Double(0.1 + 0.2)), serde_json::json!(0.3));
+        assert_eq!(round(&AvroValue::Double(2.0 / 3.0)), serde_json::json!(0.67));
+        assert_eq!(round(&AvroValue::Double(-1234.5678)), serde_json::json!(-1234.57));
+        assert_eq!(round(&AvroValue::Float(0.1)), serde_json::json!(0.1));
+        assert_eq!(serde_json::to_string(&round(&AvroValue::Double(2.5))).unwrap(), "2.5");
+        assert_eq!(round(&AvroValue::Double(f64::NAN)), Value::Null);
+        // Full precision by default
+        assert_eq!(avro_value_to_json(&AvroValue::Double(0.1 + 0.2)).unwrap(), serde_json::json!(0.30000000000000004));
+    }
+
+    #[test]
+    fn test_decimal_field_override() {
+        let options = JsonOptions {
+            decimal_fields: BTreeMap::from([("price".to_string(), 2)]),
+            ..Default::default()
+        };
+        let record = AvroValue::Record(vec![
+            ("price".to_string(), AvroValue::Bytes(12345u16.to_be_bytes().to_vec())),
+            ("refund".to_string(), AvroValue::Union(1, Box::new(AvroValue::Bytes(vec![0xff, 0x9c])))),
+            ("sku".to_string(), AvroValue::Bytes(vec![0x30, 0x39])),
+            ("line".to_string(), AvroValue::Record(vec![("price".to_string(), AvroValue::Bytes(vec![0x01]))])),
+        ]);
+        let json = avro_value_to_json_with_options(&record, &options).unwrap();
+        assert_eq!(json["price"], Value::from("123.45"));
+        // Only the named top-level fields are overridden
+        assert_eq!(json["refund"], Value::from("/5w="));
+        assert_eq!(json["sku"], Value::from("MDk="));
+        assert_eq!(json["line"]["price"], Value::from("AQ=="));
+
+        let options = JsonOptions {
+            decimal_fields: BTreeMap::from([("refund".to_string(), 1)]),
+            ..Default::default()
+        };
+        let json = avro_value_to_json_with_options(&record, &options).unwrap();
+        assert_eq!(json["refund"], Value::from("-10.0"));
+    }
+
+    #[test]
+    fn test_float_policy_error_reports_record_index() {
+        let bytes = feature_container(&[0.5, 1.0, f64::NAN]);
+
+        let options = ConvertOptions {
+            json: JsonOptions {
+                float_policy: FloatPolicy::Error,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let err = convert_bytes(&bytes[..], &mut output, &options).unwrap_err();
+        assert!(matches!(err, ConvertError::Record { index: 2, .. }));
+        assert_eq!(err.to_string(), "Failed to convert record 2: Non-finite float value NaN");
+    }
+
+    #[test]
+    fn test_strict_utf8_reports_record_and_field() {
+        let records = [(1, "caf\u{e9}"), (2, "caf\u{FFFD}")].map(|(id, tag)| {
+            AvroValue::Record(vec![
+                ("id".to_string(), AvroValue::Long(id)),
+                ("tags".to_string(), AvroValue::Array(vec![AvroValue::String(tag.to_string())])),
+            ])
+        });
+        let bytes = container(
+            r#"{"type": "record", "name": "User", "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "tags", "type": {"type": "array", "items": "string"}}
+            ]}"#,
+            records,
+        );
+
+        let mut output = Vec::new();
+        convert_bytes(&bytes[..], &mut output, &ConvertOptions::default()).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap().lines().count(), 2);
+
+        let mut options = ConvertOptions {
+            json: JsonOptions {
+                strict_utf8: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let expected = "Failed to convert record 1: String at /tags/0 holds a U+FFFD replacement character at byte 3, \
+                        a sign of invalid UTF-8";
+        let err = convert_bytes(&bytes[..], &mut Vec::new(), &options).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+        options.json.avro_json = true;
+        let err = convert_bytes(&bytes[..], &mut Vec::new(), &options).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+
+    #[test]
+    fn test_on_error_skip() {
+        let bytes = feature_container(&[0.5, f64::NAN, 1.0]);
+
+        let options = ConvertOptions {
+            as_array: true,
+            on_error: OnError::Skip,
+            json: JsonOptions {
+                float_policy: FloatPolicy::Error,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        convert_bytes(&bytes[..], &mut output, &options).unwrap();
+        let json: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json, serde_json::json!([{"weight": 0.5}, {"weight": 1.0}]));
+
+        // A corrupt input is abandoned, but the following inputs are still converted
+        let users = std::fs::read("test_users.avro").unwrap();
+        let mut corrupt = users.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xff;
+        let mut output = Vec::new();
+        let readers = [("a.avro".to_string(), Ok(&corrupt[..])), ("b.avro".to_string(), Ok(&users[..]))];
+        convert_readers(readers, &mut output, false, &options).unwrap();
+        let json: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+
+        // A truncated one isn't skipped
+        let truncated = &users[..users.len() - 20];
+        let readers = [("a.avro".to_string(), Ok(truncated)), ("b.avro".to_string(), Ok(&users[..]))];
+        let err = convert_readers(readers, &mut Vec::new(), false, &options).unwrap_err();
+        assert!(matches!(err, ConvertError::Truncated { .. }), "{:?}", err);
+
+        let options = ConvertOptions {
+            on_error: OnError::Abort,
+            ..options
+        };
+        let readers = [("a.avro".to_string(), Ok(&corrupt[..])), ("b.avro".to_string(), Ok(&users[..]))];
+        assert!(convert_readers(readers, &mut Vec::new(), false, &options).is_err());
+    }
+
+    #[test]
+    fn test_parallel_conversion_preserves_order() {
+        let bytes = event_container(0..5000);
+
+        let sequential = ConvertOptions {
+            skip: Some(10),
+            limit: Some(4000),
+            ..Default::default()
+        };
+        let parallel = ConvertOptions {
+            jobs: Some(3),
+            ..sequential.clone()
+        };
+        let mut expected = Vec::new();
+        convert_bytes(&bytes[..], &mut expected, &sequential).unwrap();
+        let mut output = Vec::new();
+        convert_bytes(&bytes[..], &mut output, &parallel).unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(String::from_utf8(output).unwrap().lines().count(), 4000);
+    }
+
+    #[test]
+    fn test_flush_every() {
+        /// Records how many bytes had been written at each flush.
+        #[derive(Default)]
+        struct FlushLog {
+            written: usize,
+            flushes: Vec<usize>,
+        }
+
+        impl Write for FlushLog {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.written += buf.len();
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.flushes.push(self.written);
+                Ok(())
+            }
+        }
+
+        let bytes = std::fs::read("test_users.avro").unwrap();
+        let options = ConvertOptions {
+            flush_every: Some(2),
+            ..Default::default()
+        };
+        let mut output = FlushLog::default();
+        convert_bytes(&bytes[..], &mut output, &options).unwrap();
+        // Once after the second record, then the final flush after the third
+        assert_eq!(output.flushes.len(), 2);
+        assert!(output.flushes[0] > 0 && output.flushes[0] < output.written);
+
+        let mut output = FlushLog::default();
+        convert_bytes(&bytes[..], &mut output, &ConvertOptions::default()).unwrap();
+        assert_eq!(output.flushes, vec![output.written]);
+    }
+
+    #[test]
+    fn test_progress_leaves_output_alone() {
+        let with_progress = convert_users(&ConvertOptions {
+            progress: true,
+            ..Default::default()
+        });
+        assert_eq!(with_progress, convert_users(&ConvertOptions::default()));
+    }
+
+    #[test]
+    fn test_indent() {
+        assert_eq!("4".parse::<Indent>(), Ok(Indent::Spaces(4)));
+        assert_eq!("tab".parse::<Indent>(), Ok(Indent::Tab));
+        assert!("-1".parse::<Indent>().is_err());
+        assert!("17".parse::<Indent>().is_err());
+        assert!("wide".parse::<Indent>().is_err());
+
+        let record = serde_json::json!({"id": 1, "tags": ["a"]});
+        assert_eq!(
+            to_string_indented(&record, Indent::Spaces(4)).unwrap(),
+            "{\n    \"id\": 1,\n    \"tags\": [\n        \"a\"\n    ]\n}"
+        );
+        assert_eq!(to_string_indented(&record, Indent::Tab).unwrap(), "{\n\t\"id\": 1,\n\t\"tags\": [\n\t\t\"a\"\n\t]\n}");
+        assert_eq!(
+            to_string_indented(&record, Indent::default()).unwrap(),
+            serde_json::to_string_pretty(&record).unwrap()
+        );
+
+        let output = convert_users(&ConvertOptions {
+            pretty: true,
+            indent: Indent::Spaces(4),
+            limit: Some(1),
+            ..Default::default()
+        });
+        assert!(String::from_utf8(output).unwrap().starts_with("{\n    \"id\": 1,"));
+    }
+
+    #[test]
+    fn test_with_metadata() {
+        let inputs = vec!["test_users.avro".to_string(), "test_products.avro".to_string()];
+        let output_path = temp_path("with-metadata.json");
+        let output = output_path.to_string_lossy().to_string();
+        let options = ConvertOptions {
+            as_array: true,
+            skip: Some(2),
+            metadata_prefix: Some("_".to_string()),
+            ..Default::default()
+        };
+        convert_many(&inputs, Some(&output), &options).unwrap();
+        let json: Value = serde_json::from_str(&std::fs::read_to_string(&output_path).unwrap()).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+
+        let records = json.as_array().unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0]["_file"], Value::from("test_users.avro"));
+        assert_eq!(records[0]["_index"], Value::from(2));
+        assert_eq!(records[0]["_record"]["name"], Value::from("Charlie Brown"));
+        assert_eq!(records[1]["_file"], Value::from("test_products.avro"));
+        assert_eq!(records[1]["_index"], Value::from(0));
+
+        let output = convert_users(&ConvertOptions {
+            limit: Some(1),
+            metadata_prefix: Some("@".to_string()),
+            ..Default::default()
+        });
+        assert!(String::from_utf8(output).unwrap().starts_with(r#"{"@file":"test.avro","@index":0,"@record":{"id":1,"#));
+    }
+
+    #[test]
+    fn test_zero_record_file() {
+        let schema = event_schema();
+        // apache_avro's writer skips the header when nothing was appended, so build one by hand:
+        // magic, metadata map, sync marker, and no data blocks
+        let metadata = AvroValue::Map(HashMap::from([
+            ("avro.schema".to_string(), AvroValue::Bytes(schema.canonical_form().into_bytes())),
+            ("avro.codec".to_string(), AvroValue::Bytes(b"null".to_vec())),
+        ]));
+        let mut bytes = b"Obj\x01".to_vec();
+        bytes.extend(apache_avro::to_avro_datum(&Schema::Map(Box::new(Schema::Bytes)), metadata).unwrap());
+        bytes.extend([7u8; 16]);
+        assert_eq!(Reader::new(&bytes[..]).unwrap().count(), 0);
+
+        let mut output = Vec::new();
+        convert_bytes(&bytes, &mut output, &ConvertOptions::default()).unwrap();
+        assert!(output.is_empty());
+
+        for pretty in [false, true] {
+            let options = ConvertOptions {
+                as_array: true,
+                pretty,
+                ..Default::default()
+            };
+            let mut output = Vec::new();
+            convert_bytes(&bytes, &mut output, &options).unwrap();
+            assert_eq!(String::from_utf8(output).unwrap(), "[]\n");
+        }
+    }
+
+    #[test]
+    fn test_fixed_uuid_and_decimal() {
+        // Written by hand, since apache_avro can neither parse this schema nor write it out
+        let schema = r#"{"type": "record", "name": "Payment", "fields": [
+            {"name": "id", "type": {"type": "fixed", "name": "Id", "size": 16, "logicalType": "uuid"}},
+            {"name": "amount", "type": {"type": "fixed", "name": "Amount", "size": 8, "logicalType": "decimal",
+                "precision": 18, "scale": 2}}
+        ]}"#;
+        let metadata = AvroValue::Map(HashMap::from([(
+            "avro.schema".to_string(),
+            AvroValue::Bytes(schema.as_bytes().to_vec()),
+        )]));
+        let uuid: uuid::Uuid = "123e4567-e89b-12d3-a456-426614174000".parse().unwrap();
+        let mut data = Vec::new();
+        for (uuid, unscaled) in [(uuid, 1234i64), (uuid::Uuid::nil(), -150)] {
+            data.extend(uuid.as_bytes());
+            data.extend(unscaled.to_be_bytes());
+        }
+        let long = |n: usize| apache_avro::to_avro_datum(&Schema::Long, AvroValue::Long(n as i64)).unwrap();
+        let mut bytes = b"Obj\x01".to_vec();
+        bytes.extend(apache_avro::to_avro_datum(&Schema::Map(Box::new(Schema::Bytes)), metadata).unwrap());
+        bytes.extend([7u8; 16]);
+        bytes.extend(long(2));
+        bytes.extend(long(data.len()));
+        bytes.extend(data);
+        bytes.extend([7u8; 16]);
+
+        let mut output = Vec::new();
+        convert_bytes(&bytes, &mut output, &ConvertOptions::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                r#"{"id":"123e4567-e89b-12d3-a456-426614174000","amount":"12.34"}"#,
+                "\n",
+                r#"{"id":"00000000-0000-0000-0000-000000000000","amount":"-1.50"}"#,
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_sort_keys_option() {
+        let output = convert_users(&ConvertOptions {
+            limit: Some(1),
+            ..Default::default()
+        });
+        assert!(String::from_utf8(output).unwrap().starts_with("{\"id\":1,\"name\":"));
+
+        let output = convert_users(&ConvertOptions {
+            limit: Some(1),
+            sort_keys: true,
+            ..Default::default()
+        });
+        assert!(String::from_utf8(output).unwrap().starts_with("{\"active\":true,\"age\":28,"));
+    }
+
+    #[test]
+    fn test_omit_nulls_option() {
+        let options = ConvertOptions {
+            omit_nulls: true,
+            ..Default::default()
+        };
+        let output = String::from_utf8(convert_users(&options)).unwrap();
+        assert_eq!(output.lines().last(), Some(r#"{"id":3,"name":"Charlie Brown","active":true}"#));
+    }
+
+    #[test]
+    fn test_null_as_option() {
+        for repr in ["", "NULL"] {
+            let options = ConvertOptions {
+                null_as: Some(repr.to_string()),
+                ..Default::default()
+            };
+            let output = String::from_utf8(convert_users(&options)).unwrap();
+            let last: Value = serde_json::from_str(output.lines().last().unwrap()).unwrap();
+            assert_eq!(last["email"], Value::from(repr));
+            assert_eq!(last["age"], Value::from(repr));
+            assert_eq!(last["active"], Value::Bool(true));
+        }
+    }
+
+    #[test]
+    fn test_max_records_in_memory() {
+        let bytes = std::fs::read("test_users.avro").unwrap();
+        let options = ConvertOptions {
+            format: OutputFormat::Csv,
+            max_records_in_memory: Some(2),
+            ..Default::default()
+        };
+        let err = convert_bytes(&bytes, &mut Vec::new(), &options).unwrap_err();
+        assert!(matches!(err, ConvertError::TooManyRecordsInMemory(2)));
+        assert!(err.to_string().contains("newline-delimited JSON"));
+
+        // Streamed output never holds records back
+        let options = ConvertOptions {
+            as_array: true,
+            max_records_in_memory: Some(2),
+            ..Default::default()
+        };
+        convert_bytes(&bytes, &mut Vec::new(), &options).unwrap();
+        let options = ConvertOptions {
+            format: OutputFormat::Csv,
+            max_records_in_memory: Some(3),
+            ..Default::default()
+        };
+        convert_bytes(&bytes, &mut Vec::new(), &options).unwrap();
+    }
+
+    #[test]
+    fn test_envelope_output() {
+        let options = ConvertOptions {
+            envelope: Some(Envelope::default()),
+            ..Default::default()
+        };
+        let output: Value = serde_json::from_slice(&convert_users(&options)).unwrap();
+        let object = output.as_object().unwrap();
+        assert_eq!(object.keys().collect::<Vec<_>>(), ["schema", "count", "records"]);
+        let schema: Value = serde_json::from_str(&print_avro_schema("test_users.avro").unwrap()).unwrap();
+        assert_eq!(output["schema"], schema);
+        assert_eq!(output["count"], Value::from(3));
+        let records = output["records"].as_array().unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1]["name"], Value::from("Bob Smith"));
+
+        let options = ConvertOptions {
+            envelope: Some(Envelope {
+                schema_key: "writer_schema".to_string(),
+                count_key: "total".to_string(),
+                records_key: "items".to_string(),
+            }),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let output: Value = serde_json::from_slice(&convert_users(&options)).unwrap();
+        assert_eq!(output["writer_schema"]["name"], Value::from("User"));
+        assert_eq!(output["total"], Value::from(1));
+        assert_eq!(output["items"][0]["id"], Value::from(1));
+    }
+
+    #[test]
+    fn test_key_by_option() {
+        let options = ConvertOptions {
+            key_by: Some(KeyBy {
+                pointer: "/id".to_string(),
+                last_wins: false,
+            }),
+            as_array: true,
+            ..Default::default()
+        };
+        let output: Value = serde_json::from_slice(&convert_users(&options)).unwrap();
+        let object = output.as_object().unwrap();
+        assert_eq!(object.keys().collect::<Vec<_>>(), ["1", "2", "3"]);
+        assert_eq!(object["3"]["name"], Value::from("Charlie Brown"));
+
+        let bytes = std::fs::read("test_users.avro").unwrap();
+        let options = ConvertOptions {
+            key_by: Some(KeyBy {
+                pointer: "/active".to_string(),
+                last_wins: false,
+            }),
+            ..Default::default()
+        };
+        let result = convert_bytes(&bytes, &mut Vec::new(), &options);
+        assert!(matches!(result, Err(ConvertError::DuplicateKey(key)) if key == "true"));
+    }
+
+    #[test]
+    fn test_avro_info() {
+        let info = avro_info("test_users.avro").unwrap();
+        assert_eq!(info.schema_type(), "record");
+        assert_eq!(info.field_names(), vec!["id", "name", "email", "age", "active"]);
+        assert_eq!(info.codec, "null");
+        assert_eq!(info.record_count, 3);
+        assert_eq!(info.to_json()["name"], Value::from("User"));
+
+        let schema = Schema::parse_str(r#"{"type": "array", "items": "long"}"#).unwrap();
+        let mut writer = apache_avro::Writer::with_codec(&schema, Vec::new(), apache_avro::Codec::Deflate);
+        writer.append(AvroValue::Array(vec![AvroValue::Long(1)])).unwrap();
+        let bytes = writer.into_inner().unwrap();
+        let (metadata, mut replayed) = read_container_metadata(&bytes[..]).unwrap();
+        assert_eq!(metadata["avro.codec"], b"deflate");
+        let mut all = Vec::new();
+        replayed.read_to_end(&mut all).unwrap();
+        assert_eq!(all, bytes);
+    }
+
+    #[test]
+    fn test_compressed_containers() {
+        use apache_avro::Codec;
+
+        let schema = event_schema();
+        let codecs = [
+            Codec::Null,
+            Codec::Deflate,
+            #[cfg(feature = "snappy")]
+            Codec::Snappy,
+            #[cfg(feature = "zstandard")]
+            Codec::Zstandard,
+            #[cfg(feature = "bzip")]
+            Codec::Bzip2,
+            #[cfg(feature = "xz")]
+            Codec::Xz,
+        ];
+        for codec in codecs {
+            let mut writer = apache_avro::Writer::with_codec(&schema, Vec::new(), codec);
+            writer.append(AvroValue::Record(vec![("id".to_string(), AvroValue::Long(7))])).unwrap();
+            let bytes = writer.into_inner().unwrap();
+
+            let mut output = Vec::new();
+            convert_bytes(&bytes[..], &mut output, &ConvertOptions::default()).unwrap();
+            assert_eq!(String::from_utf8(output).unwrap(), "{\"id\":7}\n", "codec {:?}", codec);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_codec_error() {
+        let err = container_error(apache_avro::Error::CodecNotSupported("snappy".to_string()));
+        assert_eq!(err.to_string(), "file uses snappy codec; rebuild with the snappy feature");
+        let err = container_error(apache_avro::Error::CodecNotSupported("bzip2".to_string()));
+        assert_eq!(err.to_string(), "file uses bzip2 codec; rebuild with the bzip feature");
+        let err = container_error(apache_avro::Error::CodecNotSupported("lz4".to_string()));
+        assert_eq!(err.to_string(), "file uses lz4 codec, which is not supported");
+    }
+
+    #[test]
+    fn test_validate_avro() {
+        assert_eq!(validate_avro("test_users.avro").unwrap(), 3);
+
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "User", "fields": [{"name": "id", "type": "long"}]}"#,
+        )
+        .unwrap();
+        let mut validator = apache_avro::Writer::new(&schema, std::io::sink());
+        let valid = AvroValue::Record(vec![("id".to_string(), AvroValue::Long(1))]);
+        let invalid = AvroValue::Record(vec![("id".to_string(), AvroValue::String("one".to_string()))]);
+        validate_record(&mut validator, 0, &valid).unwrap();
+        match validate_record(&mut validator, 1, &invalid) {
+            Err(ConvertError::InvalidRecord { index, .. }) => assert_eq!(index, 1),
+            other => panic!("expected an invalid record error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_reader_schema_resolution() {
+        let reader_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "User", "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "name", "type": "string"},
+                {"name": "country", "type": "string", "default": "FR"}
+            ]}"#,
+        )
+        .unwrap();
+        let output = convert_users(&ConvertOptions {
+            reader_schema: Some(reader_schema),
+            ..Default::default()
+        });
+
+        let first: Value = serde_json::from_str(String::from_utf8(output).unwrap().lines().next().unwrap()).unwrap();
+        assert_eq!(first, serde_json::json!({"id": 1, "name": "Alice Johnson", "country": "FR"}));
+    }
+
+    #[test]
+    fn test_reader_schema_field_order() {
+        let order = AvroValue::Record(vec![
+            ("id".to_string(), AvroValue::Long(7)),
+            ("note".to_string(), AvroValue::String("gift".to_string())),
+            (
+                "customer".to_string(),
+                AvroValue::Record(vec![
+                    ("name".to_string(), AvroValue::String("Alice".to_string())),
+                    ("email".to_string(), AvroValue::String("alice@example.com".to_string())),
+                    ("city".to_string(), AvroValue::String("Paris".to_string())),
+                ]),
+            ),
+            ("total".to_string(), AvroValue::Double(9.5)),
+        ]);
+        let bytes = container(
+            r#"{"type": "record", "name": "Order", "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "note", "type": "string"},
+                {"name": "customer", "type": {"type": "record", "name": "Customer", "fields": [
+                    {"name": "name", "type": "string"},
+                    {"name": "email", "type": "string"},
+                    {"name": "city", "type": "string"}
+                ]}},
+                {"name": "total", "type": "double"}
+            ]}"#,
+            [order],
+        );
+
+        // Reordered at both levels, with writer fields dropped and a defaulted field added
+        let reader_schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Order", "fields": [
+                {"name": "total", "type": "double"},
+                {"name": "customer", "type": {"type": "record", "name": "Customer", "fields": [
+                    {"name": "city", "type": "string"},
+                    {"name": "name", "type": "string"}
+                ]}},
+                {"name": "currency", "type": "string", "default": "EUR"},
+                {"name": "id", "type": "long"}
+            ]}"#,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        let options = ConvertOptions {
+            reader_schema: Some(reader_schema),
+            ..Default::default()
+        };
+        convert_bytes(&bytes[..], &mut output, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"total\":9.5,\"customer\":{\"city\":\"Paris\",\"name\":\"Alice\"},\"currency\":\"EUR\",\"id\":7}\n"
+        );
+    }
+
+    #[test]
+    fn test_single_object_encoding() {
+        let schema = event_schema();
+        let mut encoder = apache_avro::GenericSingleObjectWriter::new_with_capacity(&schema, 64).unwrap();
+        let mut messages = Vec::new();
+        for id in 0..3 {
+            let event = AvroValue::Record(vec![("id".to_string(), AvroValue::Long(id))]);
+            encoder.write_value_ref(&event, &mut messages).unwrap();
+        }
+        let options = ConvertOptions {
+            single_object_schema: Some(schema),
+            ..Default::default()
+        };
+
+        let message_len = messages.len() / 3;
+        let mut output = Vec::new();
+        convert_bytes(&messages[..message_len], &mut output, &options).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"id\":0}\n");
+
+        let mut output = Vec::new();
+        convert_bytes(&messages[..], &mut output, &options).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"id\":0}\n{\"id\":1}\n{\"id\":2}\n");
+
+        // A message cut short is reported rather than silently dropped
+        let mut output = Vec::new();
+        assert!(convert_bytes(&messages[..messages.len() - 1], &mut output, &options).is_err());
+    }
+
+    #[test]
+    fn test_framed_datums() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Event", "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "kind", "type": "string"}
+            ]}"#,
+        )
+        .unwrap();
+        let mut frames = Vec::new();
+        for (id, kind) in [(1, "click"), (300, "view")] {
+            let event = AvroValue::Record(vec![
+                ("id".to_string(), AvroValue::Long(id)),
+                ("kind".to_string(), AvroValue::String(kind.to_string())),
+            ]);
+            let datum = apache_avro::to_avro_datum(&schema, event).unwrap();
+            frames.extend((datum.len() as u32).to_be_bytes());
+            frames.extend(datum);
+        }
+        let options = ConvertOptions {
+            framed_schema: Some(schema),
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        convert_bytes(&frames, &mut output, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"id\":1,\"kind\":\"click\"}\n{\"id\":300,\"kind\":\"view\"}\n"
+        );
+
+        // The second frame starts after the first's 4-byte prefix and 7-byte datum
+        let err = convert_bytes(&frames[..frames.len() - 2], &mut Vec::new(), &options).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid frame at byte 11: input ends after 5 of the 7 bytes of its datum");
+        let err = convert_bytes(&frames[..13], &mut Vec::new(), &options).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid frame at byte 11: input ends after 2 of the 4 bytes of its length prefix");
+    }
+
+    #[test]
+    fn test_read_metadata() {
+        let schema = Schema::parse_str(r#""long""#).unwrap();
+        let mut writer = apache_avro::Writer::new(&schema, Vec::new());
+        writer.add_user_metadata("producer".to_string(), "billing-service").unwrap();
+        writer.add_user_metadata("checksum".to_string(), [0xff, 0x00]).unwrap();
+        writer.append(AvroValue::Long(1)).unwrap();
+        let path = temp_path("metadata.avro");
+        std::fs::write(&path, writer.into_inner().unwrap()).unwrap();
+
+        let metadata = read_metadata(&path.to_string_lossy()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(metadata, serde_json::json!({"checksum": "/wA=", "producer": "billing-service"}));
+        assert_eq!(read_metadata("test_users.avro").unwrap(), serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_print_avro_schema() {
+        let schema: Value = serde_json::from_str(&print_avro_schema("test_users.avro").unwrap()).unwrap();
+        assert_eq!(schema["type"], Value::String("record".to_string()));
+        assert_eq!(schema["name"], Value::String("User".to_string()));
+        assert_eq!(schema["fields"].as_array().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_schema_fingerprint() {
+        // Computed independently from the parsing canonical form of the User schema:
+        // {"name":"User","type":"record","fields":[{"name":"id","type":"int"},...]}
+        assert_eq!(
+            schema_fingerprint("test_users.avro", FingerprintAlgorithm::Rabin).unwrap(),
+            "84c431c97b499a83"
+        );
+        assert_eq!(
+            schema_fingerprint("test_users.avro", FingerprintAlgorithm::Sha256).unwrap(),
+            "abd2a759ea9fd36b0afca313d5bc2f67a9e974e4d167274f0d03c5ec5c34f8b0"
+        );
+        // The example from the specification: "int" has the fingerprint 0x7275d51a3f395c8f
+        assert_eq!(Schema::Int.fingerprint::<apache_avro::rabin::Rabin>().to_string(), "8f5c393f1ad57572");
+    }
+
+    fn convert_bytes(bytes: &[u8], output: &mut dyn Write, options: &ConvertOptions) -> Result<()> {
+        convert_readers([("test.avro".to_string(), Ok(bytes))], output, false, options).map(drop)
+    }
+
+    fn convert_users(options: &ConvertOptions) -> Vec<u8> {
+        let bytes = std::fs::read("test_users.avro").unwrap();
+        let mut output = Vec::new();
+        convert_bytes(&bytes[..], &mut output, options).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        // Every record in a block of its own
+        let bytes = container_blocks(r#""long""#, (1..=3).map(|id| [AvroValue::Long(id)]));
+
+        let mut output = Vec::new();
+        let err = convert_bytes(&bytes[..bytes.len() - 5], &mut output, &ConvertOptions::default()).unwrap_err();
+        assert!(matches!(&err, ConvertError::Truncated { records: 2, .. }), "{:?}", err);
+        assert_eq!(
+            err.to_string(),
+            "test.avro is truncated: it ends partway through a block, after 2 complete records"
+        );
+        assert_eq!(output, b"1\n2\n");
+
+        // A file that ends after its last block is complete
+        let mut output = Vec::new();
+        convert_bytes(&bytes, &mut output, &ConvertOptions::default()).unwrap();
+        assert_eq!(output, b"1\n2\n3\n");
+
+        // Corrupt data is still a plain decode error
+        let mut corrupt = bytes.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xff;
+        let err = convert_bytes(&corrupt, &mut Vec::new(), &ConvertOptions::default()).unwrap_err();
+        assert!(matches!(err, ConvertError::AvroRead(_)), "{:?}", err);
+
+        // Truncation isn't a bad record to skip
+        let options = ConvertOptions {
+            on_error: OnError::Skip,
+            ..Default::default()
+        };
+        let err = convert_bytes(&bytes[..bytes.len() - 5], &mut Vec::new(), &options).unwrap_err();
+        assert!(matches!(err, ConvertError::Truncated { kind: Truncation::Block, records: 2, .. }), "{:?}", err);
+
+        // The records before the cut are still written as complete output
+        let options = ConvertOptions {
+            as_array: true,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let err = convert_bytes(&bytes[..bytes.len() - 5], &mut output, &options).unwrap_err();
+        assert!(matches!(err, ConvertError::Truncated { .. }), "{:?}", err);
+        assert_eq!(serde_json::from_slice::<Value>(&output).unwrap(), serde_json::json!([1, 2]));
+        let input_path = temp_path("truncated.avro");
+        std::fs::write(&input_path, &bytes[..bytes.len() - 5]).unwrap();
+        let output_path = temp_path("truncated-array.json");
+        let output = output_path.to_string_lossy().to_string();
+        let err = convert(&input_path.to_string_lossy(), Some(&output), &options).unwrap_err();
+        std::fs::remove_file(&input_path).unwrap();
+        assert!(matches!(err, ConvertError::Truncated { .. }), "{:?}", err);
+        let written: Value = serde_json::from_slice(&std::fs::read(&output_path).unwrap()).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(written, serde_json::json!([1, 2]));
+
+        // A cut gzip stream is told apart from a cut container
+        let gz_input = temp_path("truncated.avro.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes).unwrap();
+        let compressed = encoder.finish().unwrap();
+        std::fs::write(&gz_input, &compressed[..compressed.len() - 4]).unwrap();
+        let output_path = temp_path("truncated.json").to_string_lossy().to_string();
+        let err = convert(&gz_input.to_string_lossy(), Some(&output_path), &ConvertOptions::default()).unwrap_err();
+        std::fs::remove_file(&gz_input).unwrap();
+        assert_eq!(std::fs::read(&output_path).unwrap(), b"1\n2\n3\n");
+        std::fs::remove_file(&output_path).unwrap();
+        assert!(matches!(err, ConvertError::Truncated { kind: Truncation::GzipStream, .. }), "{:?}", err);
+        let message = err.to_string();
+        assert!(message.ends_with("is truncated: its gzip stream ends early, after 3 complete records"), "{}", message);
+
+        // Streams without blocks keep their own errors
+        let schema = Schema::parse_str(r#""long""#).unwrap();
+        let mut encoder = apache_avro::GenericSingleObjectWriter::new_with_capacity(&schema, 64).unwrap();
+        let mut messages = Vec::new();
+        encoder.write_value_ref(&AvroValue::Long(1), &mut messages).unwrap();
+        let options = ConvertOptions {
+            single_object_schema: Some(schema),
+            ..Default::default()
+        };
+        let err = convert_bytes(&messages[..messages.len() - 1], &mut Vec::new(), &options).unwrap_err();
+        assert!(matches!(err, ConvertError::AvroRead(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn test_filter_records() {
+        let options = ConvertOptions {
+            filter: Some("/active==true".parse().unwrap()),
+            ..Default::default()
+        };
+        let output = String::from_utf8(convert_users(&options)).unwrap();
+        let ids: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["id"].clone())
+            .collect();
+        assert_eq!(ids, vec![Value::from(1), Value::from(3)]);
+
+        // The limit counts matching records, and the filter sees fields `select` drops
+        let options = ConvertOptions {
+            as_array: true,
+            limit: Some(1),
+            filter: Some("/age!=28".parse().unwrap()),
+            select: Some(parse_field_paths("name")),
+            ..Default::default()
+        };
+        let json: Value = serde_json::from_slice(&convert_users(&options)).unwrap();
+        assert_eq!(json, serde_json::json!([{"name": "Bob Smith"}]));
+    }
+
+    #[test]
+    fn test_describe_schema_for_logs() {
+        let record =
+            Schema::parse_str(r#"{"type": "record", "name": "User", "namespace": "app", "fields": []}"#).unwrap();
+        assert_eq!(SchemaContext::new(&record).unwrap().describe(), "record (app.User)");
+        let map = Schema::parse_str(r#"{"type": "map", "values": "long"}"#).unwrap();
+        assert_eq!(SchemaContext::new(&map).unwrap().describe(), "map");
+    }
+
+    #[test]
+    fn test_select_by_field_alias() {
+        let customer = AvroValue::Record(vec![
+            ("email_address".to_string(), AvroValue::String("a@example.com".to_string())),
+            (
+                "address".to_string(),
+                AvroValue::Union(
+                    1,
+                    Box::new(AvroValue::Record(vec![("postcode".to_string(), AvroValue::String("75001".to_string()))])),
+                ),
+            ),
+            ("id".to_string(), AvroValue::Long(1)),
+        ]);
+        let bytes = container(
+            r#"{"type": "record", "name": "Customer", "fields": [
+                {"name": "email_address", "aliases": ["email"], "type": "string"},
+                {"name": "address", "aliases": ["addr"], "type": ["null", {"type": "record", "name": "Address",
+                    "fields": [{"name": "postcode", "aliases": ["zip"], "type": "string"}]}]},
+                {"name": "id", "type": "long"}
+            ]}"#,
+            [customer],
+        );
+
+        let select = |paths: &str| {
+            let options = ConvertOptions {
+                select: Some(parse_field_paths(paths)),
+                ..Default::default()
+            };
+            let mut output = Vec::new();
+            convert_bytes(&bytes, &mut output, &options).unwrap();
+            serde_json::from_slice::<Value>(&output).unwrap()
+        };
+        let expected = serde_json::json!({"email_address": "a@example.com", "address": {"postcode": "75001"}});
+        assert_eq!(select("email_address,address.postcode"), expected);
+        assert_eq!(select("email,addr.zip"), expected);
+        assert_eq!(select("email,unknown.zip"), serde_json::json!({"email_address": "a@example.com"}));
+
+        let options = ConvertOptions {
+            select: Some(parse_field_paths("email")),
+            json: JsonOptions {
+                key_style: KeyStyle::Camel,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        convert_bytes(&bytes, &mut output, &options).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"emailAddress\":\"a@example.com\"}\n");
+    }
+
+    #[test]
+    fn test_dedup_records() {
+        let records = [Some(1), Some(2), Some(1), None, None, Some(2)].into_iter().enumerate().map(|(seq, event_id)| {
+            let meta = match event_id {
+                Some(event_id) => AvroValue::Union(
+                    1,
+                    Box::new(AvroValue::Record(vec![("event_id".to_string(), AvroValue::Long(event_id))])),
+                ),
+                None => AvroValue::Union(0, Box::new(AvroValue::Null)),
+            };
+            AvroValue::Record(vec![("meta".to_string(), meta), ("seq".to_string(), AvroValue::Int(seq as i32))])
+        });
+        let bytes = container(
+            r#"{"type": "record", "name": "Event", "fields": [
+                {"name": "meta", "type": ["null", {"type": "record", "name": "Meta", "fields": [
+                    {"name": "event_id", "type": "long"}
+                ]}]},
+                {"name": "seq", "type": "int"}
+            ]}"#,
+            records,
+        );
+
+        let convert_seqs = |drop_missing: bool| {
+            let options = ConvertOptions {
+                as_array: true,
+                dedup: Some(Dedup {
+                    pointer: "/meta/event_id".to_string(),
+                    drop_missing,
+                }),
+                select: Some(parse_field_paths("seq")),
+                ..Default::default()
+            };
+            let mut output = Vec::new();
+            convert_bytes(&bytes[..], &mut output, &options).unwrap();
+            serde_json::from_slice::<Value>(&output).unwrap()
+        };
+
+        assert_eq!(convert_seqs(false), serde_json::json!([{"seq": 0}, {"seq": 1}, {"seq": 3}, {"seq": 4}]));
+        assert_eq!(convert_seqs(true), serde_json::json!([{"seq": 0}, {"seq": 1}]));
+    }
+
+    #[test]
+    fn test_profile_written_records() {
+        let profile_path = temp_path("profile.json");
+        let options = ConvertOptions {
+            filter: Some("/active==true".parse().unwrap()),
+            profile: Some(ProfileOptions {
+                deep: false,
+                output: Some(profile_path.to_string_lossy().to_string()),
+            }),
+            ..Default::default()
+        };
+        convert_users(&options);
+
+        let profile: Value = serde_json::from_slice(&std::fs::read(&profile_path).unwrap()).unwrap();
+        std::fs::remove_file(&profile_path).unwrap();
+        assert_eq!(profile["records"], Value::from(2));
+        assert_eq!(profile["fields"]["email"], serde_json::json!({"string": 1, "null": 1}));
+    }
+
+    #[test]
+    fn test_stats_leave_output_alone() {
+        let options = ConvertOptions {
+            stats: true,
+            ..Default::default()
+        };
+        assert_eq!(convert_users(&options), convert_users(&ConvertOptions::default()));
+    }
+
+    #[test]
+    fn test_tail_records() {
+        let ids = |options: &ConvertOptions| {
+            let json: Value = serde_json::from_slice(&convert_users(options)).unwrap();
+            json.as_array().unwrap().iter().map(|record| record["id"].clone()).collect::<Vec<_>>()
+        };
+        let options = ConvertOptions {
+            as_array: true,
+            tail: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(ids(&options), vec![Value::from(2), Value::from(3)]);
+
+        let options = ConvertOptions {
+            tail: Some(5),
+            skip: Some(1),
+            ..options
+        };
+        assert_eq!(ids(&options), vec![Value::from(2), Value::from(3)]);
+
+        let options = ConvertOptions {
+            tail: Some(0),
+            ..options
+        };
+        assert_eq!(convert_users(&options), b"[]\n");
+
+        // Newline-delimited output keeps the original order too
+        let options = ConvertOptions {
+            tail: Some(2),
+            ..Default::default()
+        };
+        let output = String::from_utf8(convert_users(&options)).unwrap();
+        let names: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["name"].clone())
+            .collect();
+        assert_eq!(names, vec![Value::from("Bob Smith"), Value::from("Charlie Brown")]);
+    }
+
+    #[test]
+    fn test_sample_records() {
+        let ids = |options: &ConvertOptions| {
+            let json: Value = serde_json::from_slice(&convert_users(options)).unwrap();
+            json.as_array().unwrap().iter().map(|record| record["id"].as_i64().unwrap()).collect::<Vec<_>>()
+        };
+        let options = ConvertOptions {
+            as_array: true,
+            sample: Some(Sample { size: 2, seed: Some(7) }),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let sampled = ids(&options);
+        assert_eq!(sampled.len(), 2);
+        assert!(sampled.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", sampled);
+        // The same seed picks the same records
+        for _ in 0..5 {
+            assert_eq!(ids(&options), sampled);
+        }
+
+        let options = ConvertOptions {
+            sample: Some(Sample { size: 10, seed: None }),
+            ..options
+        };
+        assert_eq!(ids(&options), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_limit_records() {
+        let options = ConvertOptions {
+            as_array: true,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let json: Value = serde_json::from_slice(&convert_users(&options)).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+
+        let options = ConvertOptions {
+            as_array: true,
+            limit: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(convert_users(&options), b"[]\n");
+
+        let options = ConvertOptions {
+            limit: Some(0),
+            ..Default::default()
+        };
+        assert!(convert_users(&options).is_empty());
+    }
+
+    #[test]
+    fn test_skip_records() {
+        let options = ConvertOptions {
+            as_array: true,
+            skip: Some(1),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let json: Value = serde_json::from_slice(&convert_users(&options)).unwrap();
+        let records = json.as_array().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["name"], Value::String("Bob Smith".to_string()));
+
+        let options = ConvertOptions {
+            skip: Some(10),
+            ..Default::default()
+        };
+        assert!(convert_users(&options).is_empty());
+    }
+
+    #[test]
+    fn test_key_style_leaves_map_keys_alone() {
+        let record = AvroValue::Record(vec![
+            ("user_id".to_string(), AvroValue::Int(1)),
+            (
+                "page_views".to_string(),
+                AvroValue::Map(HashMap::from([("home_page".to_string(), AvroValue::Int(3))])),
+            ),
+        ]);
+        let mut options = JsonOptions {
+            key_style: KeyStyle::Camel,
+            ..Default::default()
+        };
+        assert_eq!(
+            avro_value_to_json_with_options(&record, &options).unwrap(),
+            serde_json::json!({"userId": 1, "pageViews": {"home_page": 3}})
+        );
+
+        options.rename_map_keys = true;
+        assert_eq!(
+            avro_value_to_json_with_options(&record, &options).unwrap(),
+            serde_json::json!({"userId": 1, "pageViews": {"homePage": 3}})
+        );
+    }
+
+    #[test]
+    fn test_avro_json_embeds_in_serde_values() {
+        let record = AvroValue::Record(vec![
+            ("id".to_string(), AvroValue::Long(7)),
+            ("day".to_string(), AvroValue::Date(0)),
+        ]);
+        let payload = AvroJson::try_from(&record).unwrap();
+        assert_eq!(
+            serde_json::json!({"payload": payload, "source": "kafka"}),
+            serde_json::json!({"payload": {"id": 7, "day": "1970-01-01"}, "source": "kafka"})
+        );
+    }
+
+    #[test]
+    fn test_avro_value_to_json_with_schema() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Order", "fields": [
+                {"name": "placed_on", "type": {"type": "int", "logicalType": "date"}},
+                {"name": "placed_at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+                {"name": "total", "type": {"type": "bytes", "logicalType": "decimal", "precision": 6, "scale": 2}}
+            ]}"#,
+        )
+        .unwrap();
+        // Built from plain primitives, as a hand-assembled value would be
+        let record = AvroValue::Record(vec![
+            ("placed_on".to_string(), AvroValue::Int(-1)),
+            ("placed_at".to_string(), AvroValue::Long(0)),
+            ("total".to_string(), AvroValue::Bytes(vec![0x04, 0xD2])),
+        ]);
+
+        assert_eq!(
+            avro_value_to_json_with_schema(&record, &schema).unwrap(),
+            serde_json::json!({"placed_on": "1969-12-31", "placed_at": "1970-01-01T00:00:00Z", "total": "12.34"})
+        );
+        // Without the schema there is nothing to say these aren't plain numbers and bytes
+        assert_eq!(avro_value_to_json(&record).unwrap()["placed_on"], Value::from(-1));
+    }
+
+    #[test]
+    fn test_enum_named_mode() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Paint", "fields": [
+                {"name": "color", "type": {"type": "enum", "name": "Color", "namespace": "com.example",
+                    "symbols": ["RED", "GREEN"]}}
+            ]}"#,
+        )
+        .unwrap();
+        let record = AvroValue::Record(vec![("color".to_string(), AvroValue::Enum(0, "RED".to_string()))]);
+
+        let json_value = value_to_json(&record, Some(&schema), &Names::new(), &JsonOptions::default()).unwrap();
+        assert_eq!(json_value["color"], Value::String("RED".to_string()));
+
+        let options = JsonOptions {
+            enum_mode: EnumMode::Named,
+            ..Default::default()
+        };
+        let json_value = value_to_json(&record, Some(&schema), &Names::new(), &options).unwrap();
+        assert_eq!(json_value["color"], serde_json::json!({"enum": "com.example.Color", "symbol": "RED"}));
+    }
+
+    #[test]
+    fn test_enum_index_mode() {
+        let green = AvroValue::Enum(2, "GREEN".to_string());
+        assert_eq!(avro_value_to_json(&green).unwrap(), Value::from("GREEN"));
+
+        let options = JsonOptions {
+            enum_mode: EnumMode::Index,
+            ..Default::default()
+        };
+        assert_eq!(avro_value_to_json_with_options(&green, &options).unwrap(), Value::from(2));
+    }
+
+    #[test]
+    fn test_union_tagged_mode() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "User", "namespace": "com.example", "fields": [
+                {"name": "email", "type": ["null", "string"]},
+                {"name": "address", "type": ["null", {"type": "record", "name": "Address", "fields": [
+                    {"name": "city", "type": "string"}
+                ]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let record = AvroValue::Record(vec![
+            ("email".to_string(), AvroValue::Union(0, Box::new(AvroValue::Null))),
+            (
+                "address".to_string(),
+                AvroValue::Union(
+                    1,
+                    Box::new(AvroValue::Record(vec![(
+                        "city".to_string(),
+                        AvroValue::String("Paris".to_string()),
+                    )])),
+                ),
+            ),
+        ]);
+        let options = JsonOptions {
+            union_mode: UnionMode::Tagged,
+            ..Default::default()
+        };
+
+        let json_value = value_to_json(&record, Some(&schema), &Names::new(), &options).unwrap();
+        assert_eq!(json_value["email"], Value::Null);
+        assert_eq!(json_value["address"]["com.example.Address"]["city"], Value::String("Paris".to_string()));
+
+        // Without a schema the branch is named after the value's type
+        let json_value = avro_value_to_json_with_options(
+            &AvroValue::Union(1, Box::new(AvroValue::Long(5))),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(json_value, serde_json::json!({"long": 5}));
+    }
+
+    #[test]
+    fn test_union_tagged_mode_multiple_branches() {
+        let payloads = [
+            AvroValue::Union(0, Box::new(AvroValue::Int(5))),
+            AvroValue::Union(1, Box::new(AvroValue::String("5".to_string()))),
+            AvroValue::Union(2, Box::new(AvroValue::Record(vec![("id".to_string(), AvroValue::Int(5))]))),
+        ];
+        let bytes = container(
+            r#"{"type": "record", "name": "Event", "fields": [
+                {"name": "payload", "type": ["int", "string", {"type": "record", "name": "MyRecord", "fields": [
+                    {"name": "id", "type": "int"}
+                ]}]}
+            ]}"#,
+            payloads.map(|payload| AvroValue::Record(vec![("payload".to_string(), payload)])),
+        );
+
+        let options = ConvertOptions {
+            as_array: true,
+            json: JsonOptions {
+                union_mode: UnionMode::Tagged,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        convert_bytes(&bytes[..], &mut output, &options).unwrap();
+        let json: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"payload": {"int": 5}},
+                {"payload": {"string": "5"}},
+                {"payload": {"MyRecord": {"id": 5}}},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tag_maps() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Inventory", "fields": [
+                {"name": "stores", "type": {"type": "map", "values": {"type": "record", "name": "Store", "fields": [
+                    {"name": "stock", "type": {"type": "map", "values": "int"}}
+                ]}}}
+            ]}"#,
+        )
+        .unwrap();
+        let store = AvroValue::Record(vec![(
+            "stock".to_string(),
+            AvroValue::Map(HashMap::from([("apples".to_string(), AvroValue::Int(3))])),
+        )]);
+        let record = AvroValue::Record(vec![(
+            "stores".to_string(),
+            AvroValue::Map(HashMap::from([("paris".to_string(), store)])),
+        )]);
+
+        let options = JsonOptions {
+            tag_maps: true,
+            ..Default::default()
+        };
+        let json_value = avro_value_to_json_with_schema_and_options(&record, &schema, &options).unwrap();
+        assert_eq!(
+            json_value,
+            serde_json::json!({"stores": {"$map": {"paris": {"stock": {"$map": {"apples": 3}}}}}})
+        );
+
+        let json_value = avro_value_to_json_with_schema(&record, &schema).unwrap();
+        assert_eq!(json_value, serde_json::json!({"stores": {"paris": {"stock": {"apples": 3}}}}));
+    }
+
+    #[test]
+    fn test_output_is_reproducible() {
+        let attributes = (0..32).map(|i| (format!("key{}", i), AvroValue::Int(i))).collect();
+        let record = AvroValue::Record(vec![
+            ("zone".to_string(), AvroValue::String("eu".to_string())),
+            ("attributes".to_string(), AvroValue::Map(attributes)),
+            ("at".to_string(), AvroValue::Long(1)),
+        ]);
+        let bytes = container(
+            r#"{"type": "record", "name": "Event", "fields": [
+                {"name": "zone", "type": "string"},
+                {"name": "attributes", "type": {"type": "map", "values": "int"}},
+                {"name": "at", "type": "long"}
+            ]}"#,
+            [record],
+        );
+
+        // Each conversion decodes into fresh, differently seeded hash maps
+        let convert = || {
+            let mut output = Vec::new();
+            convert_bytes(&bytes[..], &mut output, &ConvertOptions::default()).unwrap();
+            output
+        };
+        let first = convert();
+        assert_eq!(first, convert());
+
+        let json: Value = serde_json::from_slice(&first).unwrap();
+        let fields: Vec<&String> = json.as_object().unwrap().keys().collect();
+        assert_eq!(fields, ["zone", "attributes", "at"]);
+        let keys: Vec<&String> = json["attributes"].as_object().unwrap().keys().collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let nested = |depth: usize| {
+            let mut value = AvroValue::Int(1);
+            for _ in 0..depth {
+                value = AvroValue::Array(vec![value]);
             }
-            _ => panic!("Expected array"),
-        }
+            value
+        };
+
+        assert!(avro_value_to_json(&nested(DEFAULT_MAX_DEPTH)).is_ok());
+        let err = avro_value_to_json(&nested(DEFAULT_MAX_DEPTH + 1)).unwrap_err();
+        assert!(matches!(err, ConvertError::MaxDepthExceeded(DEFAULT_MAX_DEPTH)));
+
+        // Far past the limit still fails cleanly instead of overflowing the stack
+        assert!(avro_value_to_json(&nested(10_000)).is_err());
+        let avro_json = JsonOptions {
+            avro_json: true,
+            ..Default::default()
+        };
+        assert!(avro_value_to_json_with_options(&nested(DEFAULT_MAX_DEPTH), &avro_json).is_ok());
+        assert!(avro_value_to_json_with_options(&nested(10_000), &avro_json).is_err());
+
+        let options = JsonOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let record = AvroValue::Record(vec![("tags".to_string(), nested(1))]);
+        assert!(avro_value_to_json_with_options(&record, &options).is_ok());
+        let record = AvroValue::Record(vec![("tags".to_string(), nested(2))]);
+        assert_eq!(
+            avro_value_to_json_with_options(&record, &options).unwrap_err().to_string(),
+            "Value is nested more than 2 levels deep"
+        );
+        let options = JsonOptions {
+            avro_json: true,
+            ..options
+        };
+        assert!(avro_value_to_json_with_options(&record, &options).is_err());
     }
 
     #[test]
-    fn test_avro_record_to_json() {
-        let mut fields = Vec::new();
-        fields.push(("name".to_string(), AvroValue::String("John".to_string())));
-        fields.push(("age".to_string(), AvroValue::Int(30)));
-        
-        let avro_value = AvroValue::Record(fields);
-        let json_value = avro_value_to_json(&avro_value).unwrap();
-        
-        match json_value {
-            Value::Object(obj) => {
-                assert_eq!(obj.len(), 2);
-                assert_eq!(obj["name"], Value::String("John".to_string()));
-                assert_eq!(obj["age"], Value::Number(30.into()));
+    fn test_iterative_matches_recursive() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Order", "fields": [
+                {"name": "order_id", "type": "long"},
+                {"name": "total", "type": {"type": "bytes", "logicalType": "decimal", "precision": 9, "scale": 2}},
+                {"name": "raw_amount", "type": "bytes"},
+                {"name": "note", "type": ["null", "string"]},
+                {"name": "line_items", "type": {"type": "array", "items": {"type": "record", "name": "Item", "fields": [
+                    {"name": "sku", "type": "string"},
+                    {"name": "qty", "type": ["null", "int", "string"]}
+                ]}}},
+                {"name": "item_groups", "type": {"type": "map", "values": {"type": "array", "items": "Item"}}},
+                {"name": "parent", "type": ["null", "Order"]}
+            ]}"#,
+        )
+        .unwrap();
+        let item = |sku: &str, qty: AvroValue| {
+            let qty = match qty {
+                AvroValue::Null => AvroValue::Union(0, Box::new(AvroValue::Null)),
+                AvroValue::Int(_) => AvroValue::Union(1, Box::new(qty)),
+                _ => AvroValue::Union(2, Box::new(qty)),
+            };
+            AvroValue::Record(vec![("sku".to_string(), AvroValue::String(sku.to_string())), ("qty".to_string(), qty)])
+        };
+        let mut order = AvroValue::Union(0, Box::new(AvroValue::Null));
+        for order_id in 0..4 {
+            let record = AvroValue::Record(vec![
+                ("order_id".to_string(), AvroValue::Long(order_id)),
+                ("total".to_string(), AvroValue::Bytes(12345u32.to_be_bytes().to_vec())),
+                ("raw_amount".to_string(), AvroValue::Bytes(vec![0xff, 0x9c])),
+                ("note".to_string(), AvroValue::Union(1, Box::new(AvroValue::String(format!("order {}", order_id))))),
+                (
+                    "line_items".to_string(),
+                    AvroValue::Array(vec![
+                        item("a-1", AvroValue::Int(2)),
+                        item("b-2", AvroValue::String("many".to_string())),
+                        item("c-3", AvroValue::Null),
+                    ]),
+                ),
+                (
+                    "item_groups".to_string(),
+                    AvroValue::Map(HashMap::from([
+                        ("group_b".to_string(), AvroValue::Array(vec![item("d-4", AvroValue::Int(1))])),
+                        ("group_a".to_string(), AvroValue::Array(Vec::new())),
+                    ])),
+                ),
+                ("parent".to_string(), order),
+            ]);
+            order = AvroValue::Union(1, Box::new(record));
+        }
+        let AvroValue::Union(_, order) = order else { unreachable!() };
+
+        let all_options = [
+            JsonOptions::default(),
+            JsonOptions {
+                union_mode: UnionMode::Tagged,
+                tag_maps: true,
+                rename_map_keys: true,
+                key_style: KeyStyle::Camel,
+                decimal_fields: BTreeMap::from([("raw_amount".to_string(), 1)]),
+                ..Default::default()
+            },
+            JsonOptions {
+                max_depth: Some(5),
+                ..Default::default()
+            },
+        ];
+        for options in &all_options {
+            for schema in [Some(&schema), None] {
+                let iterative = match schema {
+                    Some(schema) => avro_value_to_json_with_schema_and_options(&order, schema, options),
+                    None => avro_value_to_json_with_options(&order, options),
+                };
+                let recursive = avro_value_to_json_recursive(&order, schema, options);
+                match (iterative, recursive) {
+                    (Ok(iterative), Ok(recursive)) => {
+                        // Compared as text, so key order has to match too
+                        assert_eq!(iterative.to_string(), recursive.to_string(), "{:?}", options);
+                    }
+                    (Err(iterative), Err(recursive)) => assert_eq!(iterative.to_string(), recursive.to_string()),
+                    (iterative, recursive) => panic!("{:?} != {:?}", iterative, recursive),
+                }
             }
-            _ => panic!("Expected object"),
         }
     }
 
     #[test]
-    fn test_base64_encode() {
-        let input = b"hello";
-        let encoded = base64_encode(input);
-        assert_eq!(encoded, "aGVsbG8=");
-        
-        let input2 = b"hello world";
-        let encoded2 = base64_encode(input2);
-        assert_eq!(encoded2, "aGVsbG8gd29ybGQ=");
+    fn test_convert_datum() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Order", "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "placed_at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+                {"name": "total", "type": {"type": "bytes", "logicalType": "decimal", "precision": 8, "scale": 2}},
+                {"name": "note", "type": ["null", "string"]}
+            ]}"#,
+        )
+        .unwrap();
+        let record = AvroValue::Record(vec![
+            ("id".to_string(), AvroValue::Long(7)),
+            ("placed_at".to_string(), AvroValue::TimestampMillis(1_700_000_000_000)),
+            ("total".to_string(), AvroValue::Decimal(apache_avro::Decimal::from(12345u32.to_be_bytes()))),
+            ("note".to_string(), AvroValue::Union(0, Box::new(AvroValue::Null))),
+        ]);
+        let bytes = apache_avro::to_avro_datum(&schema, record).unwrap();
+
+        assert_eq!(
+            convert_datum(&schema, &bytes).unwrap(),
+            serde_json::json!({
+                "id": 7,
+                "placed_at": "2023-11-14T22:13:20Z",
+                "total": "123.45",
+                "note": null,
+            })
+        );
+
+        let options = JsonOptions {
+            legacy_logical_types: true,
+            ..Default::default()
+        };
+        let json_value = convert_datum_with_options(&schema, &bytes, &options).unwrap();
+        assert_eq!(json_value["placed_at"], Value::from("timestamp-millis:1700000000000"));
+
+        assert!(matches!(convert_datum(&schema, &bytes[..3]), Err(ConvertError::AvroRead(_))));
     }
 
     #[test]
-    fn test_avro_bytes_to_json() {
-        let bytes = vec![0x48, 0x65, 0x6c, 0x6c, 0x6f]; // "Hello" in bytes
-        let avro_value = AvroValue::Bytes(bytes);
-        let json_value = avro_value_to_json(&avro_value).unwrap();
-        assert_eq!(json_value, Value::String("SGVsbG8=".to_string()));
+    fn test_avro_json_encoding() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Event", "namespace": "com.example", "fields": [
+                {"name": "payload", "type": "bytes"},
+                {"name": "source", "type": ["null", "string", {"type": "fixed", "name": "Id", "size": 2}]},
+                {"name": "missing", "type": ["null", "long"]},
+                {"name": "day", "type": {"type": "int", "logicalType": "date"}}
+            ]}"#,
+        )
+        .unwrap();
+        let record = AvroValue::Record(vec![
+            ("payload".to_string(), AvroValue::Bytes(vec![0x00, 0x41, 0xff])),
+            ("source".to_string(), AvroValue::Union(2, Box::new(AvroValue::Fixed(2, vec![0xc3, 0xa9])))),
+            ("missing".to_string(), AvroValue::Union(0, Box::new(AvroValue::Null))),
+            ("day".to_string(), AvroValue::Date(19_000)),
+        ]);
+
+        let json_value = avro_value_to_avro_json(&record, &schema).unwrap();
+        assert_eq!(
+            json_value,
+            serde_json::json!({
+                "payload": "\u{0}A\u{ff}",
+                "source": {"com.example.Id": "\u{c3}\u{a9}"},
+                "missing": null,
+                "day": 19_000,
+            })
+        );
+
+        let record = AvroValue::Record(vec![
+            ("payload".to_string(), AvroValue::Bytes(Vec::new())),
+            ("source".to_string(), AvroValue::Union(1, Box::new(AvroValue::String("web".to_string())))),
+            ("missing".to_string(), AvroValue::Union(1, Box::new(AvroValue::Long(7)))),
+            ("day".to_string(), AvroValue::Int(0)),
+        ]);
+        let json_value = avro_value_to_avro_json(&record, &schema).unwrap();
+        assert_eq!(json_value["source"], serde_json::json!({"string": "web"}));
+        assert_eq!(json_value["missing"], serde_json::json!({"long": 7}));
+        assert_eq!(json_value["day"], serde_json::json!(0));
+    }
+
+    #[test]
+    fn test_errors_are_distinguishable() {
+        let err = convert_avro_to_json("does-not-exist.avro", None, false, false).unwrap_err();
+        match err {
+            ConvertError::Io(io_err) => assert_eq!(io_err.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("Expected an I/O error, got {:?}", other),
+        }
+
+        let mut output = Vec::new();
+        let err = convert_bytes(&b"not an avro file"[..], &mut output, &ConvertOptions::default()).unwrap_err();
+        assert!(matches!(err, ConvertError::AvroRead(_)));
+    }
+
+    #[test]
+    fn test_avro_json_records_iterator() {
+        let bytes = std::fs::read("test_users.avro").unwrap();
+        let names: Vec<Value> = avro_json_records(&bytes[..])
+            .unwrap()
+            .map(|record| record.unwrap()["name"].clone())
+            .collect();
+        assert_eq!(names, vec!["Alice Johnson", "Bob Smith", "Charlie Brown"]);
+
+        // A truncated data block surfaces as an error item rather than a failed call
+        let truncated = &bytes[..bytes.len() - 20];
+        let records: Vec<Result<Value>> = avro_json_records(truncated).unwrap().collect();
+        assert!(records.last().unwrap().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "color")]
+    fn test_colorize_json_escaped_quotes() {
+        let colored = colorize_json(r#"{"a":"he said \"hi\"","b":"\u00e9\\","c":1}"#);
+
+        // Escapes are colored as whole sequences
+        assert!(colored.contains(&Paint::Escape.paint(r#"\""#)));
+        assert!(colored.contains(&Paint::Escape.paint(r"\u00e9")));
+        assert!(colored.contains(&Paint::Escape.paint(r"\\")));
+
+        // Structure after the strings is still punctuation, not string content
+        assert!(colored.ends_with(
+            "\x1b[37m,\x1b[0m\x1b[1;34m\"c\"\x1b[0m\x1b[36m:\x1b[0m\x1b[33m1\x1b[0m\x1b[34m}\x1b[0m"
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "color")]
+    fn test_colorize_pretty_json_keys() {
+        let record = serde_json::json!({"outer": {"inner": "value", "n": -1.5}, "tags": ["key"]});
+        let pretty = to_string_indented(&record, Indent::default()).unwrap();
+        let colored = colorize_json(&pretty);
+
+        let key = |name: &str| Paint::Key.paint(&format!("\"{}\"", name));
+        let colon = Paint::Colon.paint(":");
+        assert!(colored.contains(&format!("{}{} {}", key("outer"), colon, Paint::Bracket.paint("{"))));
+        assert!(colored.contains(&format!("{}{} {}", key("inner"), colon, Paint::String.paint("\"value\""))));
+        assert!(colored.contains(&format!("{}{} {}", key("n"), colon, Paint::Number.paint("-1.5"))));
+        // A string in an array is a value, even though a key comes after it
+        assert!(colored.contains(&Paint::String.paint("\"key\"")));
+        // Indentation is left uncolored
+        assert!(colored.contains(&format!("{}\n    {}", Paint::Bracket.paint("{"), key("inner"))));
+    }
+
+    #[test]
+    #[cfg(feature = "color")]
+    fn test_color_choice() {
+        let options = ConvertOptions {
+            color: ColorChoice::Always,
+            ..Default::default()
+        };
+        assert!(String::from_utf8(convert_users(&options)).unwrap().contains('\x1b'));
+
+        let options = ConvertOptions {
+            color: ColorChoice::Never,
+            ..Default::default()
+        };
+        assert!(!String::from_utf8(convert_users(&options)).unwrap().contains('\x1b'));
+    }
+
+    #[test]
+    fn test_auto_color_never_writes_escapes_to_files() {
+        let output_path = temp_path("colored.json");
+        let output = output_path.to_string_lossy().to_string();
+        let options = ConvertOptions {
+            color: ColorChoice::Auto,
+            ..Default::default()
+        };
+        convert_avro_to_json_with_options(&Input::from("test_users.avro"), Some(&output), &options).unwrap();
+
+        let written = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert!(!written.contains('\x1b'));
+        assert!(!should_colorize(ColorChoice::Auto, false));
+    }
+
+    #[test]
+    fn test_jsonl_forces_one_record_per_line() {
+        let options = ConvertOptions {
+            pretty: true,
+            jsonl: true,
+            ..Default::default()
+        };
+        let output = String::from_utf8(convert_users(&options)).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        for line in lines {
+            serde_json::from_str::<Value>(line).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_select_records_by_index() {
+        let bytes = event_container(0..10);
+
+        let options = ConvertOptions {
+            records: Some(BTreeSet::from([7, 1, 4, 25])),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        convert_bytes(&bytes[..], &mut output, &options).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"id\":1}\n{\"id\":4}\n{\"id\":7}\n");
+
+        // Indexes count records before --skip drops them
+        let options = ConvertOptions {
+            skip: Some(3),
+            ..options
+        };
+        let mut output = Vec::new();
+        convert_bytes(&bytes[..], &mut output, &options).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"id\":4}\n{\"id\":7}\n");
+    }
+
+    #[test]
+    fn test_builder_options() {
+        let options = ConvertOptions::new()
+            .as_array(true)
+            .color(ColorChoice::Never)
+            .skip(1)
+            .limit(1)
+            .select(parse_field_paths("name"));
+        assert!(options.as_array);
+        assert_eq!((options.skip, options.limit), (Some(1), Some(1)));
+
+        let output_path = temp_path("builder.json");
+        let output = output_path.to_string_lossy().to_string();
+        convert("test_users.avro", Some(&output), &options).unwrap();
+        let written = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(written, "[{\"name\":\"Bob Smith\"}]\n");
+    }
+
+    #[test]
+    fn test_extract_field() {
+        let mut options = ConvertOptions {
+            extract: Some(Extract {
+                pointer: "/email".to_string(),
+                skip_missing: false,
+            }),
+            as_array: true,
+            ..Default::default()
+        };
+        assert_eq!(convert_users(&options), b"alice@example.com\nbob@example.com\nnull\n");
+
+        options.extract = Some(Extract {
+            pointer: "/phone".to_string(),
+            skip_missing: false,
+        });
+        assert_eq!(convert_users(&options), b"\n\n\n");
+        options.extract = Some(Extract {
+            pointer: "/phone".to_string(),
+            skip_missing: true,
+        });
+        assert_eq!(convert_users(&options), b"");
+    }
+
+    #[test]
+    fn test_no_final_newline() {
+        let options = ConvertOptions {
+            as_array: true,
+            no_final_newline: true,
+            select: Some(vec![vec!["id".to_string()]]),
+            ..Default::default()
+        };
+        assert_eq!(convert_users(&options), b"[{\"id\":1},{\"id\":2},{\"id\":3}]");
+        let options = ConvertOptions {
+            as_array: false,
+            ..options
+        };
+        assert_eq!(convert_users(&options), b"{\"id\":1}\n{\"id\":2}\n{\"id\":3}");
+    }
+
+    #[test]
+    fn test_convert_many_concatenates_inputs() {
+        let inputs = vec!["test_users.avro".to_string(), "test_products.avro".to_string()];
+        let output_path = temp_path("many.json");
+        let output = output_path.to_string_lossy().to_string();
+
+        let options = ConvertOptions {
+            as_array: true,
+            ..Default::default()
+        };
+        convert_many(&inputs, Some(&output), &options).unwrap();
+        let json: Value = serde_json::from_str(&std::fs::read_to_string(&output_path).unwrap()).unwrap();
+        let records = json.as_array().unwrap();
+        assert_eq!(records.len(), 6);
+        assert_eq!(records[0]["name"], Value::String("Alice Johnson".to_string()));
+        assert_eq!(records[3]["name"], Value::String("Laptop".to_string()));
+
+        let options = ConvertOptions {
+            skip: Some(2),
+            limit: Some(2),
+            ..Default::default()
+        };
+        convert_many(&inputs, Some(&output), &options).unwrap();
+        let written = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        let names: Vec<Value> = written
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["name"].clone())
+            .collect();
+        assert_eq!(names, vec!["Charlie Brown", "Laptop"]);
+    }
+
+    #[test]
+    fn test_buffer_size_leaves_output_alone() {
+        let output_path = temp_path("buffer-size.json");
+        let output = output_path.to_string_lossy().to_string();
+        let mut written = Vec::new();
+        for buffer_size in [None, Some(1), Some(1 << 20)] {
+            let options = ConvertOptions {
+                buffer_size,
+                ..Default::default()
+            };
+            convert_avro_to_json_with_options(&Input::from("test_users.avro"), Some(&output), &options).unwrap();
+            written.push(std::fs::read_to_string(&output_path).unwrap());
+        }
+        std::fs::remove_file(&output_path).unwrap();
+        assert_eq!(written[0].lines().count(), 3);
+        assert!(written.iter().all(|output| *output == written[0]));
+    }
+
+    #[test]
+    fn test_convert_bytes_to_json_string_matches_file_output() {
+        let bytes = std::fs::read("test_users.avro").unwrap();
+        let output_path = temp_path("bytes.json");
+        let output = output_path.to_string_lossy().to_string();
+        for (pretty, as_array) in [(false, false), (true, true)] {
+            convert_avro_to_json("test_users.avro", Some(&output), pretty, as_array).unwrap();
+            let expected = std::fs::read_to_string(&output_path).unwrap();
+            assert_eq!(convert_bytes_to_json_string(&bytes, pretty, as_array).unwrap(), expected);
+        }
+        std::fs::remove_file(&output_path).unwrap();
+
+        assert!(matches!(convert_bytes_to_json_string(b"not avro", false, false), Err(ConvertError::AvroRead(_))));
     }
 }
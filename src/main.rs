@@ -1,8 +1,96 @@
-use avro_to_json::convert_avro_to_json_with_color;
-use anyhow::{Context, Result};
+use avro_to_json::{
+    avro_info, check_date_format, check_timestamp_format, convert_json_to_avro, convert_many, count_records,
+    parse_field_paths, print_avro_schema, read_metadata, read_schema_file, schema_fingerprint, validate_avro,
+    Base64Mode, BlockRange, BytesEncoding, ColorChoice, ConvertError, ConvertOptions, Dedup, DurationFormat, Envelope,
+    EnumMode, Extract, Filter, FingerprintAlgorithm, FlattenOptions, FloatPolicy, Indent, JsonOptions, KeyBy, KeyStyle,
+    OnError, OutputFormat, PartitionBy, ProfileOptions, Sample, UnionMode,
+};
+use anyhow::{bail, Context, Result};
+use clap::parser::ValueSource;
 use clap::{Arg, Command};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        // A truncated input is still reported, but as a warning, since the records before the cut
+        // were read fine
+        Err(err) => match err.chain().find_map(|cause| cause.downcast_ref::<ConvertError>()) {
+            Some(truncated @ ConvertError::Truncated { .. }) => {
+                eprintln!("Warning: {}", truncated);
+                ExitCode::from(exit_code(&err))
+            }
+            _ => {
+                eprintln!("Error: {:?}", err);
+                ExitCode::from(exit_code(&err))
+            }
+        },
+    }
+}
+
+/// Picks the process exit code for a failed run from the innermost [`ConvertError`] in the
+/// chain: 2 for I/O, 3 for input that can't be decoded (bad Avro, or JSON that doesn't fit its
+/// schema), 4 for serialization errors, 5 for a truncated input, and 1 for anything else.
+/// Failing to write the serialized output is an I/O error, so 2.
+fn exit_code(err: &anyhow::Error) -> u8 {
+    fn code(err: &ConvertError) -> u8 {
+        match err {
+            ConvertError::Io(_) => 2,
+            #[cfg(feature = "http")]
+            ConvertError::Http { .. } => 2,
+            ConvertError::AvroRead(_)
+            | ConvertError::UnsupportedCodec { .. }
+            | ConvertError::InvalidRecord { .. }
+            | ConvertError::InvalidFrame { .. }
+            | ConvertError::ReplacementCharacter { .. }
+            | ConvertError::InvalidJson(_)
+            | ConvertError::JsonMismatch(_) => 3,
+            ConvertError::Json(_)
+            | ConvertError::Csv(_)
+            | ConvertError::NonFiniteFloat(_)
+            | ConvertError::AvroWrite(_) => 4,
+            #[cfg(feature = "yaml")]
+            ConvertError::Yaml(_) => 4,
+            #[cfg(feature = "toml")]
+            ConvertError::Toml(_) => 4,
+            #[cfg(feature = "msgpack")]
+            ConvertError::MessagePack(_) => 4,
+            ConvertError::Truncated { .. } => 5,
+            ConvertError::Record { source, .. } => code(source),
+            ConvertError::InvalidBase64(_)
+            | ConvertError::UnsupportedLogicalType(_)
+            | ConvertError::InvalidFormat(_)
+            | ConvertError::MaxDepthExceeded(_)
+            | ConvertError::MissingKey { .. }
+            | ConvertError::DuplicateKey(_)
+            | ConvertError::TooManyRecordsInMemory(_)
+            | ConvertError::TooManyPartitions(_)
+            | ConvertError::InvalidOptions(_) => 1,
+        }
+    }
+    err.chain().find_map(|cause| cause.downcast_ref::<ConvertError>()).map_or(1, code)
+}
+
+/// Sends the library's log events at `level` and above to stderr, so they never mix with
+/// output written to stdout.
+fn init_logging(level: &str) {
+    use std::io::IsTerminal;
+    let level = match level {
+        "debug" => tracing::Level::DEBUG,
+        "info" => tracing::Level::INFO,
+        "warn" => tracing::Level::WARN,
+        _ => tracing::Level::ERROR,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .init();
+}
+
+fn run() -> Result<()> {
     let matches = Command::new("avro-to-json")
         .version("0.1.0")
         .author("Your Name")
@@ -12,8 +100,16 @@ fn main() -> Result<()> {
                 .short('i')
                 .long("input")
                 .value_name("FILE")
-                .help("Input Avro file")
-                .required(true),
+                .help("Input Avro file (- for stdin, or an http(s) URL with the `http` feature); repeat to concatenate files")
+                .required_unless_present("stdin")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("stdin")
+                .long("stdin")
+                .help("Read the Avro input from stdin")
+                .conflicts_with("input")
+                .action(clap::ArgAction::SetTrue),
         )
         .arg(
             Arg::new("output")
@@ -22,6 +118,14 @@ fn main() -> Result<()> {
                 .value_name("FILE")
                 .help("Output JSON file (optional, defaults to stdout)"),
         )
+        .arg(
+            Arg::new("format")
+                .short('f')
+                .long("format")
+                .value_name("FORMAT")
+                .help("Output format: json (default), csv for flat records (see --flatten), yaml, toml or msgpack")
+                .value_parser(["json", "csv", "yaml", "toml", "msgpack"]),
+        )
         .arg(
             Arg::new("pretty")
                 .short('p')
@@ -29,6 +133,14 @@ fn main() -> Result<()> {
                 .help("Pretty print JSON output")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("indent")
+                .long("indent")
+                .value_name("N|tab")
+                .help("Indentation for --pretty: a number of spaces (default 2) or tab")
+                .value_parser(|s: &str| s.parse::<Indent>())
+                .requires("pretty"),
+        )
         .arg(
             Arg::new("array")
                 .short('a')
@@ -36,25 +148,1009 @@ fn main() -> Result<()> {
                 .help("Output as JSON array instead of newline-delimited JSON")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("jsonl")
+                .long("jsonl")
+                .help("Strict JSON Lines: one compact record per line, even with --pretty")
+                .conflicts_with("array")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-newline")
+                .long("no-newline")
+                .help("Leave out the newline at the very end of the JSON output, e.g. after an array's closing ]")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("color")
                 .short('c')
                 .long("color")
-                .help("Colorize JSON output")
+                .value_name("WHEN")
+                .help("Colorize JSON output: auto (default when given without a value), always, or never")
+                .value_parser(["auto", "always", "never"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("auto")
+                .default_value("never")
+                .hide(cfg!(not(feature = "color"))),
+        )
+        .arg(
+            Arg::new("pretty-color")
+                .long("pretty-color")
+                .help("Shorthand for --pretty --color")
+                .conflicts_with_all(["color", "jsonl"])
+                .action(clap::ArgAction::SetTrue)
+                .hide(cfg!(not(feature = "color"))),
+        )
+        .arg(
+            Arg::new("limit")
+                .short('n')
+                .long("limit")
+                .value_name("N")
+                .help("Only convert the first N records")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .help("Print the record count and how often each logical type appeared to stderr")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("timing")
+                .long("timing")
+                .help("Print the elapsed time, records read and throughput to stderr when done")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("FILE")
+                .help("Report the JSON types seen for each field to stderr, or to FILE with --profile=FILE")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value(""),
+        )
+        .arg(
+            Arg::new("profile-deep")
+                .long("profile-deep")
+                .help("Profile nested fields too, not only top-level ones")
+                .requires("profile")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("split")
+                .long("split")
+                .value_name("N")
+                .help("Write the output as numbered files of N records each (out.json becomes out.000.json, ...)")
+                .requires("output")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("partition-by")
+                .long("partition-by")
+                .value_name("POINTER")
+                .help("Write a file per value at this JSON Pointer (out.json becomes out_region=US.json, ...)")
+                .requires("output")
+                .conflicts_with_all(["split", "key-by", "envelope"])
+                .value_parser(parse_pointer),
+        )
+        .arg(
+            Arg::new("max-partitions")
+                .long("max-partitions")
+                .value_name("N")
+                .help("Fail once --partition-by finds more than N distinct values [default: 256]")
+                .requires("partition-by")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("block-range")
+                .long("block-range")
+                .value_name("START:END")
+                .help("Only convert the blocks starting between these byte offsets, aligned to sync markers")
+                .conflicts_with_all(["single-object", "framed"])
+                .value_parser(|s: &str| s.parse::<BlockRange>()),
+        )
+        .arg(
+            Arg::new("tail")
+                .long("tail")
+                .value_name("N")
+                .help("Only write the last N records (the whole input is still read)")
+                .conflicts_with("limit")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("sample")
+                .long("sample")
+                .value_name("N")
+                .help("Only write a uniform random sample of N records, in input order (the whole input is read)")
+                .conflicts_with_all(["limit", "tail"])
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Seed for --sample, to pick the same records on every run")
+                .requires("sample")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("max-records-in-memory")
+                .long("max-records-in-memory")
+                .value_name("N")
+                .help("Fail rather than hold over N records in memory for unstreamed output like CSV (0: no limit)")
+                .default_value("1000000")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("skip")
+                .long("skip")
+                .value_name("N")
+                .help("Skip the first N records")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("records")
+                .long("records")
+                .value_name("INDEXES")
+                .help("Only convert the records at these zero-based indexes, e.g. 0,5,99")
+                .value_parser(parse_record_indexes),
+        )
+        .arg(
+            Arg::new("select")
+                .long("select")
+                .value_name("PATHS")
+                .help("Comma-separated dotted field paths to keep (e.g. name,address.city,tags.0)"),
+        )
+        .arg(
+            Arg::new("on-error")
+                .long("on-error")
+                .value_name("ACTION")
+                .help("What to do with a record that fails to decode or convert: abort, or skip it and log the error")
+                .value_parser(["abort", "skip"])
+                .default_value("abort"),
+        )
+        .arg(
+            Arg::new("filter")
+                .long("filter")
+                .value_name("CONDITION")
+                .help("Only write records where a JSON Pointer matches a value, e.g. '/status==ACTIVE' or '/age!=0'")
+                .value_parser(|s: &str| s.parse::<Filter>()),
+        )
+        .arg(
+            Arg::new("dedup-by")
+                .long("dedup-by")
+                .value_name("POINTER")
+                .help("Only write the first record for each distinct value at this JSON Pointer, e.g. /event_id")
+                .value_parser(parse_pointer),
+        )
+        .arg(
+            Arg::new("dedup-drop-missing")
+                .long("dedup-drop-missing")
+                .help("With --dedup-by, drop records that have no value at the pointer instead of keeping them")
+                .requires("dedup-by")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("extract")
+                .long("extract")
+                .value_name("POINTER")
+                .help("Print only the value at this JSON Pointer from each record, strings unquoted, e.g. /user/email")
+                .conflicts_with_all(["array", "key-by", "envelope", "partition-by", "format", "pretty"])
+                .value_parser(parse_pointer),
+        )
+        .arg(
+            Arg::new("extract-missing")
+                .long("extract-missing")
+                .value_name("MODE")
+                .help("With --extract, what to print for records without the value: empty (a blank line) or skip")
+                .requires("extract")
+                .value_parser(["empty", "skip"])
+                .default_value("empty"),
+        )
+        .arg(
+            Arg::new("key-by")
+                .long("key-by")
+                .value_name("POINTER")
+                .help("Write one JSON object mapping each record's value at this JSON Pointer to the record, e.g. /id")
+                .conflicts_with_all(["array", "jsonl"])
+                .value_parser(parse_pointer),
+        )
+        .arg(
+            Arg::new("key-by-last-wins")
+                .long("key-by-last-wins")
+                .help("With --key-by, let a later record replace an earlier one with the same key instead of failing")
+                .requires("key-by")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("envelope")
+                .long("envelope")
+                .help("Write one JSON object holding the writer schema, the record count and the records")
+                .conflicts_with_all(["array", "jsonl", "key-by"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("envelope-keys")
+                .long("envelope-keys")
+                .value_name("SCHEMA,COUNT,RECORDS")
+                .help("Names of the --envelope fields [default: schema,count,records]")
+                .requires("envelope")
+                .value_parser(parse_envelope_keys),
+        )
+        .arg(
+            Arg::new("flatten")
+                .long("flatten")
+                .help("Flatten nested objects into dotted keys ({\"a\":{\"b\":1}} becomes {\"a.b\":1})")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("flatten-separator")
+                .long("flatten-separator")
+                .value_name("SEP")
+                .help("Separator placed between flattened keys")
+                .requires("flatten")
+                .default_value("."),
+        )
+        .arg(
+            Arg::new("flatten-arrays")
+                .long("flatten-arrays")
+                .help("Also flatten arrays, keyed by element index (tags.0)")
+                .requires("flatten")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("with-metadata")
+                .long("with-metadata")
+                .help("Wrap each record as {\"_file\": ..., \"_index\": ..., \"_record\": ...}")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("metadata-prefix")
+                .long("metadata-prefix")
+                .value_name("PREFIX")
+                .help("Prefix for the --with-metadata keys")
+                .requires("with-metadata")
+                .default_value("_"),
+        )
+        .arg(
+            Arg::new("omit-nulls")
+                .long("omit-nulls")
+                .help("Drop object fields whose value is null, at every level")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("omit-null-items")
+                .long("omit-null-items")
+                .help("With --omit-nulls, drop null array items too")
+                .requires("omit-nulls")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("null-as")
+                .long("null-as")
+                .value_name("STRING")
+                .help("Write nulls as this string, e.g. '\\N' for PostgreSQL COPY or '' for empty")
+                .conflicts_with("omit-nulls"),
+        )
+        .arg(
+            Arg::new("sort-keys")
+                .long("sort-keys")
+                .help("Sort object keys alphabetically instead of following schema order")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("schema")
+                .short('s')
+                .long("schema")
+                .help("Print the embedded writer schema as JSON and exit")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fingerprint")
+                .long("fingerprint")
+                .value_name("ALGORITHM")
+                .help("Print the fingerprint of the writer schema's parsing canonical form in hex and exit")
+                .value_parser(["rabin", "sha256"])
+                .conflicts_with("schema"),
+        )
+        .arg(
+            Arg::new("metadata")
+                .long("metadata")
+                .help("Print the user metadata from the container header as JSON and exit")
+                .conflicts_with_all(["schema", "fingerprint"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("count")
+                .long("count")
+                .help("Print the number of records and exit")
+                .conflicts_with_all(["schema", "fingerprint", "metadata"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("info")
+                .long("info")
+                .help("Print the schema type, field names, codec and record count, then exit (as JSON with --format json)")
+                .conflicts_with_all(["schema", "fingerprint", "metadata", "count"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("validate")
+                .long("validate")
+                .help("Check every record against the writer schema instead of converting")
+                .conflicts_with_all(["schema", "fingerprint", "metadata", "count"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("reader-schema")
+                .long("reader-schema")
+                .value_name("FILE")
+                .help("Resolve records against this Avro schema (.avsc) instead of the writer schema")
+                .conflicts_with_all(["single-object", "framed"]),
+        )
+        .arg(
+            Arg::new("single-object")
+                .long("single-object")
+                .help("Read a stream of single-object encoded messages instead of a container file")
+                .requires("schema-file")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("framed")
+                .long("framed")
+                .help("Read binary datums each prefixed by a 4-byte big-endian length instead of a container file")
+                .requires("schema-file")
+                .conflicts_with("single-object")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("schema-file")
+                .long("schema-file")
+                .value_name("FILE")
+                .help("Avro schema (.avsc) that single-object or framed messages were written with, or to write JSON with"),
+        )
+        .arg(
+            Arg::new("input-format")
+                .long("input-format")
+                .value_name("FORMAT")
+                .help("Format of the input: avro, or json to write JSON records to an Avro container instead")
+                .value_parser(["avro", "json"])
+                .default_value("avro"),
+        )
+        .arg(
+            Arg::new("flush-every")
+                .long("flush-every")
+                .value_name("N")
+                .help("Flush the output after every N records (newline-delimited output only)")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .help("Show a running record count on stderr (only when stderr is a terminal)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Suppress progress and status messages on stderr; errors are still reported")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("log-level")
+                .long("log-level")
+                .value_name("LEVEL")
+                .help("Log what the conversion is doing to stderr at LEVEL and above")
+                .value_parser(["error", "warn", "info", "debug"])
+                .default_value("error"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .value_name("N")
+                .help("Convert records on N threads, keeping output order")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("input-gzip")
+                .long("input-gzip")
+                .help("Decompress gzip input (implied for .gz files)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("output-gzip")
+                .long("output-gzip")
+                .help("Compress the output with gzip")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("buffer-size")
+                .long("buffer-size")
+                .value_name("BYTES")
+                .help("Read input and write output through buffers of BYTES bytes [default: 65536]")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        )
+        .arg(
+            Arg::new("legacy-logical-types")
+                .long("legacy-logical-types")
+                .help("Render logical types as legacy tagged strings (e.g. timestamp-millis:<n>)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bytes-encoding")
+                .long("bytes-encoding")
+                .value_name("ENCODING")
+                .help("Text encoding for bytes and fixed values")
+                .value_parser(["base64", "hex"])
+                .default_value("base64"),
+        )
+        .arg(
+            Arg::new("hex-prefix")
+                .long("hex-prefix")
+                .help("Prefix hex-encoded bytes with 0x (with --bytes-encoding hex)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bytes-as-text")
+                .long("bytes-as-text")
+                .help("Render bytes values holding valid UTF-8 as plain strings")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fixed-as-text")
+                .long("fixed-as-text")
+                .help("Render fixed values holding valid UTF-8 as plain strings")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bytes-verbose")
+                .long("bytes-verbose")
+                .help("Render bytes and fixed values as objects holding the encoded value and its length")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("base64")
+                .long("base64")
+                .value_name("MODE")
+                .help("Base64 variant used for bytes and fixed values")
+                .value_parser(["standard", "url-safe", "url-safe-no-pad"])
+                .default_value("standard"),
+        )
+        .arg(
+            Arg::new("union-mode")
+                .long("union-mode")
+                .value_name("MODE")
+                .help("How union values are rendered: unwrap the branch, or tag it with its type name")
+                .value_parser(["unwrap", "tagged"])
+                .default_value("unwrap"),
+        )
+        .arg(
+            Arg::new("local-tz")
+                .long("local-tz")
+                .value_name("ZONE")
+                .help("IANA time zone (e.g. America/New_York) that local-timestamp values are in")
+                .value_parser(|s: &str| s.parse::<chrono_tz::Tz>()),
+        )
+        .arg(
+            Arg::new("format-date")
+                .long("format-date")
+                .value_name("FORMAT")
+                .help("chrono format string for date values, e.g. '%Y/%m/%d' (default ISO-8601)")
+                .value_parser(|s: &str| check_date_format(s).map(|()| s.to_string())),
+        )
+        .arg(
+            Arg::new("format-timestamp")
+                .long("format-timestamp")
+                .value_name("FORMAT")
+                .help("chrono format string for timestamp values in UTC, e.g. '%+' (default RFC 3339)")
+                .value_parser(|s: &str| check_timestamp_format(s).map(|()| s.to_string())),
+        )
+        .arg(
+            Arg::new("duration-format")
+                .long("duration-format")
+                .value_name("FORMAT")
+                .help("How duration values are rendered: a {months, days, millis} object, or an ISO-8601 string")
+                .value_parser(["object", "iso8601"])
+                .default_value("object"),
+        )
+        .arg(
+            Arg::new("enum-mode")
+                .long("enum-mode")
+                .value_name("MODE")
+                .help("How enum values are rendered: the symbol, {\"enum\": name, \"symbol\": symbol}, or its index")
+                .value_parser(["symbol", "named", "index"])
+                .default_value("symbol"),
+        )
+        .arg(
+            Arg::new("enum-as-index")
+                .long("enum-as-index")
+                .help("Render enum values as their position in the symbol list (same as --enum-mode index)")
+                .conflicts_with("enum-mode")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("rename-keys")
+                .long("rename-keys")
+                .value_name("STYLE")
+                .help("Rewrite record field names: camel (userId), snake (user_id), or none")
+                .value_parser(["camel", "snake", "none"])
+                .default_value("none"),
+        )
+        .arg(
+            Arg::new("rename-map-keys")
+                .long("rename-map-keys")
+                .help("Also rewrite the keys of Avro maps with --rename-keys")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("large-longs-as-string")
+                .long("large-longs-as-string")
+                .help("Emit longs beyond JavaScript's safe integer range (±2^53 - 1) as strings")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-depth")
+                .long("max-depth")
+                .value_name("N")
+                .help("Fail on values whose arrays, maps and records nest more than N deep [default: 256]")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("tag-maps")
+                .long("tag-maps")
+                .help("Wrap Avro maps as {\"$map\": {...}} so they can be told apart from records")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("avro-json")
+                .long("avro-json")
+                .help("Emit the canonical Avro JSON encoding (tagged unions, bytes as Latin-1 strings, raw logical types)")
+                .conflicts_with_all([
+                    "legacy-logical-types",
+                    "bytes-encoding",
+                    "base64",
+                    "hex-prefix",
+                    "bytes-as-text",
+                    "fixed-as-text",
+                    "bytes-verbose",
+                    "union-mode",
+                    "duration-format",
+                    "enum-mode",
+                    "enum-as-index",
+                    "large-longs-as-string",
+                    "local-tz",
+                    "format-date",
+                    "format-timestamp",
+                    "round-floats",
+                    "decimal-field",
+                    "rename-keys",
+                    "tag-maps",
+                ])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("float-policy")
+                .long("float-policy")
+                .value_name("POLICY")
+                .help("How NaN and infinite floats are rendered: null, string (\"NaN\", \"Infinity\"), or error")
+                .value_parser(["null", "string", "error"])
+                .default_value("null"),
+        )
+        .arg(
+            Arg::new("round-floats")
+                .long("round-floats")
+                .value_name("N")
+                .help("Round float and double values to N decimal places")
+                .value_parser(clap::value_parser!(u8)),
+        )
+        .arg(
+            Arg::new("decimal-field")
+                .long("decimal-field")
+                .value_name("NAME:SCALE")
+                .help("Render the top-level bytes field NAME as a decimal with SCALE fractional digits; repeatable")
+                .value_parser(parse_decimal_field)
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("strict-utf8")
+                .long("strict-utf8")
+                .help("Fail on strings holding U+FFFD replacement characters, a sign of invalid UTF-8")
                 .action(clap::ArgAction::SetTrue),
         )
         .get_matches();
 
-    let input_file = matches.get_one::<String>("input").unwrap();
+    // Skipped records are reported as warnings, so they are shown unless asked not to be
+    let skips_shown = matches.get_one::<String>("on-error").is_some_and(|action| action == "skip")
+        && !matches.get_flag("quiet");
+    let log_level = match matches.value_source("log-level") {
+        Some(ValueSource::DefaultValue) if skips_shown => "warn",
+        _ => matches.get_one::<String>("log-level").map_or("error", String::as_str),
+    };
+    init_logging(log_level);
+
+    let input_files: Vec<String> = match matches.get_many::<String>("input") {
+        Some(input_files) if !matches.get_flag("stdin") => expand_inputs(input_files)?,
+        _ => vec!["-".to_string()],
+    };
+
+    // JSON input reverses the conversion, so none of the JSON output options apply
+    if matches.get_one::<String>("input-format").is_some_and(|format| format == "json") {
+        let Some(schema_file) = matches.get_one::<String>("schema-file") else {
+            bail!("--input-format json needs --schema-file to write the records with");
+        };
+        let [input_file] = &input_files[..] else {
+            bail!("--input-format json reads a single input");
+        };
+        convert_json_to_avro(input_file, schema_file, matches.get_one::<String>("output"))
+            .context("Failed to convert JSON to Avro")?;
+        if !matches.get_flag("quiet") {
+            eprintln!("Conversion completed successfully!");
+        }
+        return Ok(());
+    }
+
+    // Printing the schema replaces conversion entirely
+    if matches.get_flag("schema") {
+        let [input_file] = &input_files[..] else {
+            bail!("--schema reads a single input");
+        };
+        let schema = print_avro_schema(input_file).context("Failed to read Avro schema")?;
+        println!("{}", schema);
+        return Ok(());
+    }
+
+    if let Some(algorithm) = matches.get_one::<String>("fingerprint") {
+        let algorithm = match algorithm.as_str() {
+            "sha256" => FingerprintAlgorithm::Sha256,
+            _ => FingerprintAlgorithm::Rabin,
+        };
+        let [input_file] = &input_files[..] else {
+            bail!("--fingerprint reads a single input");
+        };
+        let fingerprint = schema_fingerprint(input_file, algorithm).context("Failed to read Avro schema")?;
+        println!("{}", fingerprint);
+        return Ok(());
+    }
+
+    if matches.get_flag("metadata") {
+        let [input_file] = &input_files[..] else {
+            bail!("--metadata reads a single input");
+        };
+        let metadata = read_metadata(input_file).context("Failed to read Avro header")?;
+        println!("{}", serde_json::to_string_pretty(&metadata)?);
+        return Ok(());
+    }
+
+    // Counting decodes records but never converts them
+    if matches.get_flag("count") {
+        let mut count = 0;
+        for input_file in &input_files {
+            count += count_records(input_file).with_context(|| format!("Failed to count records in {}", input_file))?;
+        }
+        println!("{}", count);
+        return Ok(());
+    }
+
+    if matches.get_flag("info") {
+        let as_json = matches.get_one::<String>("format").is_some_and(|format| format == "json");
+        for (index, input_file) in input_files.iter().enumerate() {
+            let info = avro_info(input_file).with_context(|| format!("Failed to read {}", input_file))?;
+            if as_json {
+                println!("{}", info.to_json());
+            } else {
+                if index > 0 {
+                    println!();
+                }
+                if input_files.len() > 1 {
+                    println!("File:    {}", input_file);
+                }
+                println!("{}", info);
+            }
+        }
+        return Ok(());
+    }
+
+    if matches.get_flag("validate") {
+        let mut count = 0;
+        for input_file in &input_files {
+            count += validate_avro(input_file).with_context(|| format!("Validation failed for {}", input_file))?;
+        }
+        println!("All {} records are valid", count);
+        return Ok(());
+    }
+
+    #[cfg(not(feature = "color"))]
+    if matches.value_source("color") == Some(clap::parser::ValueSource::CommandLine)
+        || matches.get_flag("pretty-color")
+    {
+        bail!("--color is not available: avro-to-json was built without the `color` feature");
+    }
+    #[cfg(not(feature = "yaml"))]
+    if matches.get_one::<String>("format").is_some_and(|format| format == "yaml") {
+        bail!("--format yaml is not available: avro-to-json was built without the `yaml` feature");
+    }
+    #[cfg(not(feature = "toml"))]
+    if matches.get_one::<String>("format").is_some_and(|format| format == "toml") {
+        bail!("--format toml is not available: avro-to-json was built without the `toml` feature");
+    }
+    #[cfg(not(feature = "msgpack"))]
+    if matches.get_one::<String>("format").is_some_and(|format| format == "msgpack") {
+        bail!("--format msgpack is not available: avro-to-json was built without the `msgpack` feature");
+    }
+    if matches.contains_id("key-by") && matches.get_one::<String>("format").is_some_and(|format| format != "json") {
+        bail!("--key-by only works with JSON output");
+    }
+    if matches.get_flag("envelope") && matches.get_one::<String>("format").is_some_and(|format| format != "json") {
+        bail!("--envelope only works with JSON output");
+    }
+    let format_is_json = matches.get_one::<String>("format").is_none_or(|format| format == "json");
+    if matches.contains_id("partition-by") && !format_is_json {
+        bail!("--partition-by only works with JSON output");
+    }
+
     let output_file = matches.get_one::<String>("output");
-    let pretty = matches.get_flag("pretty");
-    let as_array = matches.get_flag("array");
-    let color = matches.get_flag("color");
+    let reader_schema = match matches.get_one::<String>("reader-schema") {
+        Some(path) => Some(read_schema_file(path).context("Failed to read reader schema")?),
+        None => None,
+    };
+    let message_schema = match matches.get_one::<String>("schema-file") {
+        Some(path) if matches.get_flag("single-object") || matches.get_flag("framed") => {
+            Some(read_schema_file(path).context("Failed to read schema file")?)
+        }
+        _ => None,
+    };
+    let (single_object_schema, framed_schema) = match matches.get_flag("framed") {
+        true => (None, message_schema),
+        false => (message_schema, None),
+    };
+    let base64_mode = match matches.get_one::<String>("base64").map(String::as_str) {
+        Some("url-safe") => Base64Mode::UrlSafe,
+        Some("url-safe-no-pad") => Base64Mode::UrlSafeNoPad,
+        _ => Base64Mode::Standard,
+    };
+    let options = ConvertOptions {
+        format: match matches.get_one::<String>("format").map(String::as_str) {
+            Some("csv") => OutputFormat::Csv,
+            #[cfg(feature = "yaml")]
+            Some("yaml") => OutputFormat::Yaml,
+            #[cfg(feature = "toml")]
+            Some("toml") => OutputFormat::Toml,
+            #[cfg(feature = "msgpack")]
+            Some("msgpack") => OutputFormat::MessagePack,
+            _ => OutputFormat::Json,
+        },
+        pretty: matches.get_flag("pretty") || matches.get_flag("pretty-color"),
+        indent: matches.get_one::<Indent>("indent").copied().unwrap_or_default(),
+        as_array: matches.get_flag("array"),
+        jsonl: matches.get_flag("jsonl"),
+        no_final_newline: matches.get_flag("no-newline"),
+        color: match matches.get_one::<String>("color").map(String::as_str) {
+            _ if matches.get_flag("pretty-color") => ColorChoice::Auto,
+            Some("auto") => ColorChoice::Auto,
+            Some("always") => ColorChoice::Always,
+            _ => ColorChoice::Never,
+        },
+        skip: matches.get_one::<usize>("skip").copied(),
+        records: matches.get_one::<BTreeSet<usize>>("records").cloned(),
+        block_range: matches.get_one::<BlockRange>("block-range").copied(),
+        tail: matches.get_one::<usize>("tail").copied(),
+        sample: matches.get_one::<usize>("sample").map(|&size| Sample {
+            size,
+            seed: matches.get_one::<u64>("seed").copied(),
+        }),
+        max_records_in_memory: matches.get_one::<usize>("max-records-in-memory").copied().filter(|&max| max > 0),
+        profile: matches.contains_id("profile").then(|| ProfileOptions {
+            deep: matches.get_flag("profile-deep"),
+            output: matches.get_one::<String>("profile").filter(|path| !path.is_empty()).cloned(),
+        }),
+        stats: matches.get_flag("stats"),
+        timing: matches.get_flag("timing"),
+        split: matches.get_one::<usize>("split").copied(),
+        partition_by: matches.get_one::<String>("partition-by").map(|pointer| PartitionBy {
+            pointer: pointer.clone(),
+            max_partitions: matches.get_one::<usize>("max-partitions").copied(),
+        }),
+        limit: matches.get_one::<usize>("limit").copied(),
+        on_error: match matches.get_one::<String>("on-error").map(String::as_str) {
+            Some("skip") => OnError::Skip,
+            _ => OnError::Abort,
+        },
+        filter: matches.get_one::<Filter>("filter").cloned(),
+        key_by: matches.get_one::<String>("key-by").map(|pointer| KeyBy {
+            pointer: pointer.clone(),
+            last_wins: matches.get_flag("key-by-last-wins"),
+        }),
+        envelope: matches
+            .get_flag("envelope")
+            .then(|| matches.get_one::<Envelope>("envelope-keys").cloned().unwrap_or_default()),
+        dedup: matches.get_one::<String>("dedup-by").map(|pointer| Dedup {
+            pointer: pointer.clone(),
+            drop_missing: matches.get_flag("dedup-drop-missing"),
+        }),
+        extract: matches.get_one::<String>("extract").map(|pointer| Extract {
+            pointer: pointer.clone(),
+            skip_missing: matches.get_one::<String>("extract-missing").is_some_and(|mode| mode == "skip"),
+        }),
+        select: matches.get_one::<String>("select").map(|paths| parse_field_paths(paths)),
+        flatten: matches.get_flag("flatten").then(|| FlattenOptions {
+            separator: matches.get_one::<String>("flatten-separator").cloned().unwrap_or_default(),
+            index_arrays: matches.get_flag("flatten-arrays"),
+        }),
+        metadata_prefix: matches
+            .get_flag("with-metadata")
+            .then(|| matches.get_one::<String>("metadata-prefix").cloned().unwrap_or_default()),
+        omit_nulls: matches.get_flag("omit-nulls"),
+        omit_null_items: matches.get_flag("omit-null-items"),
+        null_as: matches.get_one::<String>("null-as").cloned(),
+        sort_keys: matches.get_flag("sort-keys"),
+        input_gzip: matches.get_flag("input-gzip"),
+        output_gzip: matches.get_flag("output-gzip"),
+        buffer_size: matches.get_one::<usize>("buffer-size").copied(),
+        flush_every: matches.get_one::<usize>("flush-every").copied(),
+        progress: matches.get_flag("progress") && !matches.get_flag("quiet"),
+        jobs: matches.get_one::<usize>("jobs").copied(),
+        reader_schema,
+        single_object_schema,
+        framed_schema,
+        json: JsonOptions {
+            legacy_logical_types: matches.get_flag("legacy-logical-types"),
+            bytes_encoding: match matches.get_one::<String>("bytes-encoding").map(String::as_str) {
+                Some("hex") => BytesEncoding::Hex,
+                _ => BytesEncoding::Base64,
+            },
+            base64_mode,
+            hex_prefix: matches.get_flag("hex-prefix"),
+            bytes_as_text: matches.get_flag("bytes-as-text"),
+            fixed_as_text: matches.get_flag("fixed-as-text"),
+            bytes_verbose: matches.get_flag("bytes-verbose"),
+            union_mode: match matches.get_one::<String>("union-mode").map(String::as_str) {
+                Some("tagged") => UnionMode::Tagged,
+                _ => UnionMode::Unwrap,
+            },
+            duration_format: match matches.get_one::<String>("duration-format").map(String::as_str) {
+                Some("iso8601") => DurationFormat::Iso8601,
+                _ => DurationFormat::Object,
+            },
+            enum_mode: match matches.get_one::<String>("enum-mode").map(String::as_str) {
+                _ if matches.get_flag("enum-as-index") => EnumMode::Index,
+                Some("named") => EnumMode::Named,
+                Some("index") => EnumMode::Index,
+                _ => EnumMode::Symbol,
+            },
+            large_longs_as_string: matches.get_flag("large-longs-as-string"),
+            float_policy: match matches.get_one::<String>("float-policy").map(String::as_str) {
+                Some("string") => FloatPolicy::String,
+                Some("error") => FloatPolicy::Error,
+                _ => FloatPolicy::Null,
+            },
+            strict_utf8: matches.get_flag("strict-utf8"),
+            round_floats: matches.get_one::<u8>("round-floats").copied(),
+            decimal_fields: matches
+                .get_many::<(String, usize)>("decimal-field")
+                .map(|fields| fields.cloned().collect())
+                .unwrap_or_default(),
+            date_format: matches.get_one::<String>("format-date").cloned(),
+            timestamp_format: matches.get_one::<String>("format-timestamp").cloned(),
+            local_timezone: matches.get_one::<chrono_tz::Tz>("local-tz").copied(),
+            key_style: match matches.get_one::<String>("rename-keys").map(String::as_str) {
+                Some("camel") => KeyStyle::Camel,
+                Some("snake") => KeyStyle::Snake,
+                _ => KeyStyle::None,
+            },
+            rename_map_keys: matches.get_flag("rename-map-keys"),
+            max_depth: matches.get_one::<usize>("max-depth").copied(),
+            tag_maps: matches.get_flag("tag-maps"),
+            avro_json: matches.get_flag("avro-json"),
+        },
+    };
 
-    convert_avro_to_json_with_color(input_file, output_file, pretty, as_array, color)
+    convert_many(&input_files, output_file, &options)
         .context("Failed to convert Avro to JSON")?;
 
-    println!("Conversion completed successfully!");
+    // Stdout may be carrying the JSON itself
+    if !matches.get_flag("quiet") {
+        eprintln!("Conversion completed successfully!");
+    }
     Ok(())
 }
 
+/// Parses a comma-separated list of record indexes, in any order.
+fn parse_record_indexes(s: &str) -> Result<BTreeSet<usize>, String> {
+    s.split(',')
+        .map(|index| index.trim().parse().map_err(|_| format!("expected a record index, got `{}`", index.trim())))
+        .collect()
+}
+
+/// Parses a `--decimal-field` argument of the form `name:scale`.
+fn parse_decimal_field(s: &str) -> Result<(String, usize), String> {
+    let (name, scale) = s.rsplit_once(':').ok_or_else(|| format!("expected NAME:SCALE, got `{}`", s))?;
+    if name.is_empty() {
+        return Err(format!("expected a field name before `:`, got `{}`", s));
+    }
+    let scale = scale.parse().map_err(|_| format!("expected a scale after `:`, got `{}`", scale))?;
+    Ok((name.to_string(), scale))
+}
+
+/// Parses the three comma-separated field names of `--envelope-keys`.
+fn parse_envelope_keys(s: &str) -> Result<Envelope, String> {
+    let keys: Vec<&str> = s.split(',').map(str::trim).collect();
+    match keys[..] {
+        [schema, count, records] if !schema.is_empty() && !count.is_empty() && !records.is_empty() => {
+            if schema == count || schema == records || count == records {
+                return Err(format!("expected three different field names, got `{}`", s));
+            }
+            Ok(Envelope {
+                schema_key: schema.to_string(),
+                count_key: count.to_string(),
+                records_key: records.to_string(),
+            })
+        }
+        _ => Err(format!("expected SCHEMA,COUNT,RECORDS field names, got `{}`", s)),
+    }
+}
+
+/// Parses a JSON Pointer argument, which is empty or starts with `/`.
+fn parse_pointer(s: &str) -> Result<String, String> {
+    if s.is_empty() || s.starts_with('/') {
+        Ok(s.to_string())
+    } else {
+        Err(format!("expected a JSON Pointer starting with `/`, got `{}`", s))
+    }
+}
+
+/// Expands glob patterns and directories among the `--input` values into the files they
+/// match, each expansion sorted so the output order is deterministic. Plain paths, URLs and
+/// `-` are passed through untouched.
+fn expand_inputs<'a>(inputs: impl IntoIterator<Item = &'a String>) -> Result<Vec<String>> {
+    let mut expanded = Vec::new();
+    for input in inputs {
+        if input.starts_with("http://") || input.starts_with("https://") {
+            if cfg!(not(feature = "http")) {
+                bail!("URL inputs are not available: avro-to-json was built without the `http` feature");
+            }
+            expanded.push(input.clone());
+        } else if Path::new(input).is_dir() {
+            let mut files = Vec::new();
+            collect_avro_files(Path::new(input), &mut files)
+                .with_context(|| format!("Failed to read input directory {}", input))?;
+            if files.is_empty() {
+                bail!("No .avro files found in directory {}", input);
+            }
+            files.sort();
+            expanded.extend(files);
+        } else if input.contains(['*', '?', '[']) {
+            let mut files = Vec::new();
+            for entry in glob::glob(input).with_context(|| format!("Invalid glob pattern {}", input))? {
+                let path = entry.with_context(|| format!("Failed to expand glob pattern {}", input))?;
+                files.push(path.to_string_lossy().into_owned());
+            }
+            if files.is_empty() {
+                bail!("No files match glob pattern {}", input);
+            }
+            files.sort();
+            expanded.extend(files);
+        } else {
+            expanded.push(input.clone());
+        }
+    }
+    Ok(expanded)
+}
+
+/// Recursively collects the `*.avro` files beneath `dir`. Symlinks to files are followed, but
+/// symlinks to directories are not, so a link back up the tree can't loop forever.
+fn collect_avro_files(dir: &Path, files: &mut Vec<String>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_avro_files(&path, files)?;
+        } else if !path.is_dir() && path.extension().is_some_and(|ext| ext == "avro") {
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,399 @@
+//! Output files that only appear once they are complete.
+
+use crate::error::{io_error, ConvertError, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A file written next to its target and renamed into place by [`commit`](Self::commit), so
+/// a watcher never sees a partial file and a failed run leaves any previous output intact.
+/// Dropping it uncommitted removes what was written.
+pub(crate) struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl AtomicFile {
+    /// Starts the file, writing to it through a buffer of `buffer_size` bytes.
+    pub(crate) fn create(path: &Path, buffer_size: usize) -> Result<Self> {
+        let temp_path = temp_output_path(path);
+        let file = File::create(&temp_path)
+            .map_err(|err| io_error(format!("Failed to create output file {}", temp_path.display()), err))?;
+        Ok(AtomicFile {
+            path: path.to_path_buf(),
+            temp_path,
+            writer: Some(BufWriter::with_capacity(buffer_size, file)),
+        })
+    }
+
+    pub(crate) fn commit(mut self) -> Result<()> {
+        let writer = self.writer.take().expect("writer is only taken by commit");
+        let file = writer.into_inner().map_err(|err| io_error("Failed to write output", err.into_error()))?;
+        file.sync_all().map_err(|err| io_error("Failed to write output", err))?;
+        std::fs::rename(&self.temp_path, &self.path)
+            .map_err(|err| io_error(format!("Failed to replace output file {}", self.path.display()), err))
+    }
+
+    fn writer(&mut self) -> &mut BufWriter<File> {
+        self.writer.as_mut().expect("writer is only taken by commit")
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// A hidden sibling of `path`, unique to this process, to write the output to before it is
+/// renamed over `path`. Being in the same directory keeps the rename on one filesystem.
+pub(crate) fn temp_output_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()))
+}
+
+/// Passes writes through, except that a newline at the very end of the output is held back
+/// until more follows, so it is never written if nothing does.
+pub(crate) struct WithoutFinalNewline<W> {
+    inner: W,
+    pending_newline: bool,
+}
+
+impl<W: Write> WithoutFinalNewline<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        WithoutFinalNewline {
+            inner,
+            pending_newline: false,
+        }
+    }
+}
+
+impl<W: Write> Write for WithoutFinalNewline<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some((&last, rest)) = buf.split_last() else {
+            return Ok(0);
+        };
+        if self.pending_newline {
+            self.inner.write_all(b"\n")?;
+            self.pending_newline = false;
+        }
+        // Everything is written in full, so the held back newline is always the last byte
+        self.inner.write_all(if last == b'\n' { rest } else { buf })?;
+        self.pending_newline = last == b'\n';
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Output split across numbered files, see [`chunk_path`]. Each chunk is an [`AtomicFile`],
+/// committed once the next one is started or the output is finished.
+pub(crate) struct SplitFiles {
+    path: PathBuf,
+    gzip: bool,
+    buffer_size: usize,
+    next_index: usize,
+    current: Option<Chunk>,
+}
+
+enum Chunk {
+    Plain(AtomicFile),
+    // The gzip trailer is only written by `finish`, so the encoder can't just be dropped
+    Gzip(GzEncoder<AtomicFile>),
+}
+
+impl Chunk {
+    fn create(path: &Path, gzip: bool, buffer_size: usize) -> Result<Self> {
+        let file = AtomicFile::create(path, buffer_size)?;
+        Ok(if gzip {
+            Chunk::Gzip(GzEncoder::new(file, Compression::default()))
+        } else {
+            Chunk::Plain(file)
+        })
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Chunk::Plain(file) => file,
+            Chunk::Gzip(encoder) => encoder,
+        }
+    }
+
+    fn commit(self) -> Result<()> {
+        match self {
+            Chunk::Plain(file) => file.commit(),
+            Chunk::Gzip(encoder) => encoder
+                .finish()
+                .map_err(|err| io_error("Failed to write JSON output", err))?
+                .commit(),
+        }
+    }
+}
+
+impl SplitFiles {
+    /// Starts the first chunk, so even an empty conversion produces one file.
+    pub(crate) fn create(path: &Path, gzip: bool, buffer_size: usize) -> Result<Self> {
+        let mut files = SplitFiles {
+            path: path.to_path_buf(),
+            gzip,
+            buffer_size,
+            next_index: 0,
+            current: None,
+        };
+        files.next_chunk()?;
+        Ok(files)
+    }
+
+    /// Commits the current chunk and starts the next one.
+    pub(crate) fn next_chunk(&mut self) -> Result<()> {
+        if let Some(chunk) = self.current.take() {
+            chunk.commit()?;
+        }
+        self.current = Some(Chunk::create(&chunk_path(&self.path, self.next_index), self.gzip, self.buffer_size)?);
+        self.next_index += 1;
+        Ok(())
+    }
+
+    pub(crate) fn current(&mut self) -> &mut dyn Write {
+        self.current.as_mut().expect("a chunk is open until finish").writer()
+    }
+
+    pub(crate) fn finish(mut self) -> Result<()> {
+        match self.current.take() {
+            Some(chunk) => chunk.commit(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Writes records to one file per distinct value at a JSON Pointer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartitionBy {
+    /// JSON Pointer (RFC 6901) to the value to partition by, e.g. `/region`.
+    pub pointer: String,
+    /// How many partitions may be open at once before the conversion fails with
+    /// [`ConvertError::TooManyPartitions`], since each holds a file open. Defaults to
+    /// [`DEFAULT_MAX_PARTITIONS`].
+    pub max_partitions: Option<usize>,
+}
+
+/// Partition limit used when [`PartitionBy::max_partitions`] isn't set, well under the usual
+/// limit of 1024 open files.
+pub const DEFAULT_MAX_PARTITIONS: usize = 256;
+
+/// Output partitioned by a value of each record, see [`partition_path`]. Each partition is an
+/// [`AtomicFile`] started by its first record and committed once the output is finished.
+pub(crate) struct PartitionFiles {
+    path: PathBuf,
+    gzip: bool,
+    buffer_size: usize,
+    partition_by: PartitionBy,
+    partitions: BTreeMap<String, Partition>,
+}
+
+pub(crate) struct Partition {
+    chunk: Chunk,
+    /// Records written to the partition so far.
+    records: usize,
+}
+
+impl Partition {
+    /// The partition's file along with its record count, to write a record and count it.
+    pub(crate) fn output(&mut self) -> (&mut dyn Write, &mut usize) {
+        (self.chunk.writer(), &mut self.records)
+    }
+}
+
+impl PartitionFiles {
+    pub(crate) fn new(path: &Path, partition_by: PartitionBy, gzip: bool, buffer_size: usize) -> Self {
+        PartitionFiles {
+            path: path.to_path_buf(),
+            gzip,
+            buffer_size,
+            partition_by,
+            partitions: BTreeMap::new(),
+        }
+    }
+
+    /// The partition `record` belongs in, starting its file if this is the first record with
+    /// its value.
+    pub(crate) fn partition(&mut self, record: &Value) -> Result<&mut Partition> {
+        let name = partition_name(record.pointer(&self.partition_by.pointer));
+        if !self.partitions.contains_key(&name) {
+            let max_partitions = self.partition_by.max_partitions.unwrap_or(DEFAULT_MAX_PARTITIONS);
+            if self.partitions.len() >= max_partitions {
+                return Err(ConvertError::TooManyPartitions(max_partitions));
+            }
+            let path = partition_file(&self.path, &self.partition_by.pointer, &name);
+            let chunk = Chunk::create(&path, self.gzip, self.buffer_size)?;
+            self.partitions.insert(name.clone(), Partition { chunk, records: 0 });
+        }
+        Ok(self.partitions.get_mut(&name).expect("the partition was just started"))
+    }
+
+    pub(crate) fn partitions_mut(&mut self) -> impl Iterator<Item = &mut Partition> {
+        self.partitions.values_mut()
+    }
+
+    pub(crate) fn finish(self) -> Result<()> {
+        self.partitions.into_values().try_for_each(|partition| partition.chunk.commit())
+    }
+}
+
+/// Names the file of a partition by inserting `_<field>=<value>` before the extensions of
+/// `path`, Hive style, where the field is the last segment of `pointer` and `value` is the
+/// record's value there, `None` when it has none: the `"US"` partition of `out.json` by
+/// `/region` is `out_region=US.json`.
+///
+/// A string is its own name. Anything else is named after its type, so it can't be mistaken
+/// for a string: `__missing__` when there is no value, `__null__` for null, and
+/// `__<type>__<json>` otherwise, e.g. `__number__1` for `1` where `"1"` is just `1`. A
+/// leading `_` of a string is percent-encoded to keep these names apart, as are characters
+/// other than ASCII letters, digits, `-`, `_` and `.`, so any value makes a safe file name
+/// and no two values share one.
+pub fn partition_path(path: &Path, pointer: &str, value: Option<&Value>) -> PathBuf {
+    partition_file(path, pointer, &partition_name(value))
+}
+
+fn partition_file(path: &Path, pointer: &str, name: &str) -> PathBuf {
+    let field = pointer.rsplit('/').next().unwrap_or_default().replace("~1", "/").replace("~0", "~");
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let (stem, extensions) = split_extensions(&file_name);
+    path.with_file_name(format!("{}_{}={}{}", stem, escape_file_name(&field), name, extensions))
+}
+
+/// The escaped name of the partition for `value`, see [`partition_path`].
+fn partition_name(value: Option<&Value>) -> String {
+    let (kind, value) = match value {
+        None => return "__missing__".to_string(),
+        Some(Value::Null) => return "__null__".to_string(),
+        Some(Value::String(value)) => {
+            return match value.strip_prefix('_') {
+                Some(rest) => format!("%5F{}", escape_file_name(rest)),
+                None => escape_file_name(value),
+            };
+        }
+        Some(value @ Value::Bool(_)) => ("boolean", value),
+        Some(value @ Value::Number(_)) => ("number", value),
+        Some(value @ Value::Array(_)) => ("array", value),
+        Some(value @ Value::Object(_)) => ("object", value),
+    };
+    format!("__{}__{}", kind, escape_file_name(&value.to_string()))
+}
+
+fn escape_file_name(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.') {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+/// Numbers a chunk of split output by inserting its zero-padded index before the extensions
+/// of `path`: chunk 1 of `out.json` is `out.001.json`, of `out.json.gz` is `out.001.json.gz`,
+/// and of `out` is `out.001`. Indexes have at least three digits and grow past 999.
+pub fn chunk_path(path: &Path, index: usize) -> PathBuf {
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let (stem, extensions) = split_extensions(&file_name);
+    path.with_file_name(format!("{}.{:03}{}", stem, index, extensions))
+}
+
+/// Splits a file name before its first extension, keeping the dot with the extensions.
+fn split_extensions(file_name: &str) -> (&str, &str) {
+    // A leading dot marks a hidden file rather than an extension
+    match file_name.char_indices().skip(1).find(|&(_, ch)| ch == '.') {
+        Some((position, _)) => file_name.split_at(position),
+        None => (file_name, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_path() {
+        assert_eq!(chunk_path(Path::new("out.json"), 0), PathBuf::from("out.000.json"));
+        assert_eq!(chunk_path(Path::new("dir/out.json.gz"), 12), PathBuf::from("dir/out.012.json.gz"));
+        assert_eq!(chunk_path(Path::new("out"), 1), PathBuf::from("out.001"));
+        assert_eq!(chunk_path(Path::new(".out.json"), 1), PathBuf::from(".out.001.json"));
+        assert_eq!(chunk_path(Path::new("out.csv"), 1234), PathBuf::from("out.1234.csv"));
+    }
+
+    #[test]
+    fn test_without_final_newline() {
+        let mut output = WithoutFinalNewline::new(Vec::new());
+        output.write_all(b"{\"a\":1}\n").unwrap();
+        output.write_all(b"\n").unwrap();
+        output.write_all(b"{\"a\":2}\n").unwrap();
+        assert_eq!(output.inner, b"{\"a\":1}\n\n{\"a\":2}");
+    }
+
+    #[test]
+    fn test_partition_path() {
+        let path = |pointer: &str, value: Value| partition_path(Path::new("out.json"), pointer, Some(&value));
+        assert_eq!(path("/region", Value::from("US")), PathBuf::from("out_region=US.json"));
+        assert_eq!(
+            partition_path(Path::new("dir/out.json.gz"), "/address/country", Some(&Value::from("New Zealand"))),
+            PathBuf::from("dir/out_country=New%20Zealand.json.gz")
+        );
+        assert_eq!(
+            partition_path(Path::new("out"), "/a~1b", Some(&Value::from("../x"))),
+            PathBuf::from("out_a%2Fb=..%2Fx")
+        );
+        assert_eq!(path("/a", serde_json::json!([1, "b"])), PathBuf::from("out_a=__array__%5B1%2C%22b%22%5D.json"));
+    }
+
+    #[test]
+    fn test_partition_names_are_distinct() {
+        let values = [
+            None,
+            Some(Value::Null),
+            Some(Value::from("null")),
+            Some(Value::from(1)),
+            Some(Value::from("1")),
+            Some(Value::from(true)),
+            Some(Value::from("true")),
+            Some(Value::from("__missing__")),
+            Some(Value::from("__null__")),
+        ];
+        let names: Vec<String> = values.iter().map(|value| partition_name(value.as_ref())).collect();
+        assert_eq!(
+            names,
+            [
+                "__missing__",
+                "__null__",
+                "null",
+                "__number__1",
+                "1",
+                "__boolean__true",
+                "true",
+                "%5F_missing__",
+                "%5F_null__"
+            ]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Reports of the JSON types each field takes across converted records.
+
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// What to profile, and where the report goes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileOptions {
+    /// Profile nested fields too (`address.city`, and `tags[]` for array items), not only
+    /// top-level ones.
+    pub deep: bool,
+    /// File to write the report to; `None` writes it to stderr.
+    pub output: Option<String>,
+}
+
+/// Counts the JSON types seen at each field path, one record at a time, so a profile can be
+/// built while streaming.
+#[derive(Debug, Clone, Default)]
+pub struct FieldProfile {
+    deep: bool,
+    records: u64,
+    fields: BTreeMap<String, BTreeMap<&'static str, u64>>,
+}
+
+impl FieldProfile {
+    pub fn new(deep: bool) -> Self {
+        FieldProfile {
+            deep,
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, record: &Value) {
+        self.records += 1;
+        if let Value::Object(fields) = record {
+            for (name, value) in fields {
+                self.add_value(name.clone(), value);
+            }
+        }
+    }
+
+    fn add_value(&mut self, path: String, value: &Value) {
+        if self.deep {
+            match value {
+                Value::Object(fields) => {
+                    for (name, child) in fields {
+                        self.add_value(format!("{}.{}", path, name), child);
+                    }
+                }
+                Value::Array(items) => {
+                    for item in items {
+                        self.add_value(format!("{}[]", path), item);
+                    }
+                }
+                _ => {}
+            }
+        }
+        *self.fields.entry(path).or_default().entry(json_type(value)).or_default() += 1;
+    }
+
+    /// The report: `{"records": n, "fields": {"<path>": {"<type>": count, ...}, ...}}`, with
+    /// fields sorted by path. A top-level field absent from some records also counts those
+    /// records as `missing`.
+    pub fn to_json(&self) -> Value {
+        let mut fields = Map::new();
+        for (path, types) in &self.fields {
+            let mut counts: Map<String, Value> =
+                types.iter().map(|(name, count)| (name.to_string(), Value::from(*count))).collect();
+            let is_top_level = !path.contains(['.', '[']);
+            let seen: u64 = types.values().sum();
+            if is_top_level && seen < self.records {
+                counts.insert("missing".to_string(), Value::from(self.records - seen));
+            }
+            fields.insert(path.clone(), Value::Object(counts));
+        }
+        json!({"records": self.records, "fields": fields})
+    }
+}
+
+/// Profiles a set of converted records in one go; see [`FieldProfile::to_json`].
+pub fn profile_records<'a>(records: impl IntoIterator<Item = &'a Value>, deep: bool) -> Value {
+    let mut profile = FieldProfile::new(deep);
+    for record in records {
+        profile.add(record);
+    }
+    profile.to_json()
+}
+
+pub(crate) fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_top_level_fields() {
+        let records = [
+            json!({"name": "Alice", "age": 28, "address": {"city": "Paris"}}),
+            json!({"name": "Bob", "age": null, "address": null}),
+            json!({"name": "Carol"}),
+        ];
+        assert_eq!(
+            profile_records(&records, false),
+            json!({
+                "records": 3,
+                "fields": {
+                    "address": {"object": 1, "null": 1, "missing": 1},
+                    "age": {"number": 1, "null": 1, "missing": 1},
+                    "name": {"string": 3},
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_profile_deep() {
+        let records = [
+            json!({"address": {"city": "Paris"}, "tags": ["a", 1]}),
+            json!({"address": null, "tags": []}),
+        ];
+        assert_eq!(
+            profile_records(&records, true)["fields"],
+            json!({
+                "address": {"null": 1, "object": 1},
+                "address.city": {"string": 1},
+                "tags": {"array": 2},
+                "tags[]": {"number": 1, "string": 1},
+            })
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Reading only the blocks of a container that start within a byte range, so a large file
+//! can be split between workers.
+
+use crate::error::{io_error, ConvertError, Result};
+use crate::Input;
+use apache_avro::schema::Schema;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+
+const SYNC_SIZE: usize = 16;
+
+/// A byte range `START:END` of an Avro container, aligned to the sync markers that end each
+/// block.
+///
+/// A block belongs to the range when the sync marker just before it (the header's, for the
+/// first block) starts at or after `start` and before `end`. A block starting in the range
+/// is read to its end even when that lies past `end`, so ranges that meet, like `0:1000` and
+/// `1000:2000`, together cover every block exactly once. The header is always read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRange {
+    pub start: u64,
+    /// Exclusive; `u64::MAX` reads to the end of the file.
+    pub end: u64,
+}
+
+impl std::str::FromStr for BlockRange {
+    type Err = String;
+
+    /// Parses `START:END`, or `START:` to read to the end of the file.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((start, end)) = s.split_once(':') else {
+            return Err(format!("expected `START:END`, got `{}`", s));
+        };
+        let offset = |offset: &str| {
+            offset.trim().parse::<u64>().map_err(|_| format!("expected a byte offset, got `{}`", offset))
+        };
+        let start = offset(start)?;
+        let end = if end.trim().is_empty() { u64::MAX } else { offset(end)? };
+        if end < start {
+            return Err(format!("range end {} is before its start {}", end, start));
+        }
+        Ok(BlockRange { start, end })
+    }
+}
+
+/// Opens a container file so that reading it yields its header followed by only the blocks
+/// in `range`, which `Reader` then decodes like a whole container.
+pub(crate) fn open_block_range(
+    input: &Input,
+    range: BlockRange,
+    gzip: bool,
+    buffer_size: usize,
+) -> Result<Box<dyn Read>> {
+    let path = match input {
+        Input::Path(path) if !gzip && !input.is_gzip() => path,
+        _ => {
+            return Err(ConvertError::InvalidOptions(format!(
+                "A block range needs an uncompressed container file, not {}",
+                input
+            )))
+        }
+    };
+    let file = File::open(path).map_err(|err| io_error(format!("Failed to open input file {}", path), err))?;
+    let mut file = BufReader::with_capacity(buffer_size, file);
+
+    let Some((header, sync)) = read_header(&mut file)? else {
+        // Not a container; let `Reader` explain what's wrong from the start of the file
+        file.seek(SeekFrom::Start(0))?;
+        return Ok(Box::new(file));
+    };
+    let header_sync = (header.len() - SYNC_SIZE) as u64;
+    let first = if range.start <= header_sync {
+        Some(header_sync)
+    } else {
+        find_sync(&mut file, range.start, &sync)?
+    };
+    let blocks: Box<dyn Read> = match first {
+        Some(first) => {
+            let last = find_sync(&mut file, range.end.max(first), &sync)?;
+            let blocks_start = first + SYNC_SIZE as u64;
+            file.seek(SeekFrom::Start(blocks_start))?;
+            match last {
+                Some(last) => Box::new(file.take(last + SYNC_SIZE as u64 - blocks_start)),
+                None => Box::new(file),
+            }
+        }
+        None => Box::new(io::empty()),
+    };
+    Ok(Box::new(io::Cursor::new(header).chain(blocks)))
+}
+
+/// Reads the container header: the magic, the metadata map and the sync marker. Returns the
+/// header's bytes and its sync marker, or `None` if the input isn't a container.
+fn read_header(file: &mut BufReader<File>) -> Result<Option<(Vec<u8>, [u8; SYNC_SIZE])>> {
+    let mut magic = [0u8; 4];
+    if file.read_exact(&mut magic).is_err() || magic != *b"Obj\x01" {
+        return Ok(None);
+    }
+    apache_avro::from_avro_datum(&Schema::Map(Box::new(Schema::Bytes)), file, None)?;
+    let mut sync = [0u8; SYNC_SIZE];
+    file.read_exact(&mut sync)?;
+
+    let header_len = file.stream_position()?;
+    let mut header = vec![0u8; header_len as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    Ok(Some((header, sync)))
+}
+
+/// The position of the first sync marker starting at or after `from`, or `None` if there is
+/// none before the end of the file.
+fn find_sync<R: Read + Seek>(reader: &mut R, from: u64, sync: &[u8; SYNC_SIZE]) -> Result<Option<u64>> {
+    // Seeking far past the end, e.g. to an open-ended range's `u64::MAX`, is an error
+    if from >= reader.seek(SeekFrom::End(0))? {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(from))?;
+    let mut window = Vec::new();
+    let mut window_start = from;
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        window.extend_from_slice(&chunk[..read]);
+        if let Some(offset) = window.windows(SYNC_SIZE).position(|bytes| bytes == sync) {
+            return Ok(Some(window_start + offset as u64));
+        }
+        // Keep enough of the tail to match a marker split across reads
+        let keep = window.len().min(SYNC_SIZE - 1);
+        window_start += (window.len() - keep) as u64;
+        window.drain(..window.len() - keep);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_block_range() {
+        assert_eq!("0:1000".parse(), Ok(BlockRange { start: 0, end: 1000 }));
+        assert_eq!("1000:".parse(), Ok(BlockRange { start: 1000, end: u64::MAX }));
+        assert!("1000".parse::<BlockRange>().is_err());
+        assert!("2000:1000".parse::<BlockRange>().is_err());
+        assert!("a:b".parse::<BlockRange>().is_err());
+    }
+
+    #[test]
+    fn test_find_sync_across_reads() {
+        let sync = [7u8; SYNC_SIZE];
+        let mut bytes = vec![0u8; 64 * 1024 - 5];
+        bytes.extend(sync);
+        bytes.extend([1u8; 10]);
+        let mut reader = io::Cursor::new(bytes);
+        assert_eq!(find_sync(&mut reader, 0, &sync).unwrap(), Some(64 * 1024 - 5));
+        assert_eq!(find_sync(&mut reader, 64 * 1024, &sync).unwrap(), None);
+    }
+}
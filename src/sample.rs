@@ -0,0 +1,117 @@
+//! Uniform random sampling of records from a stream of unknown length.
+
+use std::hash::{BuildHasher, Hasher};
+
+/// Keeps a uniform random sample of records, in place of all of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sample {
+    /// How many records to keep.
+    pub size: usize,
+    /// Seed for the random choices, so a run can be repeated with the same sample. A fresh
+    /// seed is drawn when unset.
+    pub seed: Option<u64>,
+}
+
+/// The sample being collected by reservoir sampling (Algorithm R): the first `size` items
+/// fill the reservoir, then the `n`th item replaces a random one with probability
+/// `size / n`. Each item ends up kept with the same probability, having read the stream
+/// once while holding only `size` items.
+pub(crate) struct Reservoir<T> {
+    size: usize,
+    seen: u64,
+    /// Each kept item with its position in the stream.
+    items: Vec<(u64, T)>,
+    rng: SplitMix64,
+}
+
+impl<T> Reservoir<T> {
+    pub(crate) fn new(sample: &Sample) -> Self {
+        let seed = sample.seed.unwrap_or_else(random_seed);
+        Reservoir {
+            size: sample.size,
+            seen: 0,
+            items: Vec::new(),
+            rng: SplitMix64(seed),
+        }
+    }
+
+    pub(crate) fn add(&mut self, item: T) {
+        self.seen += 1;
+        if self.items.len() < self.size {
+            self.items.push((self.seen, item));
+            return;
+        }
+        let slot = self.rng.below(self.seen);
+        if slot < self.size as u64 {
+            self.items[slot as usize] = (self.seen, item);
+        }
+    }
+
+    /// The sampled items, in the order they were added.
+    pub(crate) fn into_items(mut self) -> Vec<T> {
+        self.items.sort_unstable_by_key(|(position, _)| *position);
+        self.items.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+/// A seed that differs from run to run, taken from the standard library's randomly keyed
+/// hasher rather than pulling in a dependency for it.
+fn random_seed() -> u64 {
+    std::collections::hash_map::RandomState::new().build_hasher().finish()
+}
+
+/// The SplitMix64 generator: tiny, fast and statistically sound enough for sampling. Being
+/// defined here, a given seed picks the same records in every version.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`, by scaling rather than rejection; the bias that leaves is far
+    /// too small to matter for streams shorter than 2^32 items.
+    fn below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(size: usize, seed: u64, len: usize) -> Vec<usize> {
+        let mut reservoir = Reservoir::new(&Sample { size, seed: Some(seed) });
+        for item in 0..len {
+            reservoir.add(item);
+        }
+        reservoir.into_items()
+    }
+
+    #[test]
+    fn test_reservoir_is_deterministic_for_a_seed() {
+        assert_eq!(sample(5, 42, 1000), sample(5, 42, 1000));
+        assert_ne!(sample(5, 42, 1000), sample(5, 43, 1000));
+        // Short streams are kept whole
+        assert_eq!(sample(5, 42, 3), vec![0, 1, 2]);
+        assert_eq!(sample(0, 42, 3), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_reservoir_is_uniform() {
+        let mut counts = [0; 10];
+        for seed in 0..10_000 {
+            for item in sample(3, seed, 10) {
+                counts[item] += 1;
+            }
+        }
+        // Each item is expected 3000 times
+        for count in counts {
+            assert!((2800..3200).contains(&count), "{:?}", counts);
+        }
+    }
+}
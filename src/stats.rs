@@ -0,0 +1,165 @@
+//! Summaries of a conversion printed to stderr: the logical types found in decoded
+//! records, and how long the conversion took.
+
+use apache_avro::types::Value as AvroValue;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Tallies how many values of each logical type (`date`, `decimal`, `uuid`, ...) appear
+/// across a set of records, at any depth. Built one record at a time while converting.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogicalTypeStats {
+    records: u64,
+    counts: BTreeMap<&'static str, u64>,
+}
+
+impl LogicalTypeStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, record: &AvroValue) {
+        self.records += 1;
+        // Walked with a stack rather than recursion, so deep records are no risk
+        let mut pending = vec![record];
+        while let Some(value) = pending.pop() {
+            match value {
+                AvroValue::Union(_, value) => pending.push(value),
+                AvroValue::Array(items) => pending.extend(items),
+                AvroValue::Map(entries) => pending.extend(entries.values()),
+                AvroValue::Record(fields) => pending.extend(fields.iter().map(|(_, value)| value)),
+                _ => {
+                    if let Some(name) = logical_type_name(value) {
+                        *self.counts.entry(name).or_default() += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// How many records were added.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// How many values of each logical type were found, by the type's name in the Avro
+    /// specification. Types that never appeared are absent.
+    pub fn counts(&self) -> &BTreeMap<&'static str, u64> {
+        &self.counts
+    }
+}
+
+/// The summary printed by `--stats`: the record count, then one line per logical type.
+impl fmt::Display for LogicalTypeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Records: {}", self.records)?;
+        if self.counts.is_empty() {
+            return write!(f, "Logical types: none");
+        }
+        write!(f, "Logical types:")?;
+        for (name, count) in &self.counts {
+            write!(f, "\n  {}: {}", name, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// How long a conversion took and how fast it went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// Wall time from opening the first input to finishing the output.
+    pub elapsed: Duration,
+    /// Records read, including any skipped or filtered out.
+    pub records: u64,
+    /// Combined size of the inputs, when they are all files.
+    pub input_bytes: Option<u64>,
+}
+
+/// The summary printed by `--timing`: elapsed seconds, then records and input bytes with
+/// their rates per second.
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // A conversion too quick to measure still gets a finite rate
+        let seconds = self.elapsed.as_secs_f64().max(1e-9);
+        writeln!(f, "Elapsed: {:.3}s", self.elapsed.as_secs_f64())?;
+        write!(f, "Records: {} ({:.0} records/s)", self.records, self.records as f64 / seconds)?;
+        if let Some(bytes) = self.input_bytes {
+            write!(f, "\nInput:   {} bytes ({:.0} bytes/s)", bytes, bytes as f64 / seconds)?;
+        }
+        Ok(())
+    }
+}
+
+fn logical_type_name(value: &AvroValue) -> Option<&'static str> {
+    let name = match value {
+        AvroValue::Date(_) => "date",
+        AvroValue::Decimal(_) => "decimal",
+        AvroValue::TimeMillis(_) => "time-millis",
+        AvroValue::TimeMicros(_) => "time-micros",
+        AvroValue::TimestampMillis(_) => "timestamp-millis",
+        AvroValue::TimestampMicros(_) => "timestamp-micros",
+        AvroValue::LocalTimestampMillis(_) => "local-timestamp-millis",
+        AvroValue::LocalTimestampMicros(_) => "local-timestamp-micros",
+        AvroValue::Duration(_) => "duration",
+        AvroValue::Uuid(_) => "uuid",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_logical_type_stats() {
+        let mut stats = LogicalTypeStats::new();
+        stats.add(&AvroValue::Record(vec![
+            ("day".to_string(), AvroValue::Date(19_000)),
+            ("id".to_string(), AvroValue::Uuid("00000000-0000-0000-0000-000000000000".parse().unwrap())),
+            ("amount".to_string(), AvroValue::Union(1, Box::new(AvroValue::Decimal(vec![1u8].into())))),
+        ]));
+        stats.add(&AvroValue::Record(vec![
+            ("day".to_string(), AvroValue::Date(19_001)),
+            ("id".to_string(), AvroValue::String("not a uuid".to_string())),
+            (
+                "history".to_string(),
+                AvroValue::Map(HashMap::from([(
+                    "first".to_string(),
+                    AvroValue::Array(vec![AvroValue::Date(1), AvroValue::TimestampMillis(0)]),
+                )])),
+            ),
+        ]));
+
+        assert_eq!(stats.records(), 2);
+        assert_eq!(
+            stats.counts(),
+            &BTreeMap::from([("date", 3), ("decimal", 1), ("timestamp-millis", 1), ("uuid", 1)])
+        );
+        assert_eq!(
+            stats.to_string(),
+            "Records: 2\nLogical types:\n  date: 3\n  decimal: 1\n  timestamp-millis: 1\n  uuid: 1"
+        );
+        assert_eq!(LogicalTypeStats::new().to_string(), "Records: 0\nLogical types: none");
+    }
+
+    #[test]
+    fn test_timing_summary() {
+        let timing = Timing {
+            elapsed: Duration::from_millis(2500),
+            records: 1000,
+            input_bytes: Some(50_000),
+        };
+        assert_eq!(
+            timing.to_string(),
+            "Elapsed: 2.500s\nRecords: 1000 (400 records/s)\nInput:   50000 bytes (20000 bytes/s)"
+        );
+        let timing = Timing {
+            input_bytes: None,
+            ..timing
+        };
+        assert_eq!(timing.to_string(), "Elapsed: 2.500s\nRecords: 1000 (400 records/s)");
+    }
+}
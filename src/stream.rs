@@ -0,0 +1,97 @@
+//! Converting Avro read from a tokio [`AsyncRead`] into a [`Stream`] of JSON records, for
+//! async services.
+//!
+//! `apache_avro::Reader` only reads from a blocking [`Read`](std::io::Read), so the
+//! container is decoded on tokio's blocking thread pool rather than on the runtime. The
+//! async reader is bridged to it with [`SyncIoBridge`], and records come back through a
+//! bounded channel, so decoding stays at most [`RECORDS_IN_FLIGHT`] records ahead of the
+//! consumer and stops once the stream is dropped.
+
+use crate::error::{ConvertError, Result};
+use crate::{avro_json_records_with_options, JsonOptions};
+use serde_json::Value;
+use tokio::io::AsyncRead;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::io::SyncIoBridge;
+
+pub use tokio_stream::Stream;
+
+/// How many decoded records may wait in the channel for the consumer.
+pub const RECORDS_IN_FLIGHT: usize = 64;
+
+/// The async counterpart of [`avro_json_records_with_options`]: reads an Avro container from
+/// `reader` and yields one JSON value per record.
+///
+/// Like it, the header is read before this returns, so an invalid container fails here, and a
+/// record that fails to decode is the stream's last item. Must be called from within a tokio
+/// runtime, whose blocking thread pool does the decoding.
+pub async fn convert_avro_to_json_async<R>(
+    reader: R,
+    options: &JsonOptions,
+) -> Result<impl Stream<Item = Result<Value>>>
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    let options = options.clone();
+    let (header_sender, header_receiver) = oneshot::channel();
+    let (record_sender, record_receiver) = mpsc::channel(RECORDS_IN_FLIGHT);
+    let reader = SyncIoBridge::new(reader);
+    tokio::task::spawn_blocking(move || {
+        let records = match avro_json_records_with_options(reader, &options) {
+            Ok(records) => {
+                let _ = header_sender.send(Ok(()));
+                records
+            }
+            Err(err) => {
+                let _ = header_sender.send(Err(err));
+                return;
+            }
+        };
+        for record in records {
+            // Sending only fails once the stream is dropped, and then nobody wants the rest
+            if record_sender.blocking_send(record).is_err() {
+                return;
+            }
+        }
+    });
+
+    match header_receiver.await {
+        Ok(header) => header?,
+        // The blocking task can only go away without answering by panicking
+        Err(_) => {
+            return Err(ConvertError::Io(std::io::Error::other("Avro decoding task stopped unexpectedly")));
+        }
+    }
+    Ok(ReceiverStream::new(record_receiver))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().build().unwrap()
+    }
+
+    #[test]
+    fn test_convert_avro_to_json_async() {
+        let avro = std::fs::read("test_users.avro").unwrap();
+        let records: Vec<Value> = runtime().block_on(async {
+            let stream = convert_avro_to_json_async(std::io::Cursor::new(avro), &JsonOptions::default()).await;
+            stream.unwrap().map(|record| record.unwrap()).collect().await
+        });
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["name"], Value::from("Alice Johnson"));
+        assert_eq!(records[2]["email"], Value::Null);
+    }
+
+    #[test]
+    fn test_convert_avro_to_json_async_rejects_invalid_containers() {
+        let result = runtime().block_on(async {
+            convert_avro_to_json_async(std::io::Cursor::new(b"not avro".to_vec()), &JsonOptions::default()).await
+        });
+        assert!(matches!(result, Err(ConvertError::AvroRead(_))));
+    }
+}
@@ -0,0 +1,561 @@
+//! Reshaping of already-converted JSON records.
+
+use crate::error::{ConvertError, Result};
+use serde_json::{Map, Value};
+
+/// Splits a comma-separated list of dotted paths (`name,address.city,tags.0`) into
+/// path segments.
+pub fn parse_field_paths(paths: &str) -> Vec<Vec<String>> {
+    paths
+        .split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(|path| path.split('.').map(str::to_string).collect())
+        .collect()
+}
+
+/// Prunes a record down to the given dotted paths, keeping their nesting.
+///
+/// Numeric segments index into arrays (`tags.0`). Paths that don't exist are omitted, and
+/// values that aren't JSON objects pass through unchanged.
+pub fn project_value(value: &Value, paths: &[Vec<String>]) -> Value {
+    if !value.is_object() {
+        return value.clone();
+    }
+
+    let paths: Vec<&[String]> = paths.iter().map(Vec::as_slice).collect();
+    project(value, &paths).unwrap_or_else(|| Value::Object(Map::new()))
+}
+
+fn project(value: &Value, paths: &[&[String]]) -> Option<Value> {
+    // A path that ends here selects the whole value, which covers any longer paths too
+    if paths.iter().any(|path| path.is_empty()) {
+        return Some(value.clone());
+    }
+
+    // Group the remaining paths by their first segment, in the order they were given
+    let mut groups: Vec<(&str, Vec<&[String]>)> = Vec::new();
+    for path in paths {
+        let (head, tail) = (path[0].as_str(), &path[1..]);
+        match groups.iter_mut().find(|(key, _)| *key == head) {
+            Some((_, tails)) => tails.push(tail),
+            None => groups.push((head, vec![tail])),
+        }
+    }
+
+    match value {
+        Value::Object(obj) => {
+            let mut projected = Map::new();
+            for (key, tails) in groups {
+                if let Some(child) = obj.get(key).and_then(|child| project(child, &tails)) {
+                    projected.insert(key.to_string(), child);
+                }
+            }
+            (!projected.is_empty()).then_some(Value::Object(projected))
+        }
+        Value::Array(arr) => {
+            let projected: Vec<Value> = groups
+                .into_iter()
+                .filter_map(|(key, tails)| {
+                    let item = arr.get(key.parse::<usize>().ok()?)?;
+                    project(item, &tails)
+                })
+                .collect();
+            (!projected.is_empty()).then_some(Value::Array(projected))
+        }
+        _ => None,
+    }
+}
+
+/// How [`flatten_value_with_options`] joins nested keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlattenOptions {
+    /// Placed between the keys of nested objects.
+    pub separator: String,
+    /// Also flatten arrays, using element indexes as keys (`tags.0`). Arrays are otherwise
+    /// kept intact.
+    pub index_arrays: bool,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        FlattenOptions {
+            separator: ".".to_string(),
+            index_arrays: false,
+        }
+    }
+}
+
+/// Flattens nested objects into a single level of joined keys, so `{"a":{"b":1}}` becomes
+/// `{"a.b":1}` with a `.` separator. Arrays are left intact.
+pub fn flatten_value(value: Value, separator: &str) -> Value {
+    let options = FlattenOptions {
+        separator: separator.to_string(),
+        ..Default::default()
+    };
+    flatten_value_with_options(value, &options)
+}
+
+/// Like [`flatten_value`], with control over how arrays are treated.
+///
+/// Empty objects (and empty arrays, when indexing them) have no leaves to flatten into, so
+/// they are kept as-is under their key rather than disappearing. Values that aren't JSON
+/// objects pass through unchanged.
+pub fn flatten_value_with_options(value: Value, options: &FlattenOptions) -> Value {
+    match value {
+        Value::Object(obj) => {
+            let mut flattened = Map::new();
+            for (key, child) in obj {
+                flatten_into(&mut flattened, key, child, options);
+            }
+            Value::Object(flattened)
+        }
+        other => other,
+    }
+}
+
+fn flatten_into(flattened: &mut Map<String, Value>, prefix: String, value: Value, options: &FlattenOptions) {
+    let children: Vec<(String, Value)> = match value {
+        Value::Object(obj) if !obj.is_empty() => obj.into_iter().collect(),
+        Value::Array(arr) if options.index_arrays && !arr.is_empty() => arr
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| (index.to_string(), item))
+            .collect(),
+        leaf => {
+            flattened.insert(prefix, leaf);
+            return;
+        }
+    };
+
+    for (key, child) in children {
+        flatten_into(flattened, format!("{}{}{}", prefix, options.separator, key), child, options);
+    }
+}
+
+/// Naming convention for JSON object keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyStyle {
+    /// Keep keys exactly as they are.
+    #[default]
+    None,
+    /// `httpStatus`, `userId`.
+    Camel,
+    /// `http_status`, `user_id`.
+    Snake,
+}
+
+/// Rewrites a single key in the given style.
+///
+/// Words are split at `_`, `-` and spaces, at lowercase-to-uppercase changes, and before
+/// the last capital of an acronym (`HTTPStatus` is `HTTP` + `Status`). Leading underscores
+/// are kept.
+pub fn rename_key(key: &str, style: KeyStyle) -> String {
+    if style == KeyStyle::None {
+        return key.to_string();
+    }
+
+    let body = key.trim_start_matches('_');
+    let mut renamed = key[..key.len() - body.len()].to_string();
+    for (index, word) in split_words(body).iter().enumerate() {
+        let word = word.to_lowercase();
+        match style {
+            KeyStyle::Snake if index > 0 => renamed.push('_'),
+            KeyStyle::Camel if index > 0 => {
+                let mut chars = word.chars();
+                if let Some(first) = chars.next() {
+                    renamed.extend(first.to_uppercase());
+                    renamed.push_str(chars.as_str());
+                }
+                continue;
+            }
+            _ => {}
+        }
+        renamed.push_str(&word);
+    }
+    renamed
+}
+
+fn split_words(key: &str) -> Vec<String> {
+    let chars: Vec<char> = key.chars().collect();
+    let mut words = Vec::new();
+    let mut current = String::new();
+
+    for (index, &c) in chars.iter().enumerate() {
+        if matches!(c, '_' | '-' | ' ') {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if c.is_uppercase() && !current.is_empty() {
+            let prev = chars[index - 1];
+            let next_is_lower = chars.get(index + 1).is_some_and(|next| next.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_is_lower) {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Recursively rewrites every object key in the given style.
+///
+/// This can't tell record fields from Avro map keys, which are data rather than schema
+/// names; conversion itself can, via `JsonOptions::key_style`.
+pub fn rename_keys(value: Value, style: KeyStyle) -> Value {
+    match value {
+        Value::Object(obj) => Value::Object(
+            obj.into_iter()
+                .map(|(key, child)| (rename_key(&key, style), rename_keys(child, style)))
+                .collect(),
+        ),
+        Value::Array(arr) => Value::Array(arr.into_iter().map(|item| rename_keys(item, style)).collect()),
+        other => other,
+    }
+}
+
+/// Recursively sorts object keys alphabetically, record fields included, so output doesn't
+/// depend on the order fields were produced in.
+pub fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(obj) => {
+            let mut entries: Vec<(String, Value)> = obj.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(entries.into_iter().map(|(key, child)| (key, sort_keys(child))).collect())
+        }
+        Value::Array(arr) => Value::Array(arr.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
+/// Recursively drops object entries whose value is null. Nulls inside arrays are kept, since
+/// they are positional; see [`omit_nulls_with_options`] to drop those too.
+pub fn omit_nulls(value: Value) -> Value {
+    omit_nulls_with_options(value, false)
+}
+
+/// Like [`omit_nulls`], also dropping null array items when `in_arrays` is set.
+pub fn omit_nulls_with_options(value: Value, in_arrays: bool) -> Value {
+    match value {
+        Value::Object(obj) => Value::Object(
+            obj.into_iter()
+                .filter(|(_, child)| !child.is_null())
+                .map(|(key, child)| (key, omit_nulls_with_options(child, in_arrays)))
+                .collect(),
+        ),
+        Value::Array(arr) => Value::Array(
+            arr.into_iter()
+                .filter(|item| !(in_arrays && item.is_null()))
+                .map(|item| omit_nulls_with_options(item, in_arrays))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Recursively replaces every null, including array items and a null record itself, with the
+/// string `repr`, e.g. `\N` for PostgreSQL `COPY`.
+pub fn replace_nulls(value: Value, repr: &str) -> Value {
+    match value {
+        Value::Null => Value::String(repr.to_string()),
+        Value::Object(obj) => {
+            Value::Object(obj.into_iter().map(|(key, child)| (key, replace_nulls(child, repr))).collect())
+        }
+        Value::Array(arr) => Value::Array(arr.into_iter().map(|item| replace_nulls(item, repr)).collect()),
+        other => other,
+    }
+}
+
+/// Collects records into a single object keyed by their value at a JSON Pointer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyBy {
+    /// JSON Pointer (RFC 6901) to the key, e.g. `/id`.
+    pub pointer: String,
+    /// Let a later record replace an earlier one with the same key instead of failing.
+    pub last_wins: bool,
+}
+
+/// Builds `{"<key>": record, ...}` from `records`, in order, keying each by its value at
+/// `key_by.pointer`. String keys are used as they are; any other value is keyed by its
+/// compact JSON text, so `42` becomes `"42"`.
+///
+/// Fails with [`ConvertError::MissingKey`] for a record the pointer doesn't resolve in, and
+/// with [`ConvertError::DuplicateKey`] when two records share a key, unless
+/// `key_by.last_wins` is set.
+pub fn records_to_keyed_object(records: impl IntoIterator<Item = Value>, key_by: &KeyBy) -> Result<Value> {
+    let mut object = Map::new();
+    for (index, record) in records.into_iter().enumerate() {
+        let key = match record.pointer(&key_by.pointer) {
+            Some(Value::String(key)) => key.clone(),
+            Some(key) => key.to_string(),
+            None => {
+                return Err(ConvertError::MissingKey {
+                    index: index as u64,
+                    pointer: key_by.pointer.clone(),
+                })
+            }
+        };
+        if object.contains_key(&key) && !key_by.last_wins {
+            return Err(ConvertError::DuplicateKey(key));
+        }
+        object.insert(key, record);
+    }
+    Ok(Value::Object(object))
+}
+
+/// Prints a single value from each record instead of the whole record.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extract {
+    /// JSON Pointer (RFC 6901) to the value, e.g. `/user/email`.
+    pub pointer: String,
+    /// Skip records where the pointer doesn't resolve instead of printing an empty line.
+    pub skip_missing: bool,
+}
+
+/// The value at a JSON Pointer in `value`, or `None` when the pointer doesn't resolve.
+pub fn extract_pointer(value: &Value, pointer: &str) -> Option<Value> {
+    value.pointer(pointer).cloned()
+}
+
+/// The line printed for an extracted value: a string's text without quotes, any other value
+/// as compact JSON, and nothing for a missing one.
+pub fn extracted_line(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+        None => String::new(),
+    }
+}
+
+/// Wraps all records in a single object alongside the schema they were written with and
+/// their count: `{"schema": {...}, "count": N, "records": [...]}`. The fields default to
+/// those names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub schema_key: String,
+    pub count_key: String,
+    pub records_key: String,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope {
+            schema_key: "schema".to_string(),
+            count_key: "count".to_string(),
+            records_key: "records".to_string(),
+        }
+    }
+}
+
+/// Builds the envelope object for `records`, with its fields in schema, count, records order.
+pub fn records_to_envelope(schema: Value, records: Vec<Value>, envelope: &Envelope) -> Value {
+    let mut object = Map::new();
+    object.insert(envelope.schema_key.clone(), schema);
+    object.insert(envelope.count_key.clone(), Value::from(records.len()));
+    object.insert(envelope.records_key.clone(), Value::Array(records));
+    Value::Object(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extract_pointer() {
+        let record = json!({"user": {"email": "alice@example.com", "age": 28, "tags": ["a"]}});
+        let email = extract_pointer(&record, "/user/email");
+        assert_eq!(email, Some(json!("alice@example.com")));
+        assert_eq!(extracted_line(email.as_ref()), "alice@example.com");
+        assert_eq!(extracted_line(extract_pointer(&record, "/user/tags").as_ref()), "[\"a\"]");
+        assert_eq!(extracted_line(extract_pointer(&record, "/user/age").as_ref()), "28");
+        assert_eq!(extract_pointer(&record, "/user/phone"), None);
+        assert_eq!(extracted_line(None), "");
+    }
+
+    #[test]
+    fn test_parse_field_paths() {
+        assert_eq!(
+            parse_field_paths("name, address.city,,tags.0"),
+            vec![
+                vec!["name".to_string()],
+                vec!["address".to_string(), "city".to_string()],
+                vec!["tags".to_string(), "0".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_project_value() {
+        let record = json!({
+            "name": "Alice",
+            "age": 30,
+            "address": {"city": "Paris", "zip": "75001"},
+            "tags": ["a", "b", "c"],
+        });
+        let paths = parse_field_paths("name,address.city,tags.1,missing,address.country");
+        assert_eq!(
+            project_value(&record, &paths),
+            json!({"name": "Alice", "address": {"city": "Paris"}, "tags": ["b"]})
+        );
+
+        // Selecting a parent keeps everything beneath it
+        let paths = parse_field_paths("address,address.city");
+        assert_eq!(
+            project_value(&record, &paths),
+            json!({"address": {"city": "Paris", "zip": "75001"}})
+        );
+    }
+
+    #[test]
+    fn test_project_non_object_passes_through() {
+        let paths = parse_field_paths("name");
+        assert_eq!(project_value(&json!(42), &paths), json!(42));
+        assert_eq!(project_value(&json!(["x"]), &paths), json!(["x"]));
+    }
+
+    #[test]
+    fn test_flatten_value() {
+        let record = json!({
+            "id": 1,
+            "address": {"city": "Paris", "geo": {"lat": 48.8, "lon": 2.3}},
+            "tags": ["a", {"b": 1}],
+            "attributes": {},
+        });
+        assert_eq!(
+            flatten_value(record.clone(), "."),
+            json!({
+                "id": 1,
+                "address.city": "Paris",
+                "address.geo.lat": 48.8,
+                "address.geo.lon": 2.3,
+                "tags": ["a", {"b": 1}],
+                "attributes": {},
+            })
+        );
+
+        let options = FlattenOptions {
+            separator: "_".to_string(),
+            index_arrays: true,
+        };
+        assert_eq!(
+            flatten_value_with_options(record, &options),
+            json!({
+                "id": 1,
+                "address_city": "Paris",
+                "address_geo_lat": 48.8,
+                "address_geo_lon": 2.3,
+                "tags_0": "a",
+                "tags_1_b": 1,
+                "attributes": {},
+            })
+        );
+    }
+
+    #[test]
+    fn test_flatten_nested_maps() {
+        let record = json!({"counts": {"2024": {"jan": {"views": 3}}, "2025": {}}, "empty": []});
+        let options = FlattenOptions {
+            index_arrays: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            flatten_value_with_options(record, &options),
+            json!({"counts.2024.jan.views": 3, "counts.2025": {}, "empty": []})
+        );
+        assert_eq!(flatten_value(json!({}), "."), json!({}));
+        assert_eq!(flatten_value(json!([{"a": {"b": 1}}]), "."), json!([{"a": {"b": 1}}]));
+    }
+
+    #[test]
+    fn test_rename_key() {
+        assert_eq!(rename_key("user_id", KeyStyle::Camel), "userId");
+        assert_eq!(rename_key("userId", KeyStyle::Snake), "user_id");
+        assert_eq!(rename_key("HTTPStatus", KeyStyle::Snake), "http_status");
+        assert_eq!(rename_key("HTTPStatus", KeyStyle::Camel), "httpStatus");
+        assert_eq!(rename_key("http_status", KeyStyle::Camel), "httpStatus");
+        assert_eq!(rename_key("address2City", KeyStyle::Snake), "address2_city");
+        assert_eq!(rename_key("_private_field", KeyStyle::Camel), "_privateField");
+        assert_eq!(rename_key("already-kebab", KeyStyle::Snake), "already_kebab");
+        assert_eq!(rename_key("HTTPStatus", KeyStyle::None), "HTTPStatus");
+
+        // Converting there and back lands on the same name
+        for key in ["created_at", "http_status", "x"] {
+            assert_eq!(rename_key(&rename_key(key, KeyStyle::Camel), KeyStyle::Snake), key);
+        }
+    }
+
+    #[test]
+    fn test_rename_keys() {
+        let record = json!({"user_id": 1, "home_address": {"zip_code": "75001"}, "past_orders": [{"order_id": 7}]});
+        assert_eq!(
+            rename_keys(record, KeyStyle::Camel),
+            json!({"userId": 1, "homeAddress": {"zipCode": "75001"}, "pastOrders": [{"orderId": 7}]})
+        );
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let record = json!({"b": 1, "a": {"z": [{"y": 1, "x": 2}], "c": null}});
+        let sorted = serde_json::to_string(&sort_keys(record)).unwrap();
+        assert_eq!(sorted, r#"{"a":{"c":null,"z":[{"x":2,"y":1}]},"b":1}"#);
+    }
+
+    #[test]
+    fn test_omit_nulls() {
+        let record = json!({"id": 3, "email": null, "age": null, "scores": [1, null], "address": {"zip": null}});
+        assert_eq!(omit_nulls(record.clone()), json!({"id": 3, "scores": [1, null], "address": {}}));
+        assert_eq!(
+            omit_nulls_with_options(record, true),
+            json!({"id": 3, "scores": [1], "address": {}})
+        );
+    }
+
+    #[test]
+    fn test_replace_nulls() {
+        let record = json!({"id": 3, "email": null, "scores": [1, null], "address": {"zip": null}});
+        assert_eq!(
+            replace_nulls(record.clone(), "NULL"),
+            json!({"id": 3, "email": "NULL", "scores": [1, "NULL"], "address": {"zip": "NULL"}})
+        );
+        assert_eq!(
+            replace_nulls(record, ""),
+            json!({"id": 3, "email": "", "scores": [1, ""], "address": {"zip": ""}})
+        );
+    }
+
+    #[test]
+    fn test_records_to_keyed_object() {
+        let records = vec![
+            json!({"id": "a", "n": 1}),
+            json!({"id": 2, "n": 2}),
+            json!({"id": "a", "n": 3}),
+        ];
+        let key_by = KeyBy {
+            pointer: "/id".to_string(),
+            last_wins: false,
+        };
+        assert!(matches!(
+            records_to_keyed_object(records.clone(), &key_by),
+            Err(ConvertError::DuplicateKey(key)) if key == "a"
+        ));
+
+        let key_by = KeyBy {
+            last_wins: true,
+            ..key_by
+        };
+        assert_eq!(
+            records_to_keyed_object(records, &key_by).unwrap(),
+            json!({"a": {"id": "a", "n": 3}, "2": {"id": 2, "n": 2}})
+        );
+        assert!(matches!(
+            records_to_keyed_object(vec![json!({"id": 1}), json!({})], &key_by),
+            Err(ConvertError::MissingKey { index: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+//! End-to-end checks of the command-line tool.
+
+use serde_json::Value;
+use std::process::Command;
+
+fn avro_to_json(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_avro-to-json"))
+        .args(args)
+        .output()
+        .expect("failed to run avro-to-json")
+}
+
+#[test]
+fn test_stdout_carries_only_json() {
+    let output = avro_to_json(&["-i", "test_users.avro"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 3);
+    for line in stdout.lines() {
+        serde_json::from_str::<Value>(line).expect("every stdout line is a JSON record");
+    }
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Conversion completed successfully!"));
+
+    let output = avro_to_json(&["-i", "test_users.avro", "--array", "--pretty"]);
+    assert!(output.status.success());
+    let records: Value = serde_json::from_slice(&output.stdout).expect("stdout is a single JSON array");
+    assert_eq!(records.as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn test_quiet_suppresses_status_message() {
+    let output = avro_to_json(&["-i", "test_users.avro", "--quiet"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 3);
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_quiet_suppresses_skip_reports() {
+    let output = avro_to_json(&["-i", "test_users.avro", "--on-error", "skip"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Skipped 0 records"));
+
+    let output = avro_to_json(&["-i", "test_users.avro", "--quiet", "--on-error", "skip"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_exit_codes() {
+    let output = avro_to_json(&["-i", "does_not_exist.avro", "--quiet"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("Error:"));
+
+    let output = avro_to_json(&["-i", "Cargo.toml"]);
+    assert_eq!(output.status.code(), Some(3));
+
+    // Options that can't be honored are neither I/O nor data errors
+    let output = avro_to_json(&["-i", "test_users.avro.gz", "--block-range", "0:100", "--quiet"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let schema = apache_avro::Schema::parse_str(
+        r#"{"type": "record", "name": "Reading", "fields": [{"name": "value", "type": "double"}]}"#,
+    )
+    .unwrap();
+    let mut writer = apache_avro::Writer::new(&schema, Vec::new());
+    writer.append(apache_avro::types::Value::Record(vec![("value".into(), f64::NAN.into())])).unwrap();
+    let nan = std::env::temp_dir().join(format!("avro-to-json-{}-nan.avro", std::process::id()));
+    std::fs::write(&nan, writer.into_inner().unwrap()).unwrap();
+    let output = avro_to_json(&["-i", nan.to_str().unwrap(), "--quiet", "--float-policy", "error"]);
+    std::fs::remove_file(&nan).unwrap();
+    assert_eq!(output.status.code(), Some(4));
+
+    let bytes = std::fs::read("test_users.avro").unwrap();
+    let truncated = std::env::temp_dir().join(format!("avro-to-json-{}-truncated.avro", std::process::id()));
+    std::fs::write(&truncated, &bytes[..bytes.len() - 30]).unwrap();
+    let output = avro_to_json(&["-i", truncated.to_str().unwrap(), "--quiet"]);
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("Warning:") && stderr.contains("after 0 complete records"), "{}", stderr);
+
+    // Skipping bad records doesn't hide a partial file
+    let output = avro_to_json(&["-i", truncated.to_str().unwrap(), "--quiet", "--on-error", "skip"]);
+    std::fs::remove_file(&truncated).unwrap();
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("ends partway through a block"), "{}", stderr);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_directory_input_skips_symlinked_directories() {
+    let dir = std::env::temp_dir().join(format!("avro-to-json-{}-dir", std::process::id()));
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::copy("test_users.avro", dir.join("nested/users.avro")).unwrap();
+    // A link back up the tree, which would recurse forever if followed
+    std::os::unix::fs::symlink(&dir, dir.join("nested/loop.avro")).unwrap();
+
+    let output = avro_to_json(&["-i", dir.to_str().unwrap(), "--quiet"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 3);
+}
+
+#[test]
+fn test_header_commands_read_one_input() {
+    let output = avro_to_json(&["-i", "test_users.avro", "--schema"]);
+    assert!(output.status.success());
+    let output = avro_to_json(&["-i", "test_users.avro", "-i", "test_users.avro", "--schema"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--schema reads a single input"));
+
+    let output = avro_to_json(&["-i", "test_users.avro", "-i", "test_users.avro", "--fingerprint", "rabin"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--fingerprint reads a single input"));
+
+    let output = avro_to_json(&["-i", "test_users.avro", "-i", "test_users.avro", "--metadata"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--metadata reads a single input"));
+}